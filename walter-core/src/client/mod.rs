@@ -1,33 +1,91 @@
-use std::io::Write;
+use serde::de::DeserializeOwned;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
 
-use crate::config::WalterConfig;
+use crate::cancel::CancelToken;
+use crate::config::{FileInfo, WalterConfig};
 use crate::encryptor::{decrypt_file, encrypt_file};
 use crate::sharder::Sharder;
 use crate::types::*;
 
+/// How often a running `walrus` process is checked for cancellation.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 pub struct WalrusClient {
     pub config: WalterConfig,
+    pub cancel: CancelToken,
 }
 
 impl WalrusClient {
     pub fn new(config: WalterConfig) -> Self {
-        WalrusClient { config }
+        WalrusClient {
+            config,
+            cancel: CancelToken::new(),
+        }
+    }
+
+    /// Makes uploads and downloads stop once `cancel` is cancelled.
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
     }
 
     pub async fn upload_file(
         &mut self,
         file_path: &str,
         password: Option<String>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        self.upload_file_with_progress(file_path, password, |_, _| {})
+            .await
+    }
+
+    /// Same as `upload_file`, calling `progress(uploaded, total)` with the
+    /// shard counts before the first shard and after every uploaded one.
+    ///
+    /// If the upload fails or is cancelled the file is decrypted again and
+    /// left out of the config, so it can simply be uploaded again.
+    pub async fn upload_file_with_progress(
+        &mut self,
+        file_path: &str,
+        password: Option<String>,
+        mut progress: impl FnMut(usize, usize),
     ) -> Result<bool, Box<dyn std::error::Error>> {
         let to_encrypt: bool = password.is_some();
-        if to_encrypt {
-            encrypt_file(file_path, file_path, &password.unwrap())?;
+        let sha256 = file_sha256(file_path)?;
+        if let Some(password) = &password {
+            encrypt_file(file_path, file_path, password)?;
         }
 
+        let blobs = match self.upload_shards(file_path, &mut progress).await {
+            Ok(blobs) => blobs,
+            Err(e) => {
+                if let Some(password) = &password {
+                    decrypt_file(file_path, file_path, password)?;
+                }
+                return Err(e);
+            }
+        };
+
+        self.config.save_change(|config| {
+            config.add_file(file_path, to_encrypt, blobs, Some(sha256));
+            Ok(())
+        })?;
+        Ok(true)
+    }
+
+    async fn upload_shards(
+        &self,
+        file_path: &str,
+        progress: &mut impl FnMut(usize, usize),
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
         let shards = Sharder::new(file_path, self.config.get_default_shard_size())?;
+        let total_shards = shards.total_shards;
         let mut blobs: Vec<String> = Vec::new();
+        progress(0, total_shards);
 
         for shard in shards {
+            self.cancel.check()?;
             let temp_file_path = std::env::temp_dir().join(format!(
                 "shard_{}.tmp",
                 std::time::SystemTime::now()
@@ -37,31 +95,50 @@ impl WalrusClient {
 
             std::fs::write(&temp_file_path, shard)?;
 
-            let blob_id = upload_blob(
+            let blob_id = upload_blob_cancellable(
                 temp_file_path.to_str().unwrap(),
                 self.config.get_default_epochs(),
+                &self.cancel,
             )
-            .await?;
-
-            blobs.push(blob_id);
+            .await;
             std::fs::remove_file(&temp_file_path)?;
+
+            blobs.push(blob_id?);
+            progress(blobs.len(), total_shards);
         }
 
-        self.config.add_file(file_path, to_encrypt, blobs);
-        self.config.save_config_file();
-        Ok(true)
+        Ok(blobs)
     }
 
     pub async fn download_file(
         &self,
         file_path: &str,
         password: Option<String>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        self.download_file_with_progress(file_path, file_path, password, |_, _| {})
+            .await
+    }
+
+    /// Same as `download_file`, writing the tracked `file_path` to
+    /// `destination` and calling `progress(downloaded, total)` with the shard
+    /// counts before the first shard and after every fetched one.
+    pub async fn download_file_with_progress(
+        &self,
+        file_path: &str,
+        destination: &str,
+        password: Option<String>,
+        mut progress: impl FnMut(usize, usize),
     ) -> Result<bool, Box<dyn std::error::Error>> {
         let to_decrypt: bool = password.is_some();
-        let blobs = self.config.get_file_blobs(file_path).unwrap();
+        let blobs = self
+            .config
+            .get_file_blobs(file_path)
+            .ok_or_else(|| format!("{} is not a tracked file", file_path))?;
         let mut file_data = Vec::new();
+        progress(0, blobs.len());
 
-        for blob in blobs {
+        for (index, blob) in blobs.iter().enumerate() {
+            self.cancel.check()?;
             let temp_file_path = std::env::current_dir()?.join(format!(
                 "{}.tmp",
                 std::time::SystemTime::now()
@@ -69,110 +146,235 @@ impl WalrusClient {
                     .as_nanos()
             ));
 
-            let success = download_blob(blob, &temp_file_path.to_str().unwrap()).await;
-            let success = success.unwrap();
+            let downloaded =
+                download_blob_cancellable(blob, temp_file_path.to_str().unwrap(), &self.cancel)
+                    .await;
+            if downloaded.is_err() {
+                // walrus may have written part of the shard before it stopped
+                let _ = std::fs::remove_file(&temp_file_path);
+            }
 
-            if !success {
+            if !downloaded? {
                 return Err("Failed to download blob".into());
             }
 
             let shard = std::fs::read(&temp_file_path)?;
             std::fs::remove_file(&temp_file_path)?;
             file_data.extend(shard);
+            progress(index + 1, blobs.len());
         }
 
-        let mut file = std::fs::File::create(file_path)?;
+        let mut file = std::fs::File::create(destination)?;
         file.write_all(&file_data.as_slice())?;
 
         if to_decrypt {
-            decrypt_file(file_path, file_path, &password.unwrap())?;
+            if let Err(e) = decrypt_file(destination, destination, &password.unwrap()) {
+                // Do not leave the still encrypted file behind
+                let _ = std::fs::remove_file(destination);
+                return Err(e);
+            }
         }
 
         Ok(true)
     }
+
+    /// Whether `destination` matches the checksum recorded when the tracked
+    /// `file_path` was uploaded, or `None` if no checksum was recorded.
+    pub fn verify_file(
+        &self,
+        file_path: &str,
+        destination: &str,
+    ) -> Result<Option<bool>, Box<dyn std::error::Error>> {
+        let expected = match self.config.get_files().get(file_path) {
+            Some(FileInfo {
+                sha256: Some(sha256),
+                ..
+            }) => sha256,
+            _ => return Ok(None),
+        };
+        Ok(Some(file_sha256(destination)? == *expected))
+    }
 }
 
-pub async fn upload_blob(
-    file_path: &str,
-    epochs: u16,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let command_json = serde_json::json!({
-        "command": {
-            "store": {
-                "file": file_path,
-                "epochs": epochs,
-            },
-        }
-    });
+fn file_sha256(file_path: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let digest = openssl::sha::sha256(&std::fs::read(file_path)?);
+    Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
 
-    let output = std::process::Command::new("walrus")
-        .arg("json")
-        .arg(command_json.to_string())
-        .output()
-        .expect("failed to execute process");
+/// Why a `walrus json` command failed.
+#[derive(Debug)]
+pub enum WalrusError {
+    /// `walrus` could not be started
+    Spawn(std::io::Error),
+    /// `walrus` exited with an error, carrying its stderr
+    Failed(String),
+    /// The output did not have the expected shape
+    Parse(serde_json::Error),
+    /// The command was cancelled and `walrus` killed
+    Cancelled,
+}
 
-    // che ck if output execution is successful gracefully
-    if !output.status.success() {
-        return Err("Failed to upload file to walrus".into());
+impl std::fmt::Display for WalrusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WalrusError::Spawn(err) => write!(f, "Failed to run walrus: {}", err),
+            WalrusError::Failed(stderr) => write!(f, "walrus failed: {}", stderr),
+            WalrusError::Parse(err) => write!(f, "Failed to parse walrus output: {}", err),
+            WalrusError::Cancelled => write!(f, "Cancelled"),
+        }
     }
+}
 
-    // serialize stdout in output to JSON
+impl std::error::Error for WalrusError {}
 
-    let output_json = String::from_utf8_lossy(&output.stdout);
+/// Runs `request` through `walrus json` and parses its output as `Resp`.
+pub fn run_walrus_json<Req, Resp>(request: &Req) -> Result<Resp, WalrusError>
+where
+    Req: WalrusRequest,
+    Resp: DeserializeOwned,
+{
+    run_walrus_json_cancellable(request, &CancelToken::new())
+}
 
-    // Try deserializing to WalrusNewlyCreated
-    let status_new: Result<WalrusNewlyCreated, serde_json::Error> =
-        serde_json::from_str(&output_json);
+/// Same as `run_walrus_json`, killing `walrus` if `cancel` is cancelled
+/// before it exits.
+pub fn run_walrus_json_cancellable<Req, Resp>(
+    request: &Req,
+    cancel: &CancelToken,
+) -> Result<Resp, WalrusError>
+where
+    Req: WalrusRequest,
+    Resp: DeserializeOwned,
+{
+    let stdout = run_walrus(&walrus_command(request)?, cancel)?;
+    serde_json::from_slice(&stdout).map_err(WalrusError::Parse)
+}
 
-    if let Ok(new_status) = status_new {
-        let blob_id = new_status.newlyCreated.blobObject.blobId;
-        return Ok(blob_id.to_string());
-    } else {
-        // Try deserializing to WalrusAlreadyCertified
-        let status_certified: Result<WalrusAlreadyCertified, serde_json::Error> =
-            serde_json::from_str(&output_json);
+/// Same as `run_walrus_json_cancellable`, waiting for `walrus` on tokio's
+/// blocking pool so that a long upload doesn't hold up a runtime worker.
+pub async fn run_walrus_json_async<Req, Resp>(
+    request: &Req,
+    cancel: &CancelToken,
+) -> Result<Resp, WalrusError>
+where
+    Req: WalrusRequest,
+    Resp: DeserializeOwned,
+{
+    let command = walrus_command(request)?;
+    let cancel = cancel.clone();
+    let stdout = tokio::task::spawn_blocking(move || run_walrus(&command, &cancel))
+        .await
+        .map_err(|err| WalrusError::Spawn(std::io::Error::other(err)))??;
+    serde_json::from_slice(&stdout).map_err(WalrusError::Parse)
+}
 
-        if let Ok(certified_status) = status_certified {
-            let blob_id = certified_status.alreadyCertified.blobId;
-            return Ok(blob_id.to_string());
-        } else {
-            return Err("Failed to parse output JSON to WalrusResponse".into());
-        }
-    }
+/// The `walrus json` argument for `request`.
+fn walrus_command<Req: WalrusRequest>(request: &Req) -> Result<String, WalrusError> {
+    let mut command = serde_json::Map::new();
+    command.insert(
+        Req::COMMAND.to_string(),
+        serde_json::to_value(request).map_err(WalrusError::Parse)?,
+    );
+    Ok(serde_json::json!({ "command": command }).to_string())
 }
 
-pub async fn download_blob(
-    blob_id: &str,
-    file_location: &str,
-) -> Result<bool, Box<dyn std::error::Error>> {
-    let command_json = serde_json::json!({
-        "command": {
-            "read": {
-                "blobId": blob_id,
-                "out": file_location,
-            },
-        }
-    });
+/// Runs `walrus json <command>` to completion, returning its stdout.
+fn run_walrus(command: &str, cancel: &CancelToken) -> Result<Vec<u8>, WalrusError> {
+    if cancel.is_cancelled() {
+        return Err(WalrusError::Cancelled);
+    }
 
-    let output = std::process::Command::new("walrus")
+    let mut child = Command::new("walrus")
         .arg("json")
-        .arg(command_json.to_string())
-        .output()
-        .expect("failed to execute process");
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(WalrusError::Spawn)?;
+
+    // Drain both pipes while waiting so walrus never blocks on a full one
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let status = loop {
+        if cancel.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(WalrusError::Cancelled);
+        }
+        match child.try_wait().map_err(WalrusError::Spawn)? {
+            Some(status) => break status,
+            None => std::thread::sleep(CANCEL_POLL_INTERVAL),
+        }
+    };
 
-    if !output.status.success() {
-        return Err("Failed to download file from walrus".into());
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if !status.success() {
+        return Err(WalrusError::Failed(
+            String::from_utf8_lossy(&stderr).trim().to_string(),
+        ));
     }
 
-    let output = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout)
+}
 
-    let output: serde_json::Value = match serde_json::from_str(&output) {
-        Ok(val) => val,
-        Err(_) => {
-            return Err("Failed to parse output JSON".into());
+fn read_pipe(pipe: Option<impl Read + Send + 'static>) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
         }
-    };
+        buf
+    })
+}
+
+pub async fn upload_blob(
+    file_path: &str,
+    epochs: u16,
+) -> Result<String, Box<dyn std::error::Error>> {
+    upload_blob_cancellable(file_path, epochs, &CancelToken::new()).await
+}
+
+pub async fn upload_blob_cancellable(
+    file_path: &str,
+    epochs: u16,
+    cancel: &CancelToken,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let response: WalrusResponse = run_walrus_json_async(
+        &StoreRequest {
+            file: file_path.to_string(),
+            epochs,
+            deletable: None,
+        },
+        cancel,
+    )
+    .await?;
+    Ok(response.blob_id().to_string())
+}
 
+pub async fn download_blob(
+    blob_id: &str,
+    file_location: &str,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    download_blob_cancellable(blob_id, file_location, &CancelToken::new()).await
+}
+
+pub async fn download_blob_cancellable(
+    blob_id: &str,
+    file_location: &str,
+    cancel: &CancelToken,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let _: ReadResponse = run_walrus_json_async(
+        &ReadRequest {
+            blobId: blob_id.to_string(),
+            out: Some(file_location.to_string()),
+        },
+        cancel,
+    )
+    .await?;
     Ok(true)
 }
 
@@ -250,6 +452,111 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_walrus_response_deserialization() {
+        let json = r#"{
+                            "alreadyCertified": {
+                                "blobId": "WNj9kV-79ScIKYpGmXsBBT0PjjyCeTkZYvUNtwUEr-A",
+                                "eventOrObject": {
+                                    "Object": "0x6ddf05fbd44f522a49d1eef75dab70769b986857c192f108bd52ffd1bdb732d4"
+                                },
+                                "endEpoch": 61
+                            }
+                        }"#;
+
+        let response: WalrusResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            response.blob_id(),
+            "WNj9kV-79ScIKYpGmXsBBT0PjjyCeTkZYvUNtwUEr-A"
+        );
+        let WalrusResponse::AlreadyCertified(certified) = response else {
+            panic!("expected alreadyCertified");
+        };
+        assert!(matches!(certified.eventOrObject, EventOrObject::Object(_)));
+
+        let json = r#"{
+                            "newlyCreated": {
+                                "blobObject": {
+                                    "id": "0x6ddf05fbd44f522a49d1eef75dab70769b986857c192f108bd52ffd1bdb732d4",
+                                    "registeredEpoch": 51,
+                                    "blobId": "DVZWz_QCEb2D_UPQzswv-DUqg-etmV6rEPzoERY4Tgg",
+                                    "size": 5000000000,
+                                    "encodingType": "RedStuff",
+                                    "certifiedEpoch": null,
+                                    "storage": {
+                                        "id": "0xe9be566bec206862e3807225e1a190700fcfd144250d412f51d2776571050e13",
+                                        "startEpoch": 51,
+                                        "endEpoch": 53,
+                                        "storageSize": 65023000
+                                    },
+                                    "deletable": true
+                                },
+                                "resourceOperation": {
+                                    "ReuseStorage": {
+                                        "encoded_length": 65023000
+                                    }
+                                },
+                                "cost": 8000000000
+                            }
+                        }"#;
+
+        let response: WalrusResponse = serde_json::from_str(json).unwrap();
+        let WalrusResponse::NewlyCreated(created) = response else {
+            panic!("expected newlyCreated");
+        };
+        assert_eq!(created.blobObject.size, 5000000000);
+        assert_eq!(created.blobObject.storage.endEpoch, 53);
+        assert_eq!(created.cost, 8000000000);
+    }
+
+    #[test]
+    fn test_verify_file() {
+        let path = std::env::temp_dir().join("walter_verify_test.txt");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "hello world!").unwrap();
+
+        let mut config = WalterConfig::default();
+        config.default_epochs = 1;
+        config.default_shard_size = 5;
+        let mut client = WalrusClient::new(config);
+        assert_eq!(client.verify_file("a.txt", path).unwrap(), None);
+
+        let sha256 = file_sha256(path).unwrap();
+        assert_eq!(
+            sha256,
+            "7509e5bda0c762d2bac7f90d758b5b2263fa01ccbc542ab5e3df163be08e6ca9"
+        );
+        client
+            .config
+            .add_file("a.txt", false, vec!["blob".to_string()], Some(sha256));
+        assert_eq!(client.verify_file("a.txt", path).unwrap(), Some(true));
+
+        std::fs::write(path, "hello world?").unwrap();
+        assert_eq!(client.verify_file("a.txt", path).unwrap(), Some(false));
+        std::fs::remove_file(path).unwrap();
+
+        assert!(client
+            .config
+            .download_path("dir/a.txt")
+            .ends_with("/.walter/downloads/a.txt"));
+    }
+
+    #[test]
+    fn test_cancelled_command_does_not_run() {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let result: Result<InfoResponse, _> = run_walrus_json_cancellable(&InfoRequest {}, &cancel);
+        assert!(matches!(result, Err(WalrusError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_async_command_cancelled_before_start() {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let result: Result<InfoResponse, _> = run_walrus_json_async(&InfoRequest {}, &cancel).await;
+        assert!(matches!(result, Err(WalrusError::Cancelled)));
+    }
+
     #[tokio::test]
     async fn test_upload_to_walrus() {
         let output = upload_blob("test_files/uploadcopy.test", 10).await;
@@ -261,7 +568,7 @@ mod tests {
         let config = WalterConfig::load_config_file();
         let mut client = WalrusClient::new(config);
         let output = client.upload_file("test_files/test_upload.txt", None).await;
-        client.config.save_config_file();
+        client.config.save_config_file().unwrap();
         assert!(output.is_ok());
     }
 
@@ -1,15 +1,114 @@
 use serde::{Deserialize, Serialize};
 use shellexpand;
 use std::collections::HashMap;
+use std::error::Error;
 use std::fs;
 use std::path::Path;
+use std::sync::{Mutex, PoisonError};
 
 const CONFIG_FILE_PATH: &str = "~/.walter/config.json";
 
+/// Held while the config file is read, changed and written back, so jobs
+/// running at the same time don't save over each other's changes.
+static CONFIG_LOCK: Mutex<()> = Mutex::new(());
+
+/// Most epochs a blob is stored or extended for at once.
+pub const MAX_EPOCHS: u16 = 200;
+
+/// The config values that can be edited, each of which can be overridden
+/// for a session with an environment variable.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Setting {
+    DownloadDir,
+    DefaultEpochs,
+    ShardSize,
+    RenewEpochThreshold,
+    RenewalValue,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 5] = [
+        Setting::DownloadDir,
+        Setting::DefaultEpochs,
+        Setting::ShardSize,
+        Setting::RenewEpochThreshold,
+        Setting::RenewalValue,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Setting::DownloadDir => "Download directory",
+            Setting::DefaultEpochs => "Default epochs",
+            Setting::ShardSize => "Shard size (bytes)",
+            Setting::RenewEpochThreshold => "Renew epoch threshold",
+            Setting::RenewalValue => "Renewal epochs",
+        }
+    }
+
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            Setting::DownloadDir => "WALTER_DOWNLOAD_DIR",
+            Setting::DefaultEpochs => "WALTER_DEFAULT_EPOCHS",
+            Setting::ShardSize => "WALTER_SHARD_SIZE",
+            Setting::RenewEpochThreshold => "WALTER_RENEW_EPOCH_THRESHOLD",
+            Setting::RenewalValue => "WALTER_RENEWAL_VALUE",
+        }
+    }
+
+    /// The value in effect in `config`, as text.
+    pub fn get(&self, config: &WalterConfig) -> String {
+        match self {
+            Setting::DownloadDir => config.default_file_download_dir.clone(),
+            Setting::DefaultEpochs => config.default_epochs.to_string(),
+            Setting::ShardSize => config.default_shard_size.to_string(),
+            Setting::RenewEpochThreshold => config.renew_epoch_threshold.to_string(),
+            Setting::RenewalValue => config.default_renewal_value.to_string(),
+        }
+    }
+
+    /// Parses and checks `value`, then sets it on `config`.
+    fn set(&self, config: &mut WalterConfig, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        match self {
+            Setting::DownloadDir if value.is_empty() => {
+                return Err("The download directory can't be empty".to_string())
+            }
+            Setting::DownloadDir => config.default_file_download_dir = value.to_string(),
+            Setting::DefaultEpochs => config.default_epochs = parse_epochs(value, 1)?,
+            Setting::ShardSize => match value.parse() {
+                Ok(0) | Err(_) => {
+                    return Err("The shard size must be a number of bytes above 0".to_string())
+                }
+                Ok(size) => config.default_shard_size = size,
+            },
+            Setting::RenewEpochThreshold => config.renew_epoch_threshold = parse_epochs(value, 0)?,
+            Setting::RenewalValue => config.default_renewal_value = parse_epochs(value, 1)?,
+        }
+        Ok(())
+    }
+}
+
+fn config_file_path() -> String {
+    shellexpand::tilde(CONFIG_FILE_PATH).to_string()
+}
+
+fn parse_epochs(value: &str, min: u16) -> Result<u16, String> {
+    match value.parse() {
+        Ok(epochs) if (min..=MAX_EPOCHS).contains(&epochs) => Ok(epochs),
+        _ => Err(format!(
+            "Epochs must be a number from {} to {}",
+            min, MAX_EPOCHS
+        )),
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct FileInfo {
     pub is_encrypted: bool,
     pub blobs: Vec<String>,
+    /// Hex SHA-256 of the file as it was before encryption and upload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -20,6 +119,24 @@ pub struct WalterConfig {
     pub renew_epoch_threshold: u16,
     pub default_renewal_value: u16,
     pub files: HashMap<String, FileInfo>,
+    /// The file's values of settings overridden by the environment, which
+    /// are what gets saved
+    #[serde(skip)]
+    overridden: HashMap<Setting, String>,
+}
+
+impl Default for WalterConfig {
+    fn default() -> Self {
+        WalterConfig {
+            default_file_download_dir: "~/.walter/downloads".to_string(),
+            default_epochs: 10,
+            default_shard_size: 1024 * 1024 * 10,
+            renew_epoch_threshold: 2,
+            default_renewal_value: 10,
+            files: HashMap::new(),
+            overridden: HashMap::new(),
+        }
+    }
 }
 
 impl WalterConfig {
@@ -43,55 +160,228 @@ impl WalterConfig {
         return self.files.get(file_path).map(|file_info| &file_info.blobs);
     }
 
-    pub fn add_file(&mut self, file_path: &str, is_encrypted: bool, blobs: Vec<String>) {
+    pub fn add_file(
+        &mut self,
+        file_path: &str,
+        is_encrypted: bool,
+        blobs: Vec<String>,
+        sha256: Option<String>,
+    ) {
         let file_info = FileInfo {
             is_encrypted,
             blobs,
+            sha256,
         };
 
         self.files.insert(file_path.to_string(), file_info);
     }
 
+    /// The environment variable overriding `setting`, if any.
+    pub fn overridden_by(&self, setting: Setting) -> Option<&'static str> {
+        self.overridden
+            .contains_key(&setting)
+            .then(|| setting.env_var())
+    }
+
+    /// The value of `setting` in the config file, which differs from the
+    /// one in effect when it is overridden.
+    pub fn file_value(&self, setting: Setting) -> String {
+        match self.overridden.get(&setting) {
+            Some(value) => value.clone(),
+            None => setting.get(self),
+        }
+    }
+
+    /// Checks and sets `setting` to `value`. An overridden setting keeps the
+    /// environment's value until the next session.
+    pub fn update(&mut self, setting: Setting, value: &str) -> Result<(), String> {
+        if self.overridden.contains_key(&setting) {
+            setting.set(&mut self.clone(), value)?;
+            self.overridden.insert(setting, value.trim().to_string());
+            Ok(())
+        } else {
+            setting.set(self, value)
+        }
+    }
+
+    /// Applies the `WALTER_*` environment variables of `Setting::env_var`,
+    /// skipping invalid values.
+    fn apply_env_overrides(&mut self) {
+        self.apply_overrides(|var| std::env::var(var).ok());
+    }
+
+    /// Same as `apply_env_overrides`, with the variables from `lookup`.
+    fn apply_overrides(&mut self, lookup: impl Fn(&str) -> Option<String>) {
+        for setting in Setting::ALL {
+            let Some(value) = lookup(setting.env_var()) else {
+                continue;
+            };
+            let file_value = setting.get(self);
+            if setting.set(self, &value).is_ok() {
+                self.overridden.insert(setting, file_value);
+            }
+        }
+    }
+
+    /// Where a download named `name` goes when no destination is given.
+    pub fn download_path(&self, name: &str) -> String {
+        let dir = shellexpand::tilde(&self.default_file_download_dir).to_string();
+        let name = Path::new(name)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| name.to_string());
+        Path::new(&dir).join(name).to_string_lossy().to_string()
+    }
+
     pub fn load_config_file() -> WalterConfig {
-        let path = shellexpand::tilde(CONFIG_FILE_PATH).to_string();
-        let path = Path::new(&path);
+        WalterConfig::load_config_from(Path::new(&config_file_path()))
+    }
 
+    /// Same as `load_config_file`, with the config file at `path`.
+    fn load_config_from(path: &Path) -> WalterConfig {
         if !path.exists() {
             if let Some(parent) = path.parent() {
                 fs::create_dir_all(parent).expect("Unable to create config directory");
             }
 
-            let default_config = WalterConfig {
-                default_file_download_dir: "~/.walter/downloads".to_string(),
-                default_epochs: 10,
-                default_shard_size: 1024 * 1024 * 10,
-                renew_epoch_threshold: 2,
-                default_renewal_value: 10,
-                files: HashMap::new(),
-            };
+            let default_config = WalterConfig::default();
 
             let config_json = serde_json::to_string(&default_config)
                 .expect("Unable to serialize default config!");
             fs::write(path, config_json).expect("Unable to write default config file!");
 
-            return default_config;
+            let mut config = default_config;
+            config.apply_env_overrides();
+            return config;
         }
 
         let config_json = fs::read_to_string(path).expect("Unable to read config file!");
-        serde_json::from_str(&config_json).expect("Unable to deserialize config file!")
+        let mut config: WalterConfig =
+            serde_json::from_str(&config_json).expect("Unable to deserialize config file!");
+        config.apply_env_overrides();
+        config
     }
 
-    pub fn save_config_file(&self) {
-        let path = shellexpand::tilde(CONFIG_FILE_PATH).to_string();
-        let path = Path::new(&path);
+    /// Applies `change` to the config file and saves it. The file is read
+    /// again first, so files and settings saved since this config was loaded
+    /// are kept, and this config is replaced with the one saved.
+    pub fn save_change<F>(&mut self, change: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce(&mut WalterConfig) -> Result<(), Box<dyn Error>>,
+    {
+        self.save_change_at(Path::new(&config_file_path()), change)
+    }
+
+    /// Same as `save_change`, with the config file at `path`.
+    fn save_change_at<F>(&mut self, path: &Path, change: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce(&mut WalterConfig) -> Result<(), Box<dyn Error>>,
+    {
+        let _lock = CONFIG_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut config = WalterConfig::load_config_from(path);
+        change(&mut config)?;
+        config.save_config_to(path)?;
+        *self = config;
+        Ok(())
+    }
 
+    /// Writes the config through a temporary file, so an interrupted save
+    /// leaves the previous config in place. Overridden settings are saved
+    /// with their values from the file, not the environment's.
+    pub fn save_config_file(&self) -> Result<(), Box<dyn Error>> {
+        self.save_config_to(Path::new(&config_file_path()))
+    }
+
+    /// Same as `save_config_file`, with the config file at `path`.
+    fn save_config_to(&self, path: &Path) -> Result<(), Box<dyn Error>> {
         if !path.exists() {
             if let Some(parent) = path.parent() {
                 fs::create_dir_all(parent).expect("Unable to create config directory");
             }
         }
 
-        let config_json = serde_json::to_string(self).expect("Unable to serialize config!");
-        fs::write(path, config_json).expect("Unable to write config file!");
+        let mut config = self.clone();
+        for (setting, value) in &self.overridden {
+            setting.set(&mut config, value)?;
+        }
+        let config_json = serde_json::to_string(&config)?;
+
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, config_json)
+            .map_err(|e| format!("Unable to write config file: {}", e))?;
+        fs::rename(&tmp_path, path).map_err(|e| format!("Unable to save config file: {}", e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_settings() {
+        let mut config = WalterConfig::default();
+        assert!(config.update(Setting::DefaultEpochs, "0").is_err());
+        assert!(config.update(Setting::DefaultEpochs, "201").is_err());
+        assert!(config.update(Setting::ShardSize, "0").is_err());
+        assert!(config.update(Setting::DownloadDir, " ").is_err());
+        assert_eq!(config.default_epochs, 10);
+
+        config.update(Setting::DefaultEpochs, " 20 ").unwrap();
+        config.update(Setting::RenewEpochThreshold, "0").unwrap();
+        assert_eq!(config.default_epochs, 20);
+        assert_eq!(config.renew_epoch_threshold, 0);
+    }
+
+    #[test]
+    fn test_save_change_keeps_other_changes() {
+        let dir = std::env::temp_dir().join(format!("walter_config_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("config.json");
+
+        // Two jobs loaded the config before either of them saved
+        let mut first = WalterConfig::load_config_from(&path);
+        let mut second = WalterConfig::load_config_from(&path);
+        first
+            .save_change_at(&path, |config| {
+                config.add_file("/tmp/walter_config_test_a", false, vec!["a".into()], None);
+                Ok(())
+            })
+            .unwrap();
+        second
+            .save_change_at(&path, |config| {
+                config.add_file("/tmp/walter_config_test_b", false, vec!["b".into()], None);
+                Ok(())
+            })
+            .unwrap();
+
+        let files = WalterConfig::load_config_from(&path).files;
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(files.contains_key("/tmp/walter_config_test_a"));
+        assert!(files.contains_key("/tmp/walter_config_test_b"));
+        assert_eq!(second.files.len(), 2);
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let env = HashMap::from([
+            ("WALTER_SHARD_SIZE", "2048"),
+            ("WALTER_DEFAULT_EPOCHS", "not a number"),
+        ]);
+        let mut config = WalterConfig::default();
+        config.apply_overrides(|var| env.get(var).map(|value| value.to_string()));
+
+        assert_eq!(config.default_shard_size, 2048);
+        assert_eq!(
+            config.overridden_by(Setting::ShardSize),
+            Some("WALTER_SHARD_SIZE")
+        );
+        assert_eq!(config.overridden_by(Setting::DefaultEpochs), None);
+        assert_eq!(config.default_epochs, 10);
+
+        // Editing an overridden setting changes what is saved, not what is used
+        config.update(Setting::ShardSize, "4096").unwrap();
+        assert_eq!(config.default_shard_size, 2048);
+        assert_eq!(config.file_value(Setting::ShardSize), "4096");
     }
 }
@@ -62,6 +62,9 @@ pub fn decrypt_file(
     let mut file = File::open(input_file)?;
     let mut data = Vec::new();
     file.read_to_end(&mut data)?;
+    if data.len() < SALT_LEN + IV_LEN {
+        return Err(format!("{} is too short to be an encrypted file", input_file).into());
+    }
 
     let salt = &data[0..SALT_LEN];
     let iv = &data[SALT_LEN..SALT_LEN + IV_LEN];
@@ -116,4 +119,17 @@ mod tests {
 
         assert_eq!(decrypted_content, original_contents);
     }
+
+    #[test]
+    fn test_decrypt_short_file() {
+        let short_file = "test_files/test_short.enc";
+        let decrypted_file = "test_files/test_short.dec";
+        fs::write(short_file, [0u8; SALT_LEN + IV_LEN - 1]).unwrap();
+
+        let result = decrypt_file(short_file, decrypted_file, "password");
+        fs::remove_file(short_file).unwrap();
+
+        assert!(result.unwrap_err().to_string().contains("too short"));
+        assert!(!std::path::Path::new(decrypted_file).exists());
+    }
 }
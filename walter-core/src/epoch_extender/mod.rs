@@ -1,19 +1,47 @@
 use super::client::*;
+use crate::cancel::CancelToken;
 use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub async fn extend_epoch(blob_id: &str, epochs: u16) -> Result<(), Box<dyn Error>> {
-    let output = download_blob(blob_id, "/tmp/epoch_extender").await;
-
-    if output.is_err() {
-        return Err(output.err().unwrap());
-    }
+    extend_epoch_cancellable(blob_id, epochs, &CancelToken::new()).await
+}
 
-    let output = upload_blob("/tmp/epoch_extender", epochs).await;
+/// Same as `extend_epoch`, stopping early once `cancel` is cancelled. The
+/// downloaded copy of the blob is removed either way.
+pub async fn extend_epoch_cancellable(
+    blob_id: &str,
+    epochs: u16,
+    cancel: &CancelToken,
+) -> Result<(), Box<dyn Error>> {
+    let path = extender_file(blob_id)?;
+    let result = reupload(blob_id, epochs, &path, cancel).await;
+    let _ = std::fs::remove_file(&path);
+    result
+}
 
-    if output.is_err() {
-        return Err(output.err().unwrap());
-    }
+/// A temporary path of its own for each extension, since several can run
+/// at once.
+fn extender_file(blob_id: &str) -> Result<PathBuf, Box<dyn Error>> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+    let count = COUNT.fetch_add(1, Ordering::Relaxed);
+    Ok(std::env::temp_dir().join(format!("epoch_extender_{}_{}_{}", blob_id, nanos, count)))
+}
 
+async fn reupload(
+    blob_id: &str,
+    epochs: u16,
+    path: &Path,
+    cancel: &CancelToken,
+) -> Result<(), Box<dyn Error>> {
+    let path = path
+        .to_str()
+        .ok_or("The temporary directory is not UTF-8")?;
+    download_blob_cancellable(blob_id, path, cancel).await?;
+    upload_blob_cancellable(path, epochs, cancel).await?;
     Ok(())
 }
 
@@ -26,4 +54,12 @@ mod tests {
         let output = extend_epoch("DVZWz_QCEb2D_UPQzswv-DUqg-etmV6rEPzoERY4Tgg", 1).await;
         assert!(output.is_ok());
     }
+
+    #[test]
+    fn test_extender_files_differ() {
+        let first = extender_file("blob").unwrap();
+        let second = extender_file("blob").unwrap();
+        assert_ne!(first, second);
+        assert!(first.starts_with(std::env::temp_dir()));
+    }
 }
@@ -1,8 +1,10 @@
+pub mod cancel;
 pub mod client;
 pub mod config;
 pub mod encryptor;
 pub mod epoch_extender;
 pub mod migrator;
 mod sharder;
+pub mod system_info;
 pub mod types;
 pub mod updater;
@@ -1,3 +1,4 @@
+use crate::cancel::CancelToken;
 use crate::client::WalrusClient;
 use crate::config::WalterConfig;
 use failure;
@@ -31,7 +32,41 @@ pub async fn download_ipfs_file(file_path: &str, cid: &str) -> Result<(), failur
     Ok(())
 }
 
+/// Same as `download_ipfs_file`, reading the body in chunks so a cancel
+/// stops it mid-file. Nothing is written unless the whole file arrived.
+async fn download_ipfs_file_cancellable(
+    file_path: &str,
+    cid: &str,
+    cancel: &CancelToken,
+) -> Result<(), Box<dyn Error>> {
+    let url = format!("https://ipfs.io/ipfs/{}", cid);
+    let client = reqwest::Client::new();
+    let mut response = client.get(&url).send().await?.error_for_status()?;
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        cancel.check()?;
+        body.extend_from_slice(&chunk);
+    }
+    write_file(file_path, body)?;
+    Ok(())
+}
+
 pub async fn migrate_files(jwt: &str) -> Result<(), Box<dyn Error>> {
+    migrate_files_with_progress(jwt, &CancelToken::new(), |_, _| {}).await
+}
+
+/// Same as `migrate_files`, calling `progress(migrated, total)` with the file
+/// counts before the first file and after every migrated one, and stopping
+/// early once `cancel` is cancelled.
+///
+/// Every migrated file is in the config as soon as it is uploaded and is
+/// skipped when the migration runs again, so a cancelled or failed migration
+/// picks up where it stopped.
+pub async fn migrate_files_with_progress(
+    jwt: &str,
+    cancel: &CancelToken,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<(), Box<dyn Error>> {
     let files = get_file_list(jwt).await?;
 
     let config: WalterConfig = WalterConfig::load_config_file();
@@ -40,21 +75,28 @@ pub async fn migrate_files(jwt: &str) -> Result<(), Box<dyn Error>> {
     let download_dir = shellexpand::tilde(&download_dir.to_string_lossy()).to_string();
     let download_dir = path::Path::new(&download_dir);
 
-    let mut walrus_client = WalrusClient::new(config.clone());
+    let mut walrus_client = WalrusClient::new(config.clone()).with_cancel(cancel.clone());
     if let Some(file_list) = files["data"]["files"].as_array() {
-        for file in file_list {
+        progress(0, file_list.len());
+        for (index, file) in file_list.iter().enumerate() {
             if let (Some(name), Some(cid)) = (file["name"].as_str(), file["cid"].as_str()) {
                 let file_path = download_dir.join(name).to_string_lossy().to_string();
-                if !path::Path::new(&download_dir).exists() {
-                    std::fs::create_dir_all(&download_dir)?;
+                if walrus_client.config.get_file_blobs(&file_path).is_none() {
+                    cancel.check()?;
+                    if !path::Path::new(&download_dir).exists() {
+                        std::fs::create_dir_all(&download_dir)?;
+                    }
+                    download_ipfs_file_cancellable(&file_path, cid, cancel).await?;
+                    if let Err(e) = walrus_client.upload_file(&file_path, None).await {
+                        let _ = std::fs::remove_file(&file_path);
+                        return Err(e);
+                    }
                 }
-                download_ipfs_file(&file_path, cid).await?;
-                walrus_client.upload_file(&file_path, None).await?;
             }
+            progress(index + 1, file_list.len());
         }
     }
 
-    walrus_client.config.save_config_file();
     Ok(())
 }
 
@@ -62,11 +104,14 @@ pub async fn migrate_files(jwt: &str) -> Result<(), Box<dyn Error>> {
 mod tests {
 
     use super::*;
-    const JWT : &str = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.eyJ1c2VySW5mb3JtYXRpb24iOnsiaWQiOiI4YzAxZGVjYy1iZmFiLTQ4Y2UtOTQyMy05NjJkMWNkYjlhODYiLCJlbWFpbCI6InByYW5lZXRoc2Fyb2RlQGdtYWlsLmNvbSIsImVtYWlsX3ZlcmlmaWVkIjp0cnVlLCJwaW5fcG9saWN5Ijp7InJlZ2lvbnMiOlt7ImRlc2lyZWRSZXBsaWNhdGlvbkNvdW50IjoxLCJpZCI6IkZSQTEifSx7ImRlc2lyZWRSZXBsaWNhdGlvbkNvdW50IjoxLCJpZCI6Ik5ZQzEifV0sInZlcnNpb24iOjF9LCJtZmFfZW5hYmxlZCI6ZmFsc2UsInN0YXR1cyI6IkFDVElWRSJ9LCJhdXRoZW50aWNhdGlvblR5cGUiOiJzY29wZWRLZXkiLCJzY29wZWRLZXlLZXkiOiJmOTg4MzJhZDZkZmI0Mzk0NWM3MyIsInNjb3BlZEtleVNlY3JldCI6IjhlMTE3NTFlMjE2ZTczYWI4MWIxYWQ5NDkwYjliYWYyN2RiNDVhNjU3NzQzNzVhZTNjMzI2N2U4NDMzODBhNDUiLCJleHAiOjE3NjUxMTQ2OTF9.Gl5_t61lvIF4jds9ZNnXiEZdE_O4E9_imFeuYPiJqEE";
 
+    /// Needs a Pinata API key, skipped unless one is in `PINATA_JWT`.
     #[tokio::test]
     async fn test_migration() {
-        let result = migrate_files(JWT).await;
+        let Ok(jwt) = std::env::var("PINATA_JWT") else {
+            return;
+        };
+        let result = migrate_files(&jwt).await;
         assert!(result.is_ok());
     }
 }
@@ -0,0 +1,272 @@
+use std::error::Error;
+use std::time::Duration;
+
+use crate::types::{format_size, InfoResponse};
+
+const FROST_PER_WAL: f64 = 1_000_000_000.0;
+
+/// What `walrus info` reports about the network. Only the current epoch is
+/// required, the CLI has added and reworded the other lines between versions.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SystemInfo {
+    pub current_epoch: u64,
+    pub epoch_duration: Option<Duration>,
+    /// Most epochs ahead a blob can be stored until
+    pub max_epochs_ahead: Option<u32>,
+    pub n_shards: Option<u32>,
+    pub committee_size: Option<u32>,
+    pub max_blob_size: Option<u64>,
+    pub storage_unit_size: Option<u64>,
+    /// FROST per encoded storage unit per epoch
+    pub storage_price: Option<u64>,
+    /// FROST paid on top of the storage price for each write
+    pub write_price: Option<u64>,
+}
+
+impl SystemInfo {
+    /// Takes what `walrus info --json` reports, which older CLIs leave parts
+    /// of out.
+    pub fn from_response(response: &InfoResponse) -> SystemInfo {
+        let epoch = &response.epochInfo;
+        SystemInfo {
+            current_epoch: epoch.currentEpoch,
+            epoch_duration: epoch
+                .epochDuration
+                .as_ref()
+                .map(|duration| Duration::new(duration.secs, duration.nanos)),
+            max_epochs_ahead: epoch.maxEpochsAhead,
+            n_shards: response.storageInfo.as_ref().map(|storage| storage.nShards),
+            committee_size: response.storageInfo.as_ref().map(|storage| storage.nNodes),
+            max_blob_size: response.sizeInfo.as_ref().map(|size| size.maxBlobSize),
+            storage_unit_size: response.sizeInfo.as_ref().map(|size| size.storageUnitSize),
+            storage_price: response
+                .priceInfo
+                .as_ref()
+                .map(|price| price.storagePricePerUnitSize),
+            write_price: response
+                .priceInfo
+                .as_ref()
+                .map(|price| price.writePricePerUnitSize),
+        }
+    }
+
+    /// Whether any of the optional fields is unknown.
+    pub fn is_partial(&self) -> bool {
+        self.epoch_duration.is_none()
+            || self.max_epochs_ahead.is_none()
+            || self.n_shards.is_none()
+            || self.committee_size.is_none()
+            || self.max_blob_size.is_none()
+            || self.storage_unit_size.is_none()
+            || self.storage_price.is_none()
+            || self.write_price.is_none()
+    }
+
+    /// Fills the unknown fields from `other`, keeping the ones already known.
+    pub fn fill_from(&mut self, other: SystemInfo) {
+        self.epoch_duration = self.epoch_duration.or(other.epoch_duration);
+        self.max_epochs_ahead = self.max_epochs_ahead.or(other.max_epochs_ahead);
+        self.n_shards = self.n_shards.or(other.n_shards);
+        self.committee_size = self.committee_size.or(other.committee_size);
+        self.max_blob_size = self.max_blob_size.or(other.max_blob_size);
+        self.storage_unit_size = self.storage_unit_size.or(other.storage_unit_size);
+        self.storage_price = self.storage_price.or(other.storage_price);
+        self.write_price = self.write_price.or(other.write_price);
+    }
+
+    /// Parses the text `walrus info` prints, skipping lines it doesn't know.
+    pub fn parse(text: &str) -> Result<SystemInfo, Box<dyn Error>> {
+        let mut current_epoch = None;
+        let mut info = SystemInfo::default();
+
+        for line in text.lines() {
+            // "Blobs can be stored for at most 183 epochs in the future."
+            if let Some(rest) = line.split_once("at most ").map(|(_, rest)| rest) {
+                if let Some((epochs, _)) = rest.split_once(" epochs") {
+                    info.max_epochs_ahead = epochs.trim().parse().ok();
+                }
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "current epoch" => current_epoch = value.parse().ok(),
+                "epoch duration" => info.epoch_duration = parse_duration(value),
+                "number of storage nodes" => info.committee_size = value.parse().ok(),
+                "number of shards" => info.n_shards = value.parse().ok(),
+                "maximum blob size" => info.max_blob_size = parse_size(value),
+                "storage unit" => info.storage_unit_size = parse_size(value),
+                "price per encoded storage unit" => info.storage_price = parse_price(value),
+                "additional price for each write" => info.write_price = parse_price(value),
+                _ => {}
+            }
+        }
+
+        info.current_epoch = current_epoch.ok_or("No current epoch in the walrus info output")?;
+        Ok(info)
+    }
+
+    /// At most how long until `epoch` starts, zero if it already has. None
+    /// when the epoch duration is unknown.
+    pub fn time_until_epoch(&self, epoch: u64) -> Option<Duration> {
+        let duration = self.epoch_duration?;
+        // The current epoch is partly over, so this is an upper bound
+        let epochs = epoch.saturating_sub(self.current_epoch);
+        Some(duration * u32::try_from(epochs).unwrap_or(u32::MAX))
+    }
+
+    /// Fails if the network doesn't store blobs `epochs` ahead.
+    pub fn check_epochs(&self, epochs: u16) -> Result<(), String> {
+        match self.max_epochs_ahead {
+            Some(max) if u32::from(epochs) > max => Err(format!(
+                "Blobs can be stored for at most {} epochs on this network",
+                max
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if shards of `shard_size` bytes are too big to store as blobs.
+    pub fn check_shard_size(&self, shard_size: usize) -> Result<(), String> {
+        match self.max_blob_size {
+            Some(max) if shard_size as u64 > max => Err(format!(
+                "Shard size {} is over the {} maximum blob size",
+                format_size(shard_size as u64),
+                format_size(max)
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Durations like `1day`, `2weeks` or `1h 30m`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut total = 0;
+    let mut rest = value.as_str();
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let number: u64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let letters = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..letters] {
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 3600,
+            "d" | "day" | "days" => 86400,
+            "w" | "week" | "weeks" => 7 * 86400,
+            _ => return None,
+        };
+        rest = &rest[letters..];
+        total += number * unit;
+    }
+    Some(Duration::from_secs(total))
+}
+
+/// Sizes like `13.6 GiB (14,599,718,400 B)`, preferring the exact byte count.
+fn parse_size(value: &str) -> Option<u64> {
+    if let Some(bytes) = value
+        .split_once('(')
+        .and_then(|(_, exact)| exact.strip_suffix("B)"))
+    {
+        return bytes.trim().replace(',', "").parse().ok();
+    }
+
+    let (number, unit) = value.split_once(' ')?;
+    let number: f64 = number.replace(',', "").parse().ok()?;
+    let unit: u64 = match unit.trim() {
+        "B" => 1,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        "TiB" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * unit as f64).round() as u64)
+}
+
+/// Prices in WAL or FROST, returned in FROST.
+fn parse_price(value: &str) -> Option<u64> {
+    let (number, unit) = value.split_once(' ')?;
+    let number: f64 = number.replace(',', "").parse().ok()?;
+    match unit.trim() {
+        "FROST" => Some(number.round() as u64),
+        "WAL" => Some((number * FROST_PER_WAL).round() as u64),
+        _ => None,
+    }
+}
+
+/// A FROST amount in WAL when it is at least a millionth of one.
+pub fn format_price(frost: u64) -> String {
+    if frost as f64 >= FROST_PER_WAL / 1_000_000.0 {
+        format!("{} WAL", frost as f64 / FROST_PER_WAL)
+    } else {
+        format!("{} FROST", frost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_system_info() {
+        let info = SystemInfo::parse(include_str!("../../test_files/walrus/info.txt")).unwrap();
+        assert_eq!(
+            info,
+            SystemInfo {
+                current_epoch: 55,
+                epoch_duration: Some(Duration::from_secs(86400)),
+                max_epochs_ahead: Some(183),
+                n_shards: Some(1000),
+                committee_size: Some(25),
+                max_blob_size: Some(14599718400),
+                storage_unit_size: Some(1048576),
+                storage_price: Some(100000),
+                write_price: Some(20000),
+            }
+        );
+
+        assert_eq!(
+            info.time_until_epoch(57),
+            Some(Duration::from_secs(2 * 86400))
+        );
+        assert_eq!(info.time_until_epoch(50), Some(Duration::ZERO));
+        assert!(info.check_shard_size(1_000_000).is_ok());
+        assert!(info.check_shard_size(20_000_000_000).is_err());
+        assert!(info.check_epochs(183).is_ok());
+        assert!(info.check_epochs(184).is_err());
+        assert!(SystemInfo::default().check_epochs(u16::MAX).is_ok());
+
+        assert_eq!(parse_duration("1h 30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(format_price(20000), "0.00002 WAL");
+        assert!(SystemInfo::parse("Walrus system information").is_err());
+    }
+
+    #[test]
+    fn test_system_info_from_response() {
+        let response: InfoResponse =
+            serde_json::from_str(include_str!("../../test_files/walrus/info.json")).unwrap();
+        let info = SystemInfo::from_response(&response);
+        assert!(!info.is_partial());
+        assert_eq!(
+            info,
+            SystemInfo::parse(include_str!("../../test_files/walrus/info.txt")).unwrap()
+        );
+
+        let response: InfoResponse =
+            serde_json::from_str(r#"{"epochInfo": {"currentEpoch": 60}}"#).unwrap();
+        let mut partial = SystemInfo::from_response(&response);
+        assert!(partial.is_partial());
+        partial.fill_from(info.clone());
+        assert_eq!(partial.current_epoch, 60);
+        assert_eq!(partial.max_blob_size, info.max_blob_size);
+        assert!(!partial.is_partial());
+    }
+}
@@ -1,20 +1,22 @@
 use serde::{Deserialize, Serialize};
 
 #[allow(non_snake_case)]
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Event {
     pub txDigest: String,
     pub eventSeq: String,
 }
 
-#[allow(non_snake_case)]
-#[derive(Deserialize, Debug)]
-pub struct EventOrObject {
-    pub Event: Event,
+/// Where a certified blob was found: the certification event, or the ID of
+/// the Sui object holding it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum EventOrObject {
+    Event(Event),
+    Object(String),
 }
 
 #[allow(non_snake_case)]
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct AlreadyCertified {
     pub blobId: String,
     pub eventOrObject: EventOrObject,
@@ -22,7 +24,7 @@ pub struct AlreadyCertified {
 }
 // Define a struct matching the JSON structure
 #[allow(non_snake_case)]
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Storage {
     pub id: String,
     pub startEpoch: u32,
@@ -30,10 +32,13 @@ pub struct Storage {
     pub storageSize: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct BlobInfo {
     pub blob_id: String,
     pub unencoded_size: String, // Keep as string to preserve original format
+    /// Unencoded size in bytes, `unencoded_size` can't be sorted on
+    #[serde(default)]
+    pub size: u64,
     pub is_certified: bool,
     pub is_deletable: bool,
     pub expiration_epoch: u64,
@@ -41,53 +46,445 @@ pub struct BlobInfo {
 }
 
 #[allow(non_snake_case)]
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BlobObject {
     pub id: String,
     pub registeredEpoch: u32,
     pub blobId: String,
-    pub size: u32,
+    pub size: u64,
     pub encodingType: String,
-    pub certifiedEpoch: u32,
+    pub certifiedEpoch: Option<u32>,
     pub storage: Storage,
     pub deletable: bool,
 }
 
-#[allow(non_snake_case)]
-#[derive(Deserialize, Debug)]
-pub struct ResourceOperation {
-    RegisterFromScratch: RegisterFromScratch,
+impl From<&BlobObject> for BlobInfo {
+    fn from(blob: &BlobObject) -> Self {
+        BlobInfo {
+            blob_id: blob.blobId.clone(),
+            unencoded_size: format_size(blob.size),
+            size: blob.size,
+            is_certified: blob.certifiedEpoch.is_some(),
+            is_deletable: blob.deletable,
+            expiration_epoch: u64::from(blob.storage.endEpoch),
+            object_id: blob.id.clone(),
+        }
+    }
 }
 
-#[allow(non_snake_case)]
-#[derive(Deserialize, Debug)]
-pub struct RegisterFromScratch {
-    encoded_length: u64,
-    epochs_ahead: u32,
+/// Formats a byte count the way `walrus list-blobs` does, e.g. `1.50 KiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", size, UNITS[unit])
+}
+
+/// How the storage for a newly stored blob was obtained.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ResourceOperation {
+    RegisterFromScratch {
+        encoded_length: u64,
+        epochs_ahead: u32,
+    },
+    ReuseStorage {
+        encoded_length: u64,
+    },
+    ReuseRegistration {
+        encoded_length: u64,
+    },
 }
 
 #[allow(non_snake_case)]
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct NewlyCreated {
     pub blobObject: BlobObject,
     pub resourceOperation: ResourceOperation,
-    pub cost: u32,
+    pub cost: u64,
 }
 
 #[allow(non_snake_case)]
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct WalrusNewlyCreated {
     pub newlyCreated: NewlyCreated,
 }
 
 #[allow(non_snake_case)]
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct WalrusAlreadyCertified {
     pub alreadyCertified: AlreadyCertified,
 }
 
-#[derive(Deserialize, Debug)]
+/// Output of `walrus json` for a `store` command.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub enum WalrusResponse {
-    NewlyCreated(WalrusNewlyCreated),
-    AlreadyCertified(WalrusAlreadyCertified),
+    NewlyCreated(NewlyCreated),
+    AlreadyCertified(AlreadyCertified),
+}
+
+impl WalrusResponse {
+    pub fn blob_id(&self) -> &str {
+        match self {
+            WalrusResponse::NewlyCreated(created) => &created.blobObject.blobId,
+            WalrusResponse::AlreadyCertified(certified) => &certified.blobId,
+        }
+    }
+}
+
+/// One page of a blob listing, as returned by the Walrus explorer API.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlobResponse {
+    pub content: Vec<BlobEntry>,
+    pub pageable: Pageable,
+    pub totalPages: u32,
+    pub totalElements: u32,
+    pub last: bool,
+    pub size: u32,
+    pub number: u32,
+    pub sort: Sort,
+    pub numberOfElements: u32,
+    pub first: bool,
+    pub empty: bool,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlobEntry {
+    pub blobId: String,
+    pub blobIdBase64: String,
+    pub objectId: String,
+    pub startEpoch: u32,
+    pub endEpoch: u32,
+    pub size: u64,
+    pub timestamp: u64,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Pageable {
+    pub pageNumber: u32,
+    pub pageSize: u32,
+    pub sort: Sort,
+    pub offset: u32,
+    pub paged: bool,
+    pub unpaged: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Sort {
+    pub sorted: bool,
+    pub empty: bool,
+    pub unsorted: bool,
+}
+
+/// A `walrus json` command. Its arguments serialize to the object given
+/// under the command name.
+pub trait WalrusRequest: Serialize {
+    const COMMAND: &'static str;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StoreRequest {
+    pub file: String,
+    pub epochs: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deletable: Option<bool>,
+}
+
+impl WalrusRequest for StoreRequest {
+    const COMMAND: &'static str = "store";
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReadRequest {
+    pub blobId: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub out: Option<String>,
+}
+
+impl WalrusRequest for ReadRequest {
+    const COMMAND: &'static str = "read";
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReadResponse {
+    pub blobId: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub out: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlobStatusRequest {
+    pub blobId: String,
+}
+
+impl WalrusRequest for BlobStatusRequest {
+    const COMMAND: &'static str = "blobStatus";
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlobStatusResponse {
+    pub blobId: String,
+    pub status: BlobStatus,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum BlobStatus {
+    Nonexistent,
+    Invalid {
+        event: Event,
+    },
+    Permanent {
+        endEpoch: u32,
+        isCertified: bool,
+        statusEvent: Event,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        deletableCounts: Option<DeletableCounts>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        initialCertifiedEpoch: Option<u32>,
+    },
+    Deletable {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        initialCertifiedEpoch: Option<u32>,
+        deletableCounts: DeletableCounts,
+    },
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeletableCounts {
+    pub countDeletableTotal: u32,
+    pub countDeletableCertified: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct InfoRequest {}
+
+impl WalrusRequest for InfoRequest {
+    const COMMAND: &'static str = "info";
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InfoResponse {
+    pub epochInfo: EpochInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storageInfo: Option<StorageInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sizeInfo: Option<SizeInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priceInfo: Option<PriceInfo>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EpochInfo {
+    pub currentEpoch: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epochDuration: Option<EpochDuration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maxEpochsAhead: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EpochDuration {
+    pub secs: u64,
+    pub nanos: u32,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StorageInfo {
+    pub nShards: u32,
+    pub nNodes: u32,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SizeInfo {
+    pub storageUnitSize: u64,
+    pub maxBlobSize: u64,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PriceInfo {
+    pub storagePricePerUnitSize: u64,
+    pub writePricePerUnitSize: u64,
+}
+
+/// Lists the blob objects owned by the active wallet, answered with a
+/// `Vec<BlobObject>`.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ListBlobsRequest {
+    #[serde(default)]
+    pub includeExpired: bool,
+}
+
+impl WalrusRequest for ListBlobsRequest {
+    const COMMAND: &'static str = "listBlobs";
+}
+
+/// Deletes the deletable blob objects holding a blob, answered with a
+/// `Vec<DeleteOutput>`.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeleteRequest {
+    pub blobId: String,
+    pub yes: bool,
+}
+
+impl WalrusRequest for DeleteRequest {
+    const COMMAND: &'static str = "delete";
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeleteOutput {
+    pub blobIdentity: BlobIdentity,
+    #[serde(default)]
+    pub deletedBlobs: Vec<BlobObject>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlobIdentity {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blobId: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// Extends the storage of a blob object. Its output carries nothing we use,
+/// so it is read as [`serde::de::IgnoredAny`].
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExtendRequest {
+    pub blobObjId: String,
+    pub epochsAhead: u64,
+}
+
+impl WalrusRequest for ExtendRequest {
+    const COMMAND: &'static str = "extend";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+
+    /// Parses `fixture`, then checks that serializing it again loses nothing.
+    fn round_trip<T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug>(
+        fixture: &str,
+    ) -> T {
+        let parsed: T = serde_json::from_str(fixture).unwrap();
+        let again: T = serde_json::from_str(&serde_json::to_string(&parsed).unwrap()).unwrap();
+        assert_eq!(parsed, again);
+        parsed
+    }
+
+    #[test]
+    fn test_store_fixtures() {
+        let created: WalrusResponse = round_trip(include_str!(
+            "../../test_files/walrus/store_newly_created.json"
+        ));
+        assert_eq!(
+            created.blob_id(),
+            "DVZWz_QCEb2D_UPQzswv-DUqg-etmV6rEPzoERY4Tgg"
+        );
+        let certified: WalrusResponse = round_trip(include_str!(
+            "../../test_files/walrus/store_already_certified.json"
+        ));
+        assert_eq!(
+            certified.blob_id(),
+            "WNj9kV-79ScIKYpGmXsBBT0PjjyCeTkZYvUNtwUEr-A"
+        );
+    }
+
+    #[test]
+    fn test_command_fixtures() {
+        let read: ReadResponse = round_trip(include_str!("../../test_files/walrus/read.json"));
+        assert_eq!(read.out.as_deref(), Some("/tmp/download_test.txt"));
+
+        let statuses: Vec<BlobStatusResponse> =
+            round_trip(include_str!("../../test_files/walrus/blob_status.json"));
+        assert!(matches!(
+            statuses[0].status,
+            BlobStatus::Permanent {
+                endEpoch: 61,
+                isCertified: true,
+                ..
+            }
+        ));
+        assert_eq!(statuses[1].status, BlobStatus::Nonexistent);
+
+        let info: InfoResponse = round_trip(include_str!("../../test_files/walrus/info.json"));
+        assert_eq!(info.epochInfo.currentEpoch, 55);
+        assert_eq!(info.sizeInfo.unwrap().maxBlobSize, 14599718400);
+
+        let blobs: Vec<BlobObject> =
+            round_trip(include_str!("../../test_files/walrus/list_blobs.json"));
+        assert_eq!(blobs.len(), 2);
+        let info = BlobInfo::from(&blobs[1]);
+        assert_eq!(info.unencoded_size, "8.00 KiB");
+        assert_eq!(info.size, 8192);
+        assert!(!info.is_certified);
+        assert_eq!(info.expiration_epoch, 59);
+
+        let deleted: Vec<DeleteOutput> =
+            round_trip(include_str!("../../test_files/walrus/delete.json"));
+        assert_eq!(deleted[0].deletedBlobs.len(), 1);
+    }
+
+    #[test]
+    fn test_blob_page_fixture() {
+        let page: BlobResponse = round_trip(include_str!("../../test_files/walrus/blob_page.json"));
+        assert_eq!(page.content.len(), 2);
+        assert_eq!(page.numberOfElements, 2);
+        assert!(page.first && page.last);
+        assert_eq!(page.pageable.pageSize, 20);
+        assert_eq!(page.content[1].endEpoch, 61);
+        assert_eq!(
+            page.content[0].blobId,
+            "DVZWz_QCEb2D_UPQzswv-DUqg-etmV6rEPzoERY4Tgg"
+        );
+    }
+
+    #[test]
+    fn test_requests() {
+        let request = StoreRequest {
+            file: "a.db".to_string(),
+            epochs: 3,
+            deletable: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({"file": "a.db", "epochs": 3})
+        );
+        assert_eq!(
+            serde_json::to_value(ExtendRequest {
+                blobObjId: "0x1".to_string(),
+                epochsAhead: 2,
+            })
+            .unwrap(),
+            serde_json::json!({"blobObjId": "0x1", "epochsAhead": 2})
+        );
+        assert_eq!(ListBlobsRequest::COMMAND, "listBlobs");
+    }
 }
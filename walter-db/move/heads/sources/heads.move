@@ -0,0 +1,69 @@
+/// Head pointers of walter-db databases. The `Heads` object is shared, so
+/// every teammate's save goes through the same compare-and-swap and a save
+/// based on an outdated snapshot aborts instead of forking the chain.
+module walter_heads::heads;
+
+use std::string::String;
+use sui::vec_map::{Self, VecMap};
+
+/// The head moved since the save was based on it.
+const EStaleHead: u64 = 1;
+
+public struct Heads has key {
+    id: UID,
+    /// Snapshot blob ID by database name, `<database>@<branch>` for branches
+    /// other than main
+    heads: VecMap<String, String>,
+}
+
+/// Shares a new set of heads for a team to save through.
+public fun create(ctx: &mut TxContext) {
+    transfer::share_object(Heads {
+        id: object::new(ctx),
+        heads: vec_map::empty(),
+    });
+}
+
+/// Moves the head of `name` from `expected` to `new`, aborting with
+/// `EStaleHead` when it is no longer `expected`. A database without a head
+/// accepts any save.
+public fun compare_and_swap(heads: &mut Heads, name: String, expected: String, new: String) {
+    if (heads.heads.contains(&name)) {
+        let current = heads.heads.get_mut(&name);
+        assert!(*current == expected, EStaleHead);
+        *current = new;
+    } else {
+        heads.heads.insert(name, new);
+    }
+}
+
+public fun get(heads: &Heads, name: &String): Option<String> {
+    heads.heads.try_get(name)
+}
+
+#[test]
+fun test_compare_and_swap() {
+    let mut ctx = tx_context::dummy();
+    let mut heads = Heads { id: object::new(&mut ctx), heads: vec_map::empty() };
+    let name = b"db".to_string();
+
+    compare_and_swap(&mut heads, name, b"".to_string(), b"root".to_string());
+    compare_and_swap(&mut heads, name, b"root".to_string(), b"alice".to_string());
+    assert!(get(&heads, &name) == option::some(b"alice".to_string()));
+
+    let Heads { id, heads: _ } = heads;
+    id.delete();
+}
+
+#[test, expected_failure(abort_code = EStaleHead)]
+fun test_stale_save_aborts() {
+    let mut ctx = tx_context::dummy();
+    let mut heads = Heads { id: object::new(&mut ctx), heads: vec_map::empty() };
+    let name = b"db@feature".to_string();
+
+    compare_and_swap(&mut heads, name, b"".to_string(), b"alice".to_string());
+    compare_and_swap(&mut heads, name, b"root".to_string(), b"bob".to_string());
+
+    let Heads { id, heads: _ } = heads;
+    id.delete();
+}
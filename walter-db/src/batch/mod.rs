@@ -0,0 +1,172 @@
+use anyhow::anyhow;
+use std::ffi::CString;
+
+use crate::{dot_command, execute_query, Session};
+
+/// Runs every statement of `sql` in order, stopping at the first one that fails.
+pub fn run_script(session: &mut Session, sql: &str) -> anyhow::Result<()> {
+    for (index, (line, statement)) in split_statements(sql).into_iter().enumerate() {
+        let result = if statement.starts_with('.') {
+            dot_command(session, &statement)
+        } else {
+            execute_query(session, &statement)
+        };
+        if let Err(err) = result {
+            return Err(anyhow!(
+                "Statement {} (line {}) failed: {}\n  {}",
+                index + 1,
+                line,
+                err,
+                statement
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Snapshot commands take the rest of their line, as their `--options`
+/// would otherwise read as SQL comments.
+const LINE_COMMANDS: &[&str] = &[
+    "SAVE",
+    "REBASE",
+    "GC",
+    "RETENTION",
+    "PIN",
+    "UNPIN",
+    "BRANCH",
+    "TAG",
+    "SWITCH",
+    "REFS",
+];
+
+/// Splits `sql` into complete statements, each with the line it starts on.
+/// Lines starting with `.` or a snapshot command between statements are
+/// returned on their own.
+pub fn split_statements(sql: &str) -> Vec<(usize, String)> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut start_line = 1;
+    let mut line = 1;
+
+    for text in sql.split_inclusive('\n') {
+        if is_blank(&current) && is_line_command(text) {
+            statements.push((line, text.trim().to_string()));
+            current.clear();
+            line += 1;
+            continue;
+        }
+        for c in text.chars() {
+            if current.trim().is_empty() {
+                start_line = line;
+            }
+            current.push(c);
+            if c == '\n' {
+                line += 1;
+            }
+            // A ';' only ends a statement when it is not inside a string, comment or trigger body
+            if c == ';' && is_complete(&current) {
+                if !is_blank(&current) {
+                    statements.push((start_line, current.trim().to_string()));
+                }
+                current.clear();
+            }
+        }
+    }
+    if !is_blank(&current) {
+        statements.push((start_line, current.trim().to_string()));
+    }
+
+    statements
+}
+
+fn is_line_command(line: &str) -> bool {
+    let line = line.trim_start();
+    let keyword = line
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .next()
+        .unwrap_or_default();
+    line.starts_with('.')
+        || LINE_COMMANDS
+            .iter()
+            .any(|command| command.eq_ignore_ascii_case(keyword))
+}
+
+/// Whether `sql` holds nothing but comments, whitespace and semicolons.
+fn is_blank(sql: &str) -> bool {
+    let mut rest = sql;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ';');
+        if let Some(comment) = rest.strip_prefix("--") {
+            rest = comment.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.split_once("*/").map_or("", |(_, rest)| rest);
+        } else {
+            return rest.is_empty();
+        }
+    }
+}
+
+fn is_complete(sql: &str) -> bool {
+    match CString::new(sql) {
+        // SAFETY: sqlite3_complete only reads the NUL terminated string it is given
+        Ok(sql) => unsafe { rusqlite::ffi::sqlite3_complete(sql.as_ptr()) != 0 },
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_statements() {
+        let sql = "CREATE TABLE t (x TEXT);\nINSERT INTO t VALUES ('a;b');\n\n-- done;\nSELECT * FROM t;\n/* end */;\n-- end\n";
+        let statements = split_statements(sql);
+        assert_eq!(
+            statements,
+            vec![
+                (1, "CREATE TABLE t (x TEXT);".to_string()),
+                (2, "INSERT INTO t VALUES ('a;b');".to_string()),
+                (4, "-- done;\nSELECT * FROM t;".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_trigger() {
+        let sql = "CREATE TRIGGER tr AFTER INSERT ON t BEGIN\n  DELETE FROM u;\n  DELETE FROM v;\nEND;\nSELECT 1;";
+        let statements = split_statements(sql);
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[1], (5, "SELECT 1;".to_string()));
+    }
+
+    #[test]
+    fn test_split_line_commands() {
+        let sql = "save -m \"it's done\"\nsavepoint a;\ngc --dry-run\nselect 1;";
+        let statements = split_statements(sql);
+        assert_eq!(
+            statements,
+            vec![
+                (1, "save -m \"it's done\"".to_string()),
+                (2, "savepoint a;".to_string()),
+                (3, "gc --dry-run".to_string()),
+                (4, "select 1;".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_output_commands() {
+        let sql = ".mode csv\nSELECT 1;\n  .headers off\nSELECT\n.5;\n";
+        let statements = split_statements(sql);
+        assert_eq!(
+            statements,
+            vec![
+                (1, ".mode csv".to_string()),
+                (2, "SELECT 1;".to_string()),
+                (3, ".headers off".to_string()),
+                (4, "SELECT\n.5;".to_string()),
+            ]
+        );
+    }
+}
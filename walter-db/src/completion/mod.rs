@@ -0,0 +1,323 @@
+use rusqlite::Connection;
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::{Hinter, HistoryHinter};
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use std::borrow::Cow;
+
+use crate::workspace::Workspace;
+
+const SHELL_COMMANDS: &[&str] = &[
+    "exit",
+    "quit",
+    "help",
+    "tables",
+    "databases",
+    "describe",
+    "history",
+    "open",
+    "attach",
+    "detach",
+    "encrypt",
+    "save",
+    "rollback",
+    "rebase",
+    "retention",
+    "pin",
+    "unpin",
+    "gc",
+    "branch",
+    "tag",
+    "switch",
+    "refs",
+    ".mode",
+    ".headers",
+    ".output",
+    ".import",
+    ".export",
+];
+
+const SQL_KEYWORDS: &[&str] = &[
+    "ABORT",
+    "ADD",
+    "ALL",
+    "ALTER",
+    "ANALYZE",
+    "AND",
+    "AS",
+    "ASC",
+    "ATTACH",
+    "AUTOINCREMENT",
+    "BEGIN",
+    "BETWEEN",
+    "BY",
+    "CASE",
+    "CAST",
+    "CHECK",
+    "COLLATE",
+    "COLUMN",
+    "COMMIT",
+    "CONFLICT",
+    "CONSTRAINT",
+    "CREATE",
+    "CROSS",
+    "DEFAULT",
+    "DELETE",
+    "DESC",
+    "DISTINCT",
+    "DROP",
+    "ELSE",
+    "END",
+    "ESCAPE",
+    "EXCEPT",
+    "EXISTS",
+    "EXPLAIN",
+    "FOREIGN",
+    "FROM",
+    "GLOB",
+    "GROUP",
+    "HAVING",
+    "IF",
+    "IN",
+    "INDEX",
+    "INNER",
+    "INSERT",
+    "INTEGER",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "JOIN",
+    "KEY",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "NOT",
+    "NULL",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "PRAGMA",
+    "PRIMARY",
+    "REAL",
+    "REFERENCES",
+    "RELEASE",
+    "RENAME",
+    "REPLACE",
+    "RETURNING",
+    "SAVEPOINT",
+    "SELECT",
+    "SET",
+    "TABLE",
+    "TEXT",
+    "BLOB",
+    "THEN",
+    "TRANSACTION",
+    "TRIGGER",
+    "UNION",
+    "UNIQUE",
+    "UPDATE",
+    "USING",
+    "VACUUM",
+    "VALUES",
+    "VIEW",
+    "WHEN",
+    "WHERE",
+    "WITH",
+];
+
+const MODES: &[&str] = &["box", "csv", "json", "markdown"];
+
+/// Completion and history hints for the walter-db shell. The schema is
+/// cached and refreshed by the shell after every command.
+#[derive(Default)]
+pub struct ShellHelper {
+    tables: Vec<String>,
+    columns: Vec<(String, String)>,
+    hinter: HistoryHinter,
+}
+
+impl ShellHelper {
+    /// Reloads the table and column names of every database open on `conn`.
+    pub fn refresh(&mut self, conn: &Connection) {
+        self.tables.clear();
+        self.columns.clear();
+        for database in database_names(conn) {
+            for table in table_names(conn, &database) {
+                for column in column_names(conn, &database, &table) {
+                    self.columns.push((table.clone(), column));
+                }
+                self.tables.push(table);
+            }
+        }
+        self.tables.sort();
+        self.tables.dedup();
+    }
+
+    /// Candidates for `word`, the word being typed after `before`.
+    fn candidates(&self, before: &str, word: &str) -> Vec<String> {
+        let previous = before.split_whitespace().last().unwrap_or_default();
+
+        if let Some((table, column)) = word.split_once('.') {
+            return self
+                .columns
+                .iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case(table))
+                .map(|(_, name)| name)
+                .filter(|name| starts_with_ignore_case(name, column))
+                .map(|name| format!("{}.{}", table, name))
+                .collect();
+        }
+
+        let options: Vec<String> = match previous {
+            "" => SHELL_COMMANDS
+                .iter()
+                .map(|command| command.to_string())
+                .chain(
+                    SQL_KEYWORDS
+                        .iter()
+                        .map(|keyword| keyword_case(keyword, word)),
+                )
+                .collect(),
+            "describe" => self.tables.clone(),
+            ".mode" => MODES.iter().map(|mode| mode.to_string()).collect(),
+            ".headers" => vec!["on".to_string(), "off".to_string()],
+            "open" | "attach" => Workspace::list(),
+            _ => SQL_KEYWORDS
+                .iter()
+                .map(|keyword| keyword_case(keyword, word))
+                .chain(self.tables.iter().cloned())
+                .chain(self.columns.iter().map(|(_, column)| column.clone()))
+                .collect(),
+        };
+
+        let mut candidates: Vec<String> = options
+            .into_iter()
+            .filter(|option| starts_with_ignore_case(option, word))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+}
+
+/// Keywords are completed in the case the word is being typed in.
+fn keyword_case(keyword: &str, word: &str) -> String {
+    if !word.is_empty() && word.chars().all(|c| !c.is_ascii_uppercase()) {
+        keyword.to_lowercase()
+    } else {
+        keyword.to_string()
+    }
+}
+
+fn starts_with_ignore_case(option: &str, prefix: &str) -> bool {
+    option.len() >= prefix.len()
+        && option.is_char_boundary(prefix.len())
+        && option[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+fn database_names(conn: &Connection) -> Vec<String> {
+    conn.prepare("SELECT name FROM pragma_database_list")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        })
+        .unwrap_or_default()
+}
+
+fn table_names(conn: &Connection, database: &str) -> Vec<String> {
+    let query = format!(
+        "SELECT name FROM \"{}\".sqlite_master WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%'",
+        database
+    );
+    conn.prepare(&query)
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        })
+        .unwrap_or_default()
+}
+
+fn column_names(conn: &Connection, database: &str, table: &str) -> Vec<String> {
+    conn.prepare("SELECT name FROM pragma_table_info(?1, ?2)")
+        .and_then(|mut stmt| {
+            stmt.query_map([table, database], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        })
+        .unwrap_or_default()
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos]
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .map(|index| index + 1)
+            .unwrap_or(0);
+        let candidates = self
+            .candidates(&line[..start], &line[start..pos])
+            .into_iter()
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: candidate,
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+
+    fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<String> {
+        self.hinter.hint(line, pos, ctx)
+    }
+}
+
+impl Highlighter for ShellHelper {
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("\x1b[2m{}\x1b[0m", hint))
+    }
+}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completion_candidates() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE users (id INTEGER, name TEXT); CREATE TABLE usage (bytes);",
+        )
+        .unwrap();
+        let mut helper = ShellHelper::default();
+        helper.refresh(&conn);
+
+        assert_eq!(helper.candidates("", "des"), vec!["desc", "describe"]);
+        assert_eq!(helper.candidates("", "SEL"), vec!["SELECT"]);
+        assert_eq!(helper.candidates("describe ", "us"), vec!["usage", "users"]);
+        assert_eq!(
+            helper.candidates("select * from ", "us"),
+            vec!["usage", "users", "using"]
+        );
+        assert_eq!(helper.candidates("select ", "na"), vec!["name"]);
+        assert_eq!(
+            helper.candidates("select ", "users."),
+            vec!["users.id", "users.name"]
+        );
+        assert_eq!(helper.candidates(".mode ", "c"), vec!["csv"]);
+    }
+}
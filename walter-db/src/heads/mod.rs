@@ -0,0 +1,315 @@
+use anyhow::anyhow;
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
+
+use crate::workspace::Workspace;
+
+const HEADS_ENV: &str = "WALTER_DB_HEADS";
+const SUI_PACKAGE_ENV: &str = "WALTER_DB_HEADS_PACKAGE";
+const SUI_OBJECT_ENV: &str = "WALTER_DB_HEADS_OBJECT";
+const HEADS_DIR: &str = ".heads";
+const LOCK_RETRIES: u32 = 50;
+const STALE_LOCK: Duration = Duration::from_secs(10);
+/// In MIST, far above what a compare-and-swap costs
+const SUI_GAS_BUDGET: &str = "10000000";
+
+/// Shared record of the latest snapshot of each database. Every save moves
+/// it with a compare-and-swap, so a save based on an outdated snapshot is
+/// rejected instead of silently forking the chain.
+pub trait HeadPointer: Send + Sync {
+    fn get(&self, name: &str) -> anyhow::Result<Option<String>>;
+
+    /// Moves the head of `name` from `expected` to `new`. When the head is
+    /// no longer `expected`, nothing changes and the current head is returned
+    /// as the error. A database without a recorded head accepts any save.
+    fn compare_and_swap(
+        &self,
+        name: &str,
+        expected: Option<&str>,
+        new: &str,
+    ) -> anyhow::Result<Result<(), String>>;
+}
+
+/// Raised when a database was saved elsewhere since this copy was checked out.
+#[derive(Debug)]
+pub struct Conflict {
+    pub name: String,
+    pub base: Option<String>,
+    pub head: String,
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "'{}' was saved elsewhere: its head is now {} but this copy is based on {}. Run `rebase` to replay your changes on top of it",
+            self.name,
+            self.head,
+            self.base.as_deref().unwrap_or("nothing")
+        )
+    }
+}
+
+impl std::error::Error for Conflict {}
+
+/// The shared `Heads` object on Sui when `WALTER_DB_HEADS_PACKAGE` and
+/// `WALTER_DB_HEADS_OBJECT` are set, otherwise heads kept in a directory.
+pub fn from_env() -> Box<dyn HeadPointer> {
+    match (
+        std::env::var(SUI_PACKAGE_ENV),
+        std::env::var(SUI_OBJECT_ENV),
+    ) {
+        (Ok(package), Ok(object)) => Box::new(SuiHeads::new(package, object)),
+        _ => Box::new(LocalHeads::from_env()),
+    }
+}
+
+/// Head pointers in a shared object of the `walter_heads` Move package
+/// (`walter-db/move/heads`). Sui orders every transaction on a shared
+/// object, so two teammates saving at once can't both move the head.
+pub struct SuiHeads {
+    package: String,
+    object: String,
+}
+
+impl SuiHeads {
+    pub fn new(package: String, object: String) -> Self {
+        SuiHeads { package, object }
+    }
+}
+
+impl HeadPointer for SuiHeads {
+    fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        let object = run_sui(&["client", "object", &self.object, "--json"])?;
+        parse_sui_heads(&object, name)
+    }
+
+    fn compare_and_swap(
+        &self,
+        name: &str,
+        expected: Option<&str>,
+        new: &str,
+    ) -> anyhow::Result<Result<(), String>> {
+        // Checking first saves paying for a transaction that would abort
+        if let Some(current) = self.get(name)? {
+            if expected != Some(current.as_str()) {
+                return Ok(Err(current));
+            }
+        }
+
+        let args = [
+            "client",
+            "call",
+            "--package",
+            &self.package,
+            "--module",
+            "heads",
+            "--function",
+            "compare_and_swap",
+            "--args",
+            &self.object,
+            name,
+            expected.unwrap_or(""),
+            new,
+            "--gas-budget",
+            SUI_GAS_BUDGET,
+            "--json",
+        ];
+        match run_sui(&args) {
+            Ok(_) => Ok(Ok(())),
+            // Someone else saved between the check and the call
+            Err(err) => match self.get(name)? {
+                Some(current) if expected != Some(current.as_str()) => Ok(Err(current)),
+                _ => Err(err),
+            },
+        }
+    }
+}
+
+fn run_sui(args: &[&str]) -> anyhow::Result<Value> {
+    let output = Command::new("sui")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| anyhow!("Failed to run sui: {}", err))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "sui failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|err| anyhow!("Failed to parse sui output: {}", err))
+}
+
+/// The head of `name` in the `sui client object --json` output of a `Heads`
+/// object, whose `VecMap` lists its entries under `contents`.
+fn parse_sui_heads(object: &Value, name: &str) -> anyhow::Result<Option<String>> {
+    let entries = object["content"]["fields"]["heads"]["fields"]["contents"]
+        .as_array()
+        .ok_or_else(|| anyhow!("The Sui object is not a walter_heads::heads::Heads"))?;
+    Ok(entries
+        .iter()
+        .map(|entry| &entry["fields"])
+        .find(|fields| fields["key"] == name)
+        .and_then(|fields| fields["value"].as_str())
+        .map(|head| head.to_string()))
+}
+
+/// Head pointers kept as one file per database in a directory, by default
+/// `~/.walter/db/.heads`. Used when no Sui heads object is configured and
+/// in tests; pointing `WALTER_DB_HEADS` at a directory on a network share
+/// guards a database between machines without Sui.
+pub struct LocalHeads {
+    dir: PathBuf,
+}
+
+impl LocalHeads {
+    pub fn new(dir: PathBuf) -> Self {
+        LocalHeads { dir }
+    }
+
+    pub fn from_env() -> Self {
+        match std::env::var(HEADS_ENV) {
+            Ok(dir) => Self::new(PathBuf::from(shellexpand::tilde(&dir).to_string())),
+            Err(_) => Self::new(Workspace::root().join(HEADS_DIR)),
+        }
+    }
+
+    /// Takes the lock for `name`, clearing locks left behind by crashed processes.
+    fn lock(&self, name: &str) -> anyhow::Result<Lock> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!("{}.lock", name));
+        for _ in 0..LOCK_RETRIES {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Ok(Lock { path }),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                        .is_some_and(|age| age > STALE_LOCK);
+                    if stale {
+                        let _ = fs::remove_file(&path);
+                    } else {
+                        std::thread::sleep(Duration::from_millis(100));
+                    }
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Err(anyhow!("Timed out waiting for the head of '{}'", name))
+    }
+}
+
+struct Lock {
+    path: PathBuf,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl HeadPointer for LocalHeads {
+    fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        match fs::read_to_string(self.dir.join(name)) {
+            Ok(head) => Ok(Some(head.trim().to_string()).filter(|head| !head.is_empty())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn compare_and_swap(
+        &self,
+        name: &str,
+        expected: Option<&str>,
+        new: &str,
+    ) -> anyhow::Result<Result<(), String>> {
+        let _lock = self.lock(name)?;
+        if let Some(current) = self.get(name)? {
+            if expected != Some(current.as_str()) {
+                return Ok(Err(current));
+            }
+        }
+
+        let temp_path = self.dir.join(format!("{}.tmp", name));
+        fs::write(&temp_path, new)?;
+        fs::rename(&temp_path, self.dir.join(name))?;
+        Ok(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_heads_compare_and_swap() {
+        let dir = PathBuf::from("/tmp/walter_db_test/heads_test");
+        let _ = fs::remove_dir_all(&dir);
+        let heads = LocalHeads::new(dir);
+
+        assert_eq!(heads.get("shared").unwrap(), None);
+        heads
+            .compare_and_swap("shared", None, "root")
+            .unwrap()
+            .unwrap();
+        heads
+            .compare_and_swap("shared", Some("root"), "alice")
+            .unwrap()
+            .unwrap();
+
+        // Bob saves on top of the root after Alice already did
+        assert_eq!(
+            heads
+                .compare_and_swap("shared", Some("root"), "bob")
+                .unwrap(),
+            Err("alice".to_string())
+        );
+        assert_eq!(heads.get("shared").unwrap(), Some("alice".to_string()));
+        assert!(!PathBuf::from("/tmp/walter_db_test/heads_test/shared.lock").exists());
+    }
+
+    #[test]
+    fn test_parse_sui_heads() {
+        let object: Value = serde_json::from_str(
+            r#"{
+                "objectId": "0x5",
+                "content": {
+                    "dataType": "moveObject",
+                    "type": "0x9::heads::Heads",
+                    "fields": {
+                        "id": {"id": "0x5"},
+                        "heads": {
+                            "type": "0x2::vec_map::VecMap<0x1::string::String, 0x1::string::String>",
+                            "fields": {"contents": [
+                                {"type": "0x2::vec_map::Entry", "fields": {"key": "shared", "value": "alice"}},
+                                {"type": "0x2::vec_map::Entry", "fields": {"key": "shared@feature", "value": "bob"}}
+                            ]}
+                        }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_sui_heads(&object, "shared").unwrap(),
+            Some("alice".to_string())
+        );
+        assert_eq!(
+            parse_sui_heads(&object, "shared@feature").unwrap(),
+            Some("bob".to_string())
+        );
+        assert_eq!(parse_sui_heads(&object, "other").unwrap(), None);
+        assert!(parse_sui_heads(&serde_json::json!({"content": {}}), "shared").is_err());
+    }
+}
@@ -1,30 +1,469 @@
+use rusqlite::hooks::{AuthAction, AuthContext, Authorization};
 use rusqlite::{params, Connection, Result};
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
+use std::collections::HashSet;
+use std::io::{IsTerminal, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+mod batch;
+mod completion;
+mod heads;
+mod output;
+mod refs;
+mod retention;
+mod serve;
+mod transfer;
 mod walrus_io;
+mod workspace;
+
+use completion::ShellHelper;
+use heads::HeadPointer;
+use output::Output;
+use retention::{Action, GcReport};
+use workspace::Workspace;
+
+const PASSWORD_ENV: &str = "WALTER_DB_PASSWORD";
+const HISTORY_FILE: &str = "~/.walter/db_history";
+
+/// Names of the databases written by the statements prepared on a session's
+/// connection, recorded by its authorizer.
+type Written = Arc<Mutex<HashSet<String>>>;
+
+/// The open working copies: `main` plus any databases attached under their own name.
+struct Session {
+    conn: Connection,
+    written: Written,
+    main: Workspace,
+    attached: Vec<Workspace>,
+    output: Output,
+    heads: Box<dyn HeadPointer>,
+}
+
+impl Session {
+    fn open(main: Workspace) -> anyhow::Result<Session> {
+        let written = Written::default();
+        let conn = open_connection(&main.db_path(), &written)?;
+        Ok(Session {
+            conn,
+            written,
+            main,
+            attached: Vec::new(),
+            output: Output::default(),
+            heads: heads::from_env(),
+        })
+    }
+
+    /// Whether the statements prepared since the last call may have changed
+    /// the main database, and so belong in its journal. Writes that only go
+    /// to attached databases would fail when replayed against main.
+    fn take_writes_main(&self) -> bool {
+        let written = std::mem::take(&mut *self.written.lock().unwrap());
+        written.is_empty()
+            || written
+                .iter()
+                .any(|database| database == "main" || database == "temp")
+    }
+
+    fn attach(&mut self, workspace: Workspace) -> anyhow::Result<()> {
+        if self.workspace(&workspace.name).is_some() {
+            return Err(anyhow::anyhow!("'{}' is already open", workspace.name));
+        }
+        self.conn.execute(
+            &format!("ATTACH DATABASE ?1 AS \"{}\"", workspace.name),
+            params![workspace.db_path().to_string_lossy()],
+        )?;
+        self.attached.push(workspace);
+        Ok(())
+    }
+
+    fn detach(&mut self, name: &str) -> anyhow::Result<Workspace> {
+        let index = self
+            .attached
+            .iter()
+            .position(|workspace| workspace.name == name)
+            .ok_or_else(|| anyhow::anyhow!("'{}' is not attached", name))?;
+        self.conn
+            .execute(&format!("DETACH DATABASE \"{}\"", name), params![])?;
+        Ok(self.attached.remove(index))
+    }
+
+    /// Makes `main` the main database, keeping everything else attached.
+    fn switch(&mut self, main: Workspace) -> anyhow::Result<()> {
+        self.ensure_no_transaction("open another database")?;
+        self.conn = open_connection(&main.db_path(), &self.written)?;
+        let attached = std::mem::take(&mut self.attached);
+        let previous = std::mem::replace(&mut self.main, main);
+        for workspace in std::iter::once(previous).chain(attached) {
+            if workspace.name != self.main.name {
+                self.attach(workspace)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn workspace(&self, name: &str) -> Option<&Workspace> {
+        std::iter::once(&self.main)
+            .chain(self.attached.iter())
+            .find(|workspace| workspace.name == name)
+    }
+
+    fn workspace_mut(&mut self, name: &str) -> Option<&mut Workspace> {
+        std::iter::once(&mut self.main)
+            .chain(self.attached.iter_mut())
+            .find(|workspace| workspace.name == name)
+    }
+
+    /// Fails while a `BEGIN` transaction is open, as its changes are not in
+    /// the working copies yet.
+    fn ensure_no_transaction(&self, action: &str) -> anyhow::Result<()> {
+        if self.conn.is_autocommit() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Cannot {} while a transaction is open: COMMIT or ROLLBACK it first",
+                action
+            ))
+        }
+    }
+
+    /// Uploads a snapshot of `name` (or main) and returns its blob ID. With
+    /// `vacuum` the uploaded copy is compacted first.
+    fn save(
+        &self,
+        name: Option<&str>,
+        vacuum: bool,
+        message: Option<&str>,
+    ) -> anyhow::Result<String> {
+        self.ensure_no_transaction("save")?;
+        let name = name.unwrap_or(&self.main.name);
+        self.workspace(name)
+            .ok_or_else(|| anyhow::anyhow!("'{}' is not open", name))?
+            .save(self.heads.as_ref(), None, vacuum, message)
+    }
+
+    /// Resets the working copy of `name` (or main) to its head snapshot.
+    fn rollback(&mut self, name: Option<&str>) -> anyhow::Result<Option<String>> {
+        self.ensure_no_transaction("roll back to a snapshot")?;
+        let name = name.unwrap_or(&self.main.name).to_string();
+        let head = self
+            .workspace(&name)
+            .ok_or_else(|| anyhow::anyhow!("'{}' is not open", name))?
+            .head()
+            .ok_or_else(|| anyhow::anyhow!("'{}' has no saved snapshot", name))?;
+
+        self.with_closed(&name, |workspace| workspace.checkout(&head))
+    }
+
+    /// Replays the changes made to main since its last snapshot on top of
+    /// the latest shared head, returning that head and the number of changes.
+    fn rebase(&mut self) -> anyhow::Result<(String, usize)> {
+        self.ensure_no_transaction("rebase")?;
+        let name = self.main.name.clone();
+        let head = self
+            .heads
+            .get(&self.main.head_key())?
+            .ok_or_else(|| anyhow::anyhow!("'{}' has no shared head to rebase onto", name))?;
+        if self.main.head().as_ref() == Some(&head) {
+            return Err(anyhow::anyhow!("'{}' is already up to date", name));
+        }
+
+        let replayed = self.with_closed(&name, |workspace| workspace.rebase(&head))?;
+        Ok((head, replayed))
+    }
+
+    /// Runs the retention policy of `name` (or main), never dropping the
+    /// snapshots the shared heads of its branches point at.
+    fn gc(&self, name: Option<&str>, dry_run: bool) -> anyhow::Result<GcReport> {
+        let name = name.unwrap_or(&self.main.name);
+        let workspace = self
+            .workspace(name)
+            .ok_or_else(|| anyhow::anyhow!("'{}' is not open", name))?;
+        let mut shared_heads = Vec::new();
+        for branch in workspace.refs().branches.keys() {
+            shared_heads.extend(self.heads.get(&workspace.branch_key(branch))?);
+        }
+        workspace.gc(&shared_heads, dry_run)
+    }
+
+    /// Checks out the tip of `branch` of main and saves onto it from then on.
+    fn switch_branch(&mut self, branch: &str) -> anyhow::Result<String> {
+        self.ensure_no_transaction("switch branches")?;
+        let name = self.main.name.clone();
+        self.with_closed(&name, |workspace| workspace.switch(branch))
+    }
+
+    /// Runs `f` on the workspace `name` while its working file is closed.
+    fn with_closed<T>(
+        &mut self,
+        name: &str,
+        f: impl FnOnce(&Workspace) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        if name == self.main.name {
+            let attached = std::mem::take(&mut self.attached);
+            self.conn = Connection::open_in_memory()?;
+            let result = f(&self.main);
+            self.conn = open_connection(&self.main.db_path(), &self.written)?;
+            for workspace in attached {
+                self.attach(workspace)?;
+            }
+            result
+        } else {
+            let workspace = self.detach(name)?;
+            let result = f(&workspace);
+            self.attach(workspace)?;
+            result
+        }
+    }
+}
+
+/// Opens the workspace `name`, checking out `blob_id` into it when given.
+/// Encrypted workspaces ask for their key when `key` is not given.
+fn open_workspace(
+    name: &str,
+    blob_id: Option<&str>,
+    key: Option<String>,
+) -> anyhow::Result<Workspace> {
+    let mut workspace = Workspace::open(name)?;
+    unlock(&mut workspace, key)?;
+
+    match blob_id {
+        Some(blob_id) => {
+            workspace.checkout(blob_id)?;
+        }
+        None if !workspace.has_working_copy() => {
+            return Err(anyhow::anyhow!(
+                "No working copy for '{}'. Pass a blob ID to check one out.",
+                name
+            ));
+        }
+        None => {}
+    }
+    Ok(workspace)
+}
+
+/// Sets the key of an encrypted database, asking for it when not given.
+fn unlock(workspace: &mut Workspace, key: Option<String>) -> anyhow::Result<()> {
+    match key {
+        Some(key) => workspace.set_key(key),
+        None if workspace.is_encrypted() => {
+            let key = read_key(&format!("Key for '{}': ", workspace.name))?;
+            workspace.set_key(key)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Removes `--password` or `--key-file <path>` from `args` and returns the key
+/// they stand for. New keys are asked for twice.
+fn take_key(args: &mut Vec<String>, new_key: bool) -> anyhow::Result<Option<String>> {
+    if let Some(path) = take_option(args, "--key-file")? {
+        let key = std::fs::read_to_string(&path)
+            .map_err(|err| anyhow::anyhow!("Failed to read key file {}: {}", path, err))?;
+        return Ok(Some(key.trim().to_string()));
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--password") {
+        args.remove(index);
+        return Ok(Some(prompt_key(new_key)?));
+    }
+
+    Ok(None)
+}
+
+/// Asks for a password, twice for a `new_key` typed at the terminal.
+fn prompt_key(new_key: bool) -> anyhow::Result<String> {
+    let key = read_key("Password: ")?;
+    if new_key && std::env::var(PASSWORD_ENV).is_err() && read_key("Confirm password: ")? != key {
+        return Err(anyhow::anyhow!("Passwords do not match"));
+    }
+    Ok(key)
+}
+
+/// Removes `flag` and the value following it from `args`, returning the value.
+fn take_option(args: &mut Vec<String>, flag: &str) -> anyhow::Result<Option<String>> {
+    let Some(index) = args.iter().position(|arg| arg == flag) else {
+        return Ok(None);
+    };
+    args.remove(index);
+    if index >= args.len() {
+        return Err(anyhow::anyhow!("{} needs a value", flag));
+    }
+    Ok(Some(args.remove(index)))
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> anyhow::Result<T> {
+    value
+        .parse()
+        .map_err(|_| anyhow::anyhow!("{} needs a whole number, not '{}'", flag, value))
+}
+
+/// Reads a key from `WALTER_DB_PASSWORD`, or from the terminal without echoing it.
+fn read_key(prompt: &str) -> anyhow::Result<String> {
+    match std::env::var(PASSWORD_ENV) {
+        Ok(key) => Ok(key),
+        Err(_) => Ok(rpassword::prompt_password(prompt)?),
+    }
+}
 
 pub fn main() -> anyhow::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: {} <blobID>", args[0]);
-        std::process::exit(1);
-    }
-    std::fs::File::create("/tmp/sqlite.db").expect("Unable to create file");
-    let blob_id = &args[1];
-    let blob_id = blob_id.to_string();
-    let mut blob_id =
-        match walrus_io::download_and_extract_id(blob_id.clone(), "/tmp/sqlite.db".to_string()) {
-            Some(blob_id) => blob_id,
-            None => "".to_string(),
-        };
-    let conn = Connection::open("/tmp/sqlite.db")?;
-    let mut rl = Editor::<(), _>::new()?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    run(&args)
+}
+
+/// Runs walter-db with `args`, not including the program name.
+pub fn run(args: &[String]) -> anyhow::Result<()> {
+    let mut args = args.to_vec();
+    let creating = matches!(args.first().map(|arg| arg.as_str()), Some("new" | "import"));
+    let key = take_key(&mut args, creating)?;
+    let sql = take_option(&mut args, "-e")?;
+    let script_file = take_option(&mut args, "-f")?;
+    let save_message = take_option(&mut args, "--save")?;
+    let vacuum = take_flag(&mut args, "--vacuum");
+
+    match args.first().map(|arg| arg.as_str()) {
+        None => {
+            print_usage();
+            std::process::exit(1);
+        }
+        Some("new") => {
+            let Some(name) = args.get(1) else {
+                print_usage();
+                std::process::exit(1);
+            };
+            let mut workspace = Workspace::open(name)?;
+            workspace.init()?;
+            if let Some(key) = key {
+                workspace.set_key(key)?;
+            }
+            create_root_snapshot(&workspace)
+        }
+        Some("import") => {
+            let Some(file) = args.get(1).map(std::path::Path::new) else {
+                print_usage();
+                std::process::exit(1);
+            };
+            let name = match args.get(2) {
+                Some(name) => name.clone(),
+                None => file
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            };
+            let mut workspace = Workspace::open(&name)?;
+            workspace.import(file)?;
+            if let Some(key) = key {
+                workspace.set_key(key)?;
+            }
+            create_root_snapshot(&workspace)
+        }
+        Some("serve") => {
+            let address = format!(
+                "{}:{}",
+                take_option(&mut args, "--bind")?
+                    .as_deref()
+                    .unwrap_or("127.0.0.1"),
+                take_option(&mut args, "--port")?
+                    .as_deref()
+                    .unwrap_or("8080")
+            );
+            let cors = take_flag(&mut args, "--cors");
+            let interval = match take_option(&mut args, "--interval")? {
+                Some(secs) => secs
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("--interval needs a number of seconds"))?,
+                None => 30,
+            };
+            let Some(name) = args.get(1) else {
+                print_usage();
+                std::process::exit(1);
+            };
+            let mut workspace = Workspace::open(name)?;
+            unlock(&mut workspace, key)?;
+            let server = serve::Server::open(workspace, args.get(2).cloned(), cors)?;
+            server.run(&address, std::time::Duration::from_secs(interval))
+        }
+        Some(name) => {
+            let workspace = open_workspace(name, args.get(1).map(|s| s.as_str()), key)?;
+            let mut session = Session::open(workspace)?;
+
+            let script =
+                match (sql, script_file) {
+                    (Some(sql), _) => Some(sql),
+                    (None, Some(path)) => Some(std::fs::read_to_string(&path).map_err(|err| {
+                        anyhow::anyhow!("Failed to read script {}: {}", path, err)
+                    })?),
+                    (None, None) if !std::io::stdin().is_terminal() => {
+                        let mut sql = String::new();
+                        std::io::stdin().read_to_string(&mut sql)?;
+                        Some(sql)
+                    }
+                    (None, None) => None,
+                };
+
+            match script {
+                Some(script) => {
+                    batch::run_script(&mut session, &script)?;
+                    if let Some(message) = save_message {
+                        let blob_id = session.save(None, vacuum, Some(&message))?;
+                        println!("CURRENT Blob ID: {}", blob_id);
+                    }
+                    Ok(())
+                }
+                None if save_message.is_some() => Err(anyhow::anyhow!(
+                    "--save needs SQL from -e, -f or standard input"
+                )),
+                None => shell(session),
+            }
+        }
+    }
+}
+
+fn print_usage() {
+    eprintln!(
+        "Usage: walter-db <name> [blobID]           Open a database, checking out blobID if given"
+    );
+    eprintln!("       walter-db new <name>                Create an empty database");
+    eprintln!("       walter-db import <file.db> [name]   Import an existing SQLite file");
+    eprintln!("       walter-db serve <name> [blobID]     Answer read-only SQL over HTTP [--bind <addr>] [--port <port>] [--interval <secs>] [--cors]");
+    eprintln!("Run SQL without the shell with -e \"<sql>\", -f script.sql or piped input, and --save \"<message>\" [--vacuum] to snapshot afterwards");
+    eprintln!("Encrypt snapshots with --password (or WALTER_DB_PASSWORD) or --key-file <path>");
+    let names = Workspace::list();
+    if !names.is_empty() {
+        eprintln!("Local databases: {}", names.join(", "));
+    }
+}
+
+fn create_root_snapshot(workspace: &Workspace) -> anyhow::Result<()> {
+    let blob_id = workspace.save(
+        heads::from_env().as_ref(),
+        None,
+        false,
+        Some("Initial snapshot"),
+    )?;
+    println!(
+        "Created '{}' in {}",
+        workspace.name,
+        workspace.dir.display()
+    );
+    println!("Root snapshot ID: {}", blob_id);
+    Ok(())
+}
+
+fn shell(mut session: Session) -> anyhow::Result<()> {
+    let mut rl = Editor::new()?;
+    rl.set_helper(Some(ShellHelper::default()));
+    let history_path = std::path::PathBuf::from(shellexpand::tilde(HISTORY_FILE).to_string());
+    let _ = rl.load_history(&history_path);
 
     println!(
         "\x1b[1;34mWalruSQL - SQLite on Walrus with rollbacking\x1b[0m\n\x1b[1;32mType 'help' for available commands\x1b[0m\n\x1b[1;31mType 'exit' or 'quit' to close the shell\x1b[0m"
     );
 
     loop {
+        if let Some(helper) = rl.helper_mut() {
+            helper.refresh(&session.conn);
+        }
         let readline = rl.readline("\x1b[1;33msqlite>\x1b[0m ");
         match readline {
             Ok(line) => {
@@ -32,13 +471,67 @@ pub fn main() -> anyhow::Result<()> {
                 match line.trim() {
                     "exit" | "quit" => break,
                     "help" => print_help(),
-                    "tables" => list_tables(&conn)?,
+                    "tables" => list_tables(&session.conn)?,
+                    "databases" => list_databases(&session),
+                    cmd if cmd == "history" || cmd.starts_with("history ") => {
+                        let name = cmd.split_whitespace().nth(1).unwrap_or(&session.main.name);
+                        match session.workspace(name) {
+                            Some(workspace) => print_history(workspace),
+                            None => println!("'{}' is not open", name),
+                        }
+                    }
                     cmd if cmd.starts_with("describe ") => {
                         let table_name = cmd.split_whitespace().nth(1).unwrap_or("");
-                        describe_table(&conn, table_name)?;
+                        describe_table(&session.conn, table_name)?;
+                    }
+                    cmd if cmd.starts_with("open ") || cmd.starts_with("attach ") => {
+                        let mut parts: Vec<String> =
+                            cmd.split_whitespace().map(|s| s.to_string()).collect();
+                        let command = parts.remove(0);
+                        let name = parts.first().cloned().unwrap_or_default();
+                        let result = take_key(&mut parts, false)
+                            .and_then(|key| {
+                                open_workspace(&name, parts.get(1).map(|s| s.as_str()), key)
+                            })
+                            .and_then(|workspace| {
+                                if command == "open" {
+                                    session.switch(workspace)
+                                } else {
+                                    session.attach(workspace)
+                                }
+                            });
+                        if let Err(err) = result {
+                            println!("Failed to {} '{}': {}", command, name, err);
+                        }
+                    }
+                    cmd if cmd == "encrypt" || cmd.starts_with("encrypt ") => {
+                        let name = cmd.split_whitespace().nth(1);
+                        match encrypt_workspace(&mut session, name) {
+                            Ok(name) => println!(
+                                "Snapshots of '{}' will be encrypted from the next save",
+                                name
+                            ),
+                            Err(err) => println!("Failed to enable encryption: {}", err),
+                        }
+                    }
+                    cmd if cmd.starts_with('.') => match dot_command(&mut session, cmd) {
+                        Ok(()) if cmd.starts_with(".output") => match session.output.target() {
+                            Some(path) => println!("Writing query results to {}", path),
+                            None => println!("Writing query results to stdout"),
+                        },
+                        Ok(()) => {}
+                        Err(err) => println!("{}", err),
+                    },
+                    cmd if cmd.starts_with("detach ") => {
+                        let name = cmd.split_whitespace().nth(1).unwrap_or("");
+                        if let Err(err) = session.detach(name) {
+                            println!("Failed to detach '{}': {}", name, err);
+                        }
                     }
                     query if !query.is_empty() => {
-                        execute_query(&conn, query, &mut blob_id)?;
+                        if let Err(err) = execute_query(&mut session, query) {
+                            println!("Failed to execute query: {}", err);
+                        }
                     }
                     _ => {}
                 }
@@ -58,6 +551,16 @@ pub fn main() -> anyhow::Result<()> {
         }
     }
 
+    if let Some(dir) = history_path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Err(err) = rl.save_history(&history_path) {
+        println!(
+            "Failed to save history to {}: {}",
+            history_path.display(),
+            err
+        );
+    }
     Ok(())
 }
 
@@ -66,10 +569,128 @@ fn print_help() {
     println!("  help     - Show this help message");
     println!("  tables   - List all tables in the database");
     println!("  describe <table> - Show table schema");
+    println!("  databases - List the open databases and their head snapshots");
+    println!("  open <name> [blobID] [--password | --key-file <path>] - Switch the main database, checking out blobID if given");
+    println!("  attach <name> [blobID] [--password | --key-file <path>] - Attach another database as <name>");
+    println!("  detach <name> - Detach a database");
+    println!("  save [name] [--vacuum] [-m <message>] - Upload a snapshot of the main (or named) database, compacted with --vacuum");
+    println!("  history [name] - List the snapshots saved from the main (or named) database");
+    println!("  rollback [name] - Reset the main (or named) database to its last snapshot");
+    println!("    (inside a BEGIN transaction, ROLLBACK undoes the transaction instead)");
+    println!("  retention [name] [--keep-last N] [--keep-daily M] [--epochs E] - Show or set which snapshots gc keeps and for how many epochs");
+    println!("  pin <blobID> [name] / unpin <blobID> [name] - Always keep a snapshot");
+    println!(
+        "  gc [name] [--dry-run] - Delete snapshots the retention policy drops and extend the rest"
+    );
+    println!(
+        "  rebase   - Replay changes since the last snapshot on top of a newer save made elsewhere"
+    );
+    println!("  branch [<name> [target]] / branch -d <name> - List, create or delete branches of the main database");
+    println!("  tag [<name> [target]] / tag -d <name> - List, create or delete tags; target is a branch, tag or blob ID");
+    println!("  switch <branch> - Check out a branch; later saves move it forward");
+    println!("  refs [--push | --pull <blobID>] - List branches and tags, or share them through a blob on Walrus");
+    println!(
+        "  encrypt [name] - Encrypt snapshots of the main (or named) database with a password"
+    );
+    println!("  .mode box|csv|json|markdown - Set how query results are printed");
+    println!("  .headers on|off - Show or hide column names in query results");
+    println!("  .output [file] - Write query results to a file, or back to stdout");
+    println!(
+        "  .import <file.csv|file.jsonl> <table> - Load rows into a table, creating it if needed"
+    );
+    println!("  .export <table> <file.csv|file.jsonl> - Write a table to a file");
+    println!(
+        "  .export <table> --walrus [csv|jsonl] - Upload a table as a new blob and print its ID"
+    );
     println!("  SQL queries will be executed");
     println!("  exit/quit - Close the shell");
 }
 
+fn encrypt_workspace(session: &mut Session, name: Option<&str>) -> anyhow::Result<String> {
+    let name = name.unwrap_or(&session.main.name).to_string();
+    let workspace = session
+        .workspace_mut(&name)
+        .ok_or_else(|| anyhow::anyhow!("'{}' is not open", name))?;
+    if workspace.is_encrypted() {
+        return Err(anyhow::anyhow!("'{}' is already encrypted", name));
+    }
+    workspace.set_key(prompt_key(true)?)?;
+    Ok(name)
+}
+
+fn print_history(workspace: &Workspace) {
+    let head = workspace.head();
+    println!("Snapshots of {}:", workspace.name);
+    let pins = workspace.pins();
+    let refs = workspace.refs();
+    for snapshot in workspace.snapshots().iter().rev() {
+        let names = refs.names_of(&snapshot.blob_id);
+        println!(
+            "  {} {}  {}  {}{}{}",
+            if head.as_deref() == Some(snapshot.blob_id.as_str()) {
+                "*"
+            } else {
+                " "
+            },
+            snapshot.blob_id,
+            format_time(snapshot.timestamp),
+            snapshot.message.as_deref().unwrap_or(""),
+            if pins.contains(&snapshot.blob_id) {
+                " [pinned]"
+            } else {
+                ""
+            },
+            if names.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", names.join(", "))
+            }
+        );
+    }
+}
+
+/// Lists the branches of `workspace`, marking the current one, or its tags.
+fn print_refs(workspace: &Workspace, tags: bool) {
+    let refs = workspace.refs();
+    if tags {
+        println!("Tags of {}:", workspace.name);
+        for (name, blob_id) in &refs.tags {
+            println!("    {:<20} {}", name, blob_id);
+        }
+    } else {
+        println!("Branches of {}:", workspace.name);
+        for (name, blob_id) in &refs.branches {
+            let marker = if name == refs.current_branch() {
+                "*"
+            } else {
+                " "
+            };
+            println!("  {} {:<20} {}", marker, name, blob_id);
+        }
+    }
+}
+
+fn format_time(timestamp: u64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|time| {
+            time.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+fn list_databases(session: &Session) {
+    println!("Databases:");
+    for workspace in std::iter::once(&session.main).chain(session.attached.iter()) {
+        println!(
+            "  - {}\t{}",
+            workspace.name,
+            workspace.head().unwrap_or_else(|| "(unsaved)".to_string())
+        );
+    }
+}
+
 fn list_tables(conn: &Connection) -> Result<()> {
     let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type='table';")?;
     let table_names = stmt
@@ -118,67 +739,412 @@ fn describe_table(conn: &Connection, table_name: &str) -> Result<()> {
     Ok(())
 }
 
-fn execute_query(conn: &Connection, query: &str, blob_id_prev: &mut String) -> Result<()> {
-    if query.trim().to_uppercase().starts_with("SELECT") {
-        let mut stmt = conn.prepare(query)?;
-
-        // Get column names
-        let column_names: Vec<String> = stmt
-            .column_names()
-            .into_iter()
-            .map(|s| s.to_string())
-            .collect();
-
-        // Print headers
-        println!("{}", column_names.join("\t"));
-
-        // Print rows
-        let mut rows = stmt.query(params![])?;
-        let mut row_count = 0;
-
-        while let Some(row) = rows.next()? {
-            let mut row_data = Vec::new();
-            for i in 0..row.as_ref().column_count() {
-                let value_ref = row.get_ref(i)?;
-                let value_str = match value_ref {
-                    rusqlite::types::ValueRef::Null => "NULL".to_string(),
-                    rusqlite::types::ValueRef::Integer(i) => i.to_string(),
-                    rusqlite::types::ValueRef::Real(r) => r.to_string(),
-                    rusqlite::types::ValueRef::Text(t) => String::from_utf8_lossy(t).to_string(),
-                    rusqlite::types::ValueRef::Blob(b) => format!("{:?}", b),
-                };
-                row_data.push(value_str);
+/// Runs each statement of `query`, handling the snapshot commands `save` and
+/// `rollback` and routing everything else to SQLite.
+fn execute_query(session: &mut Session, query: &str) -> anyhow::Result<()> {
+    for (_, statement) in batch::split_statements(query) {
+        execute_statement(session, &statement)?;
+    }
+    Ok(())
+}
+
+fn execute_statement(session: &mut Session, statement: &str) -> anyhow::Result<()> {
+    let command = statement.trim().trim_end_matches(';').trim_end();
+    let words: Vec<String> = command
+        .split_whitespace()
+        .map(|word| word.to_uppercase())
+        .collect();
+
+    match words.first().map(|word| word.as_str()) {
+        Some("SAVE") => {
+            let (target, message) = match command.split_once(" -m ") {
+                Some((target, message)) => (target, Some(message.trim().trim_matches('"'))),
+                None => (command, None),
+            };
+            let mut args = command_args(target);
+            let vacuum = take_flag(&mut args, "--vacuum");
+            let blob_id = session.save(args.first().map(|name| name.as_str()), vacuum, message)?;
+            println!("CURRENT Blob ID: {}", blob_id);
+        }
+        // Inside a transaction, and for `ROLLBACK TO` / `ROLLBACK TRANSACTION`,
+        // ROLLBACK is SQLite's own
+        Some("ROLLBACK")
+            if session.conn.is_autocommit()
+                && !matches!(
+                    words.get(1).map(|word| word.as_str()),
+                    Some("TO" | "TRANSACTION")
+                ) =>
+        {
+            let name = command.split_whitespace().nth(1);
+            let previous = session.rollback(name)?;
+            println!(
+                "Blob ID on ROLLBACK: {}",
+                previous.unwrap_or_else(|| "(root snapshot)".to_string())
+            );
+        }
+        Some("GC") => {
+            let mut args = command_args(command);
+            let dry_run = take_flag(&mut args, "--dry-run");
+            let report = session.gc(args.first().map(|s| s.as_str()), dry_run)?;
+            print_gc_report(&report, dry_run);
+        }
+        Some("RETENTION") => {
+            let mut args = command_args(command);
+            let keep_last = take_option(&mut args, "--keep-last")?;
+            let keep_daily = take_option(&mut args, "--keep-daily")?;
+            let epochs = take_option(&mut args, "--epochs")?;
+            let name = args.first().unwrap_or(&session.main.name);
+            let workspace = session
+                .workspace(name)
+                .ok_or_else(|| anyhow::anyhow!("'{}' is not open", name))?;
+
+            let mut policy = workspace.retention()?;
+            // 0 turns a rule off again
+            if let Some(keep_last) = keep_last {
+                policy.keep_last =
+                    Some(parse_number("--keep-last", &keep_last)?).filter(|&n| n > 0);
+            }
+            if let Some(keep_daily) = keep_daily {
+                policy.keep_daily =
+                    Some(parse_number("--keep-daily", &keep_daily)?).filter(|&n| n > 0);
+            }
+            if let Some(epochs) = epochs {
+                policy.epochs = Some(parse_number("--epochs", &epochs)?).filter(|&n| n > 0);
+            }
+            workspace.set_retention(&policy)?;
+            println!("Retention for '{}': {}", workspace.name, policy);
+        }
+        Some(keyword @ ("PIN" | "UNPIN")) => {
+            let args = command_args(command);
+            let blob_id = args.first().ok_or_else(|| {
+                anyhow::anyhow!("Usage: {} <blobID> [name]", keyword.to_lowercase())
+            })?;
+            let name = args.get(1).unwrap_or(&session.main.name);
+            let workspace = session
+                .workspace(name)
+                .ok_or_else(|| anyhow::anyhow!("'{}' is not open", name))?;
+            if keyword == "PIN" {
+                workspace.pin(blob_id)?;
+                println!("Pinned {} in '{}'", blob_id, workspace.name);
+            } else {
+                workspace.unpin(blob_id)?;
+                println!("Unpinned {} in '{}'", blob_id, workspace.name);
             }
-            println!("{}", row_data.join("\t"));
-            row_count += 1;
         }
+        Some(keyword @ ("BRANCH" | "TAG")) => {
+            let mut args = command_args(command);
+            let delete = take_flag(&mut args, "-d");
+            let workspace = &session.main;
+            match (keyword, delete, args.as_slice()) {
+                (_, false, []) => print_refs(workspace, keyword == "TAG"),
+                ("BRANCH", true, [name]) => {
+                    workspace.delete_branch(name)?;
+                    println!("Deleted branch '{}'", name);
+                }
+                ("TAG", true, [name]) => {
+                    workspace.delete_tag(name)?;
+                    println!("Deleted tag '{}'", name);
+                }
+                ("BRANCH", false, [name, target @ ..]) if target.len() <= 1 => {
+                    let target = target.first().map(|target| target.as_str());
+                    let blob_id = workspace.create_branch(name, target)?;
+                    println!("Created branch '{}' at {}", name, blob_id);
+                }
+                ("TAG", false, [name, target @ ..]) if target.len() <= 1 => {
+                    let target = target.first().map(|target| target.as_str());
+                    let blob_id = workspace.tag(name, target)?;
+                    println!("Tagged {} as '{}'", blob_id, name);
+                }
+                _ => {
+                    let keyword = keyword.to_lowercase();
+                    return Err(anyhow::anyhow!(
+                        "Usage: {} [<name> [target]] or {} -d <name>",
+                        keyword,
+                        keyword
+                    ));
+                }
+            }
+        }
+        Some("SWITCH") => {
+            let args = command_args(command);
+            let [branch] = args.as_slice() else {
+                return Err(anyhow::anyhow!("Usage: switch <branch>"));
+            };
+            let tip = session.switch_branch(branch)?;
+            println!("Switched to branch '{}' at {}", branch, tip);
+        }
+        Some("REFS") => {
+            let mut args = command_args(command);
+            if take_flag(&mut args, "--push") {
+                let blob_id = session.main.push_refs()?;
+                println!("Refs blob ID: {}", blob_id);
+            } else if let Some(blob_id) = take_option(&mut args, "--pull")? {
+                for conflict in session.main.pull_refs(&blob_id)? {
+                    println!("Kept {}", conflict);
+                }
+                print_refs(&session.main, false);
+                print_refs(&session.main, true);
+            } else {
+                print_refs(&session.main, false);
+                print_refs(&session.main, true);
+            }
+        }
+        Some("REBASE") => {
+            let (head, replayed) = session.rebase()?;
+            println!(
+                "Replayed {} change(s) on top of {}, save to publish them",
+                replayed, head
+            );
+        }
+        _ => run_sql(session, statement)?,
+    }
+
+    Ok(())
+}
+
+/// Runs a `.` command: `.import` and `.export`, or an output setting.
+fn dot_command(session: &mut Session, line: &str) -> anyhow::Result<()> {
+    let mut args = command_args(line);
+    match line.split_whitespace().next() {
+        Some(".import") => {
+            let [file, table] = args.as_slice() else {
+                return Err(anyhow::anyhow!(
+                    "Usage: .import <file.csv|file.jsonl> <table>"
+                ));
+            };
+            let file = shellexpand::tilde(file).to_string();
+            session.take_writes_main();
+            let (count, sql) = transfer::import(&session.conn, std::path::Path::new(&file), table)?;
+            if session.take_writes_main() {
+                session.main.journal(&sql)?;
+            }
+            println!("Imported {} row(s) into {}", count, table);
+        }
+        Some(".export") => {
+            let walrus = take_flag(&mut args, "--walrus");
+            let (table, target) = match args.as_slice() {
+                [table] if walrus => (table, "csv".to_string()),
+                [table, target] => (table, shellexpand::tilde(target).to_string()),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Usage: .export <table> <file.csv|file.jsonl> or .export <table> --walrus [csv|jsonl]"
+                    ))
+                }
+            };
+            let format = transfer::Format::from_name(&target)?;
+            if walrus {
+                let path = session.main.dir.join("export.tmp");
+                let count = transfer::export(&session.conn, table, &path, format)?;
+                let blob_id = session.main.upload_file(&path);
+                let _ = std::fs::remove_file(&path);
+                println!(
+                    "Exported {} row(s) of {} as blob {}",
+                    count, table, blob_id?
+                );
+            } else {
+                let count =
+                    transfer::export(&session.conn, table, std::path::Path::new(&target), format)?;
+                println!("Exported {} row(s) of {} to {}", count, table, target);
+            }
+        }
+        _ => session.output.command(line)?,
+    }
+    Ok(())
+}
+
+/// Arguments of a shell command, without the command itself.
+fn command_args(command: &str) -> Vec<String> {
+    command
+        .split_whitespace()
+        .skip(1)
+        .map(|arg| arg.to_string())
+        .collect()
+}
+
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let before = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != before
+}
+
+fn print_gc_report(report: &GcReport, dry_run: bool) {
+    if dry_run {
+        println!("Dry run, nothing is deleted or extended:");
+    }
+    let (mut deleted, mut extended, mut kept) = (0, 0, 0);
+    for (snapshot, action) in &report.actions {
+        let action = match action {
+            Action::Keep => {
+                kept += 1;
+                "keep".to_string()
+            }
+            Action::Extend(epochs) => {
+                kept += 1;
+                extended += 1;
+                format!("extend +{}", epochs)
+            }
+            Action::Delete => {
+                deleted += 1;
+                "delete".to_string()
+            }
+            Action::Expired => {
+                deleted += 1;
+                "expired".to_string()
+            }
+        };
+        println!(
+            "  {:<10} {}  {}  {}",
+            action,
+            snapshot.blob_id,
+            format_time(snapshot.timestamp),
+            snapshot.message.as_deref().unwrap_or("")
+        );
+    }
+    if report.current_epoch.is_none() {
+        println!("Could not read the current epoch from walrus, so no storage was extended");
+    }
+    println!(
+        "{} snapshot(s) removed, {} kept, {} extended",
+        deleted, kept, extended
+    );
+}
 
-        println!("\nRows returned: {}", row_count);
-    } else if query.trim().to_uppercase().starts_with("SAVE") {
-        let value = walrus_io::append_id_and_upload(
-            "/tmp/sqlite.db".to_string(),
-            blob_id_prev.clone(),
-            Some(1),
+/// Runs a single SQL statement, printing its rows when it returns any and
+/// the number of changed rows when it writes. Statements that may change the
+/// main database are journaled so `rebase` can replay them.
+fn run_sql(session: &mut Session, statement: &str) -> anyhow::Result<()> {
+    session.take_writes_main();
+    let mut stmt = session.conn.prepare(statement)?;
+    let writes_main = session.take_writes_main();
+    let keyword = statement
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_uppercase();
+    // Transaction control is replayed too, so rolled back changes stay rolled back
+    let journaled = !stmt.readonly() && writes_main
+        || matches!(
+            keyword.trim_end_matches(';'),
+            "BEGIN" | "COMMIT" | "END" | "ROLLBACK" | "SAVEPOINT" | "RELEASE"
         );
-        *blob_id_prev = walrus_io::get_blob_id(value.unwrap()).unwrap();
-        println!("CURRENT Blob ID: {}", blob_id_prev);
-    } else if query.trim().to_uppercase().starts_with("ROLLBACK") {
-        let value =
-            walrus_io::download_and_extract_id(blob_id_prev.clone(), "/tmp/sqlite.db".to_string());
-        // *blob_id_prev = value.unwrap();
-        println!("Blob ID on ROLLBACK: {}", value.unwrap());
+
+    if stmt.column_count() > 0 {
+        let (column_names, values) = output::collect_rows(&mut stmt)?;
+        session.output.print_rows(&column_names, &values)?;
+    } else if stmt.readonly() {
+        stmt.execute(params![])?;
+        println!("Query executed.");
     } else {
-        // For non-SELECT queries (INSERT, UPDATE, DELETE)
-        match conn.execute(query, params![]) {
-            Ok(rows_affected) => println!("Query executed. {} row(s) affected.", rows_affected),
-            Err(err) => println!("Failed to execute query: {}", err),
-        }
+        let rows_affected = stmt.execute(params![])?;
+        println!("Query executed. {} row(s) affected.", rows_affected);
     }
 
+    if journaled {
+        session.main.journal(statement)?;
+    }
     Ok(())
 }
 
+/// Opens a session's connection with an authorizer recording the databases
+/// each statement writes to.
+fn open_connection(path: &Path, written: &Written) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    let written = Arc::clone(written);
+    conn.authorizer(Some(move |context: AuthContext<'_>| {
+        let writes = !matches!(
+            context.action,
+            AuthAction::Read { .. }
+                | AuthAction::Select
+                | AuthAction::Function { .. }
+                | AuthAction::Transaction { .. }
+                | AuthAction::Savepoint { .. }
+                | AuthAction::Recursive
+                | AuthAction::Attach { .. }
+                | AuthAction::Detach { .. }
+        );
+        if let (true, Some(database)) = (writes, context.database_name) {
+            written.lock().unwrap().insert(database.to_string());
+        }
+        Authorization::Allow
+    }));
+    Ok(conn)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    fn test_session(name: &str) -> Session {
+        std::env::set_var("WALTER_DB_ROOT", "/tmp/walter_db_test");
+        let _ = std::fs::remove_dir_all(format!("/tmp/walter_db_test/{}", name));
+        let workspace = Workspace::open(name).unwrap();
+        workspace.init().unwrap();
+        Session::open(workspace).unwrap()
+    }
+
+    #[test]
+    fn test_execute_multiple_statements() {
+        let mut session = test_session("statements_test");
+        execute_query(
+            &mut session,
+            "create table t (x); insert into t values (1), (2); WITH c AS (SELECT count(*) FROM t) SELECT * FROM c; PRAGMA user_version",
+        )
+        .unwrap();
+        let count: i64 = session
+            .conn
+            .query_row("SELECT count(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            session.main.journal_entries(),
+            vec![
+                "create table t (x);".to_string(),
+                "insert into t values (1), (2);".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_attached_writes_not_journaled() {
+        let mut session = test_session("journal_main_test");
+        let _ = std::fs::remove_dir_all("/tmp/walter_db_test/journal_other_test");
+        let other = Workspace::open("journal_other_test").unwrap();
+        other.init().unwrap();
+        session.attach(other).unwrap();
+
+        execute_query(
+            &mut session,
+            "CREATE TABLE journal_other_test.t (x); INSERT INTO journal_other_test.t VALUES (1); CREATE TABLE t (x); INSERT INTO t SELECT x FROM journal_other_test.t;",
+        )
+        .unwrap();
+        assert_eq!(
+            session.main.journal_entries(),
+            vec![
+                "CREATE TABLE t (x);".to_string(),
+                "INSERT INTO t SELECT x FROM journal_other_test.t;".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_save_refused_in_transaction() {
+        let mut session = test_session("transaction_test");
+        execute_query(
+            &mut session,
+            "CREATE TABLE t (x); BEGIN; INSERT INTO t VALUES (1);",
+        )
+        .unwrap();
+
+        let err = execute_query(&mut session, "save;").unwrap_err();
+        assert!(err.to_string().contains("transaction is open"));
+
+        // ROLLBACK inside a transaction is SQLite's, not a snapshot rollback
+        execute_query(&mut session, "rollback;").unwrap();
+        assert!(session.conn.is_autocommit());
+        let count: i64 = session
+            .conn
+            .query_row("SELECT count(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+    }
 }
@@ -0,0 +1,3 @@
+fn main() -> anyhow::Result<()> {
+    walter_db::main()
+}
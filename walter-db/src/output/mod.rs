@@ -0,0 +1,349 @@
+use anyhow::anyhow;
+use rusqlite::types::Value;
+use std::fs::File;
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Box,
+    Csv,
+    Json,
+    Markdown,
+}
+
+/// How query results are rendered and where they are written, as set by
+/// the `.mode`, `.headers` and `.output` commands.
+pub struct Output {
+    pub mode: Mode,
+    pub headers: bool,
+    file: Option<(String, File)>,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output {
+            mode: Mode::Box,
+            headers: true,
+            file: None,
+        }
+    }
+}
+
+impl Output {
+    /// Applies a `.mode`, `.headers` or `.output` command.
+    pub fn command(&mut self, line: &str) -> anyhow::Result<()> {
+        let mut parts = line.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some(".mode"), None) => println!("Current mode: {:?}", self.mode),
+            (Some(".mode"), Some(mode)) => {
+                self.mode = match mode {
+                    "box" | "table" => Mode::Box,
+                    "csv" => Mode::Csv,
+                    "json" | "jsonl" => Mode::Json,
+                    "markdown" | "md" => Mode::Markdown,
+                    _ => {
+                        return Err(anyhow!(
+                            "Unknown mode '{}': use box, csv, json or markdown",
+                            mode
+                        ))
+                    }
+                }
+            }
+            (Some(".headers"), Some("on")) => self.headers = true,
+            (Some(".headers"), Some("off")) => self.headers = false,
+            (Some(".headers"), _) => return Err(anyhow!("Usage: .headers on|off")),
+            (Some(".output"), None | Some("stdout")) => self.file = None,
+            (Some(".output"), Some(path)) => {
+                let path = shellexpand::tilde(path).to_string();
+                let file = File::create(&path)
+                    .map_err(|err| anyhow!("Failed to open {}: {}", path, err))?;
+                self.file = Some((path, file));
+            }
+            _ => return Err(anyhow!("Unknown command '{}'", line)),
+        }
+        Ok(())
+    }
+
+    /// File results are currently redirected to, if any.
+    pub fn target(&self) -> Option<&str> {
+        self.file.as_ref().map(|(path, _)| path.as_str())
+    }
+
+    pub fn print_rows(&mut self, columns: &[String], rows: &[Vec<Value>]) -> anyhow::Result<()> {
+        let mut text = render(self.mode, self.headers, columns, rows);
+        if matches!(self.mode, Mode::Box | Mode::Markdown) {
+            text.push_str(&format!("\nRows returned: {}\n", rows.len()));
+        }
+
+        match &mut self.file {
+            Some((_, file)) => file.write_all(text.as_bytes())?,
+            None => std::io::stdout().write_all(text.as_bytes())?,
+        }
+        Ok(())
+    }
+}
+
+/// Runs `stmt` and returns its column names and every row.
+pub fn collect_rows(
+    stmt: &mut rusqlite::Statement<'_>,
+) -> rusqlite::Result<(Vec<String>, Vec<Vec<Value>>)> {
+    let columns: Vec<String> = stmt
+        .column_names()
+        .into_iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    let mut rows = stmt.query([])?;
+    let mut values = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut row_data = Vec::new();
+        for i in 0..columns.len() {
+            row_data.push(row.get::<_, Value>(i)?);
+        }
+        values.push(row_data);
+    }
+    Ok((columns, values))
+}
+
+pub fn render(mode: Mode, headers: bool, columns: &[String], rows: &[Vec<Value>]) -> String {
+    match mode {
+        Mode::Box => render_box(headers, columns, rows),
+        Mode::Csv => render_csv(headers, columns, rows),
+        Mode::Json => render_json(columns, rows),
+        Mode::Markdown => render_markdown(headers, columns, rows),
+    }
+}
+
+/// Renders a value for the text based modes, with BLOBs as hex literals.
+pub fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(r) => r.to_string(),
+        Value::Text(t) => t.clone(),
+        Value::Blob(b) => format!("x'{}'", to_hex(b)),
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn render_box(headers: bool, columns: &[String], rows: &[Vec<Value>]) -> String {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(value_to_string).collect())
+        .collect();
+
+    let mut widths: Vec<usize> = columns
+        .iter()
+        .map(|column| if headers { column.chars().count() } else { 0 })
+        .collect();
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let rule = |left: &str, middle: &str, right: &str| {
+        let segments: Vec<String> = widths.iter().map(|width| "─".repeat(width + 2)).collect();
+        format!("{}{}{}\n", left, segments.join(middle), right)
+    };
+    let line = |cells: &[String], values: Option<&[Value]>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (cell, width))| {
+                let numeric = matches!(
+                    values.and_then(|values| values.get(i)),
+                    Some(Value::Integer(_) | Value::Real(_))
+                );
+                if numeric {
+                    format!(" {:>width$} ", cell, width = width)
+                } else {
+                    format!(" {:<width$} ", cell, width = width)
+                }
+            })
+            .collect();
+        format!("│{}│\n", padded.join("│"))
+    };
+
+    let mut text = rule("┌", "┬", "┐");
+    if headers {
+        text.push_str(&line(columns, None));
+        text.push_str(&rule("├", "┼", "┤"));
+    }
+    for (row, values) in cells.iter().zip(rows) {
+        text.push_str(&line(row, Some(values)));
+    }
+    text.push_str(&rule("└", "┴", "┘"));
+    text
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn render_csv(headers: bool, columns: &[String], rows: &[Vec<Value>]) -> String {
+    let mut text = String::new();
+    if headers {
+        let fields: Vec<String> = columns.iter().map(|column| csv_field(column)).collect();
+        text.push_str(&format!("{}\n", fields.join(",")));
+    }
+    for row in rows {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|value| match value {
+                Value::Null => String::new(),
+                value => csv_field(&value_to_string(value)),
+            })
+            .collect();
+        text.push_str(&format!("{}\n", fields.join(",")));
+    }
+    text
+}
+
+fn render_json(columns: &[String], rows: &[Vec<Value>]) -> String {
+    let mut text = String::new();
+    for row in rows {
+        text.push_str(&json_row(columns, row));
+        text.push('\n');
+    }
+    text
+}
+
+/// A row as a JSON object, with BLOBs as hex strings.
+pub fn json_row(columns: &[String], row: &[Value]) -> String {
+    // Built by hand so keys keep the column order
+    let fields: Vec<String> = columns
+        .iter()
+        .zip(row)
+        .map(|(column, value)| {
+            let value = match value {
+                Value::Null => serde_json::Value::Null,
+                Value::Integer(i) => serde_json::Value::from(*i),
+                Value::Real(r) => serde_json::Value::from(*r),
+                Value::Text(t) => serde_json::Value::from(t.as_str()),
+                Value::Blob(b) => serde_json::Value::from(to_hex(b)),
+            };
+            format!("{}:{}", serde_json::Value::from(column.as_str()), value)
+        })
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+fn render_markdown(headers: bool, columns: &[String], rows: &[Vec<Value>]) -> String {
+    let line = |cells: Vec<String>| {
+        let cells: Vec<String> = cells
+            .iter()
+            .map(|cell| cell.replace('|', "\\|").replace('\n', "<br>"))
+            .collect();
+        format!("| {} |\n", cells.join(" | "))
+    };
+
+    let mut text = String::new();
+    if headers {
+        text.push_str(&line(columns.to_vec()));
+        text.push_str(&line(columns.iter().map(|_| "---".to_string()).collect()));
+    }
+    for row in rows {
+        text.push_str(&line(row.iter().map(value_to_string).collect()));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> (Vec<String>, Vec<Vec<Value>>) {
+        (
+            vec!["id".to_string(), "name".to_string(), "data".to_string()],
+            vec![
+                vec![
+                    Value::Integer(1),
+                    Value::Text("walrus, \"wal\"".to_string()),
+                    Value::Blob(vec![0xde, 0xad]),
+                ],
+                vec![Value::Integer(12), Value::Null, Value::Real(1.5)],
+            ],
+        )
+    }
+
+    #[test]
+    fn test_render_box() {
+        let (columns, rows) = sample();
+        assert_eq!(
+            render(Mode::Box, true, &columns, &rows),
+            "┌────┬───────────────┬─────────┐\n\
+             │ id │ name          │ data    │\n\
+             ├────┼───────────────┼─────────┤\n\
+             │  1 │ walrus, \"wal\" │ x'dead' │\n\
+             │ 12 │ NULL          │     1.5 │\n\
+             └────┴───────────────┴─────────┘\n"
+        );
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let (columns, rows) = sample();
+        assert_eq!(
+            render(Mode::Csv, true, &columns, &rows),
+            "id,name,data\n1,\"walrus, \"\"wal\"\"\",x'dead'\n12,,1.5\n"
+        );
+        assert_eq!(
+            render(Mode::Csv, false, &columns, &rows),
+            "1,\"walrus, \"\"wal\"\"\",x'dead'\n12,,1.5\n"
+        );
+    }
+
+    #[test]
+    fn test_render_json() {
+        let (columns, rows) = sample();
+        assert_eq!(
+            render(Mode::Json, true, &columns, &rows),
+            "{\"id\":1,\"name\":\"walrus, \\\"wal\\\"\",\"data\":\"dead\"}\n\
+             {\"id\":12,\"name\":null,\"data\":1.5}\n"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let columns = vec!["a|b".to_string()];
+        let rows = vec![vec![Value::Text("x".to_string())]];
+        assert_eq!(
+            render(Mode::Markdown, true, &columns, &rows),
+            "| a\\|b |\n| --- |\n| x |\n"
+        );
+    }
+
+    #[test]
+    fn test_output_commands() {
+        let mut output = Output::default();
+        output.command(".mode csv").unwrap();
+        assert_eq!(output.mode, Mode::Csv);
+        output.command(".headers off").unwrap();
+        assert!(!output.headers);
+        assert!(output.command(".mode xml").is_err());
+        assert!(output.command(".headers maybe").is_err());
+
+        output
+            .command(".output /tmp/walter_db_output_test.csv")
+            .unwrap();
+        assert_eq!(output.target(), Some("/tmp/walter_db_output_test.csv"));
+        output
+            .print_rows(&["x".to_string()], &[vec![Value::Integer(3)]])
+            .unwrap();
+        output.command(".output").unwrap();
+        assert_eq!(output.target(), None);
+        assert_eq!(
+            std::fs::read_to_string("/tmp/walter_db_output_test.csv").unwrap(),
+            "3\n"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Branch a database is on until it switches to another one.
+pub const DEFAULT_BRANCH: &str = "main";
+
+/// Named pointers into the snapshot history of a database. Branches move
+/// forward with every save made on them, tags stay where they were put.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Refs {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current: Option<String>,
+    #[serde(default)]
+    pub branches: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+}
+
+impl Refs {
+    pub fn current_branch(&self) -> &str {
+        self.current.as_deref().unwrap_or(DEFAULT_BRANCH)
+    }
+
+    /// The snapshot `target` names: a branch, then a tag, else a blob ID.
+    pub fn resolve(&self, target: &str) -> String {
+        self.branches
+            .get(target)
+            .or_else(|| self.tags.get(target))
+            .cloned()
+            .unwrap_or_else(|| target.to_string())
+    }
+
+    /// Snapshots some branch or tag points at.
+    pub fn targets(&self) -> Vec<String> {
+        self.branches
+            .values()
+            .chain(self.tags.values())
+            .cloned()
+            .collect()
+    }
+
+    /// Branch and tag names pointing at `blob_id`.
+    pub fn names_of(&self, blob_id: &str) -> Vec<String> {
+        self.branches
+            .iter()
+            .chain(self.tags.iter())
+            .filter(|(_, target)| *target == blob_id)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Takes the branches and tags of `other`, keeping the current branch
+    /// and any local ref `other` does not know about. A local branch only
+    /// moves forward, to a snapshot it is an ancestor of as told by
+    /// `is_ancestor(ancestor, descendant)`, and a local tag never moves.
+    /// Returns the refs kept as they were because `other` disagrees.
+    pub fn merge(
+        &mut self,
+        other: Refs,
+        mut is_ancestor: impl FnMut(&str, &str) -> bool,
+    ) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for (name, target) in other.branches {
+            match self.branches.get(&name) {
+                Some(local) if *local == target => {}
+                Some(local) if !is_ancestor(local, &target) => conflicts.push(format!(
+                    "branch '{}' is at {} here and {} in the pulled refs, which does not build on it",
+                    name, local, target
+                )),
+                _ => {
+                    self.branches.insert(name, target);
+                }
+            }
+        }
+        for (name, target) in other.tags {
+            match self.tags.get(&name) {
+                Some(local) if *local != target => conflicts.push(format!(
+                    "tag '{}' is at {} here and {} in the pulled refs",
+                    name, local, target
+                )),
+                Some(_) => {}
+                None => {
+                    self.tags.insert(name, target);
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+pub fn check_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty()
+        || name.starts_with('-')
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    {
+        return Err(anyhow!(
+            "Invalid ref name '{}': use letters, digits, '-', '_' and '.'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refs() {
+        let mut refs = Refs::default();
+        assert_eq!(refs.current_branch(), "main");
+        refs.branches.insert("main".to_string(), "b2".to_string());
+        refs.branches.insert("exp".to_string(), "b3".to_string());
+        refs.tags.insert("v1".to_string(), "b2".to_string());
+
+        assert_eq!(refs.resolve("exp"), "b3");
+        assert_eq!(refs.resolve("v1"), "b2");
+        assert_eq!(refs.resolve("b1"), "b1");
+        assert_eq!(refs.names_of("b2"), vec!["main", "v1"]);
+
+        // b1 <- b2 <- b4, and b3 on its own
+        let is_ancestor = |ancestor: &str, descendant: &str| {
+            matches!(
+                (ancestor, descendant),
+                ("b1", "b2") | ("b1", "b4") | ("b2", "b4")
+            )
+        };
+        let remote = Refs {
+            current: Some("other".to_string()),
+            branches: BTreeMap::from([
+                ("main".to_string(), "b4".to_string()),
+                ("exp".to_string(), "b4".to_string()),
+                ("new".to_string(), "b1".to_string()),
+            ]),
+            tags: BTreeMap::from([
+                ("v1".to_string(), "b1".to_string()),
+                ("v2".to_string(), "b4".to_string()),
+            ]),
+        };
+        let conflicts = refs.merge(remote, is_ancestor);
+        assert_eq!(refs.current_branch(), "main");
+        assert_eq!(refs.resolve("main"), "b4");
+        assert_eq!(refs.resolve("exp"), "b3");
+        assert_eq!(refs.resolve("new"), "b1");
+        assert_eq!(refs.resolve("v1"), "b2");
+        assert_eq!(refs.resolve("v2"), "b4");
+        assert_eq!(conflicts.len(), 2);
+        assert!(conflicts[0].starts_with("branch 'exp'"));
+        assert!(conflicts[1].starts_with("tag 'v1'"));
+
+        // An older remote branch does not move the local one back
+        let remote = Refs {
+            current: None,
+            branches: BTreeMap::from([("main".to_string(), "b2".to_string())]),
+            tags: BTreeMap::new(),
+        };
+        assert_eq!(refs.merge(remote, is_ancestor).len(), 1);
+        assert_eq!(refs.resolve("main"), "b4");
+
+        assert!(check_name("v1.0").is_ok());
+        assert!(check_name("-d").is_err());
+        assert!(check_name("a/b").is_err());
+    }
+}
@@ -0,0 +1,290 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::workspace::SnapshotInfo;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Which snapshots of a database `gc` keeps, and how many epochs they are
+/// stored for. Without `keep_last` or `keep_daily` every snapshot is kept.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Retention {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_last: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_daily: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epochs: Option<u16>,
+}
+
+impl std::fmt::Display for Retention {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut rules = Vec::new();
+        if let Some(keep_last) = self.keep_last {
+            rules.push(format!("keep the last {} snapshot(s)", keep_last));
+        }
+        if let Some(keep_daily) = self.keep_daily {
+            rules.push(format!("keep one snapshot a day for {} day(s)", keep_daily));
+        }
+        if rules.is_empty() {
+            rules.push("keep every snapshot".to_string());
+        }
+        rules.push(format!(
+            "store snapshots for {} epoch(s)",
+            self.epochs.unwrap_or(1)
+        ));
+        write!(f, "{}", rules.join(", "))
+    }
+}
+
+/// What `gc` did, or would do on a dry run, with every snapshot.
+pub struct GcReport {
+    pub current_epoch: Option<u64>,
+    pub actions: Vec<(SnapshotInfo, Action)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Keep,
+    /// Kept, with its storage extended by this many epochs
+    Extend(u64),
+    Delete,
+    /// Its storage already ran out, so it only needs dropping from the log
+    Expired,
+}
+
+/// Blob IDs of the snapshots `policy` keeps. `protected` snapshots, such as
+/// the head and pinned ones, are always kept.
+pub fn retained(
+    snapshots: &[SnapshotInfo],
+    policy: &Retention,
+    protected: &[String],
+    now: u64,
+) -> HashSet<String> {
+    if policy.keep_last.is_none() && policy.keep_daily.is_none() {
+        return snapshots
+            .iter()
+            .map(|snapshot| snapshot.blob_id.clone())
+            .collect();
+    }
+
+    // Snapshots saved within the same second stay in log order
+    let mut newest_first: Vec<&SnapshotInfo> = snapshots.iter().rev().collect();
+    newest_first.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.timestamp));
+
+    let mut keep: HashSet<String> = protected.iter().cloned().collect();
+    if let Some(keep_last) = policy.keep_last {
+        keep.extend(
+            newest_first
+                .iter()
+                .take(keep_last)
+                .map(|snapshot| snapshot.blob_id.clone()),
+        );
+    }
+    if let Some(keep_daily) = policy.keep_daily {
+        let today = now / DAY;
+        let mut days = HashSet::new();
+        for snapshot in &newest_first {
+            let day = snapshot.timestamp / DAY;
+            if today - day.min(today) < keep_daily && days.insert(day) {
+                keep.insert(snapshot.blob_id.clone());
+            }
+        }
+    }
+    keep
+}
+
+/// Decides what `gc` does with every snapshot. Kept snapshots are extended
+/// to last `policy.epochs` past `current_epoch`, when it is known.
+pub fn plan(
+    snapshots: &[SnapshotInfo],
+    policy: &Retention,
+    protected: &[String],
+    now: u64,
+    current_epoch: Option<u64>,
+) -> Vec<(SnapshotInfo, Action)> {
+    let keep = retained(snapshots, policy, protected, now);
+    let target = current_epoch.map(|epoch| epoch + u64::from(policy.epochs.unwrap_or(1)));
+
+    snapshots
+        .iter()
+        .map(|snapshot| {
+            let expired = matches!(
+                (snapshot.end_epoch, current_epoch),
+                (Some(end), Some(current)) if end <= current
+            );
+            let action = if expired {
+                Action::Expired
+            } else if !keep.contains(&snapshot.blob_id) {
+                Action::Delete
+            } else {
+                match (snapshot.end_epoch, target, &snapshot.object_id) {
+                    (Some(end), Some(target), Some(_)) if end < target => {
+                        Action::Extend(target - end)
+                    }
+                    _ => Action::Keep,
+                }
+            };
+            (snapshot.clone(), action)
+        })
+        .collect()
+}
+
+/// Points each of the `kept` snapshots back at its nearest ancestor in
+/// `kept`, skipping the ones of `all` that were dropped. Ancestors that were
+/// never in the log are left as they are.
+pub fn relink(mut kept: Vec<SnapshotInfo>, all: &[SnapshotInfo]) -> Vec<SnapshotInfo> {
+    let previous: HashMap<&str, Option<&String>> = all
+        .iter()
+        .map(|snapshot| (snapshot.blob_id.as_str(), snapshot.previous.as_ref()))
+        .collect();
+    let kept_ids: HashSet<String> = kept
+        .iter()
+        .map(|snapshot| snapshot.blob_id.clone())
+        .collect();
+
+    for snapshot in &mut kept {
+        let mut ancestor = snapshot.previous.clone();
+        // Bounded by the log, in case it holds a cycle
+        for _ in 0..all.len() {
+            match &ancestor {
+                Some(id) if !kept_ids.contains(id) => match previous.get(id.as_str()) {
+                    Some(dropped_previous) => ancestor = dropped_previous.cloned(),
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+        snapshot.previous = ancestor;
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(blob_id: &str, timestamp: u64, end_epoch: Option<u64>) -> SnapshotInfo {
+        SnapshotInfo {
+            blob_id: blob_id.to_string(),
+            previous: None,
+            timestamp,
+            message: None,
+            object_id: Some(format!("0x{}", blob_id)),
+            end_epoch,
+            integrity: None,
+            page_count: None,
+        }
+    }
+
+    #[test]
+    fn test_retained() {
+        let now = 10 * DAY + 100;
+        let snapshots = vec![
+            snapshot("a", 7 * DAY + 10, None),
+            snapshot("b", 8 * DAY + 10, None),
+            snapshot("c", 9 * DAY + 10, None),
+            snapshot("d", 10 * DAY + 10, None),
+            snapshot("e", 10 * DAY + 50, None),
+            snapshot("f", 10 * DAY + 50, None),
+        ];
+
+        let everything = retained(&snapshots, &Retention::default(), &[], now);
+        assert_eq!(everything.len(), 6);
+
+        let last_two = Retention {
+            keep_last: Some(2),
+            ..Default::default()
+        };
+        let keep = retained(&snapshots, &last_two, &["a".to_string()], now);
+        let mut keep: Vec<String> = keep.into_iter().collect();
+        keep.sort();
+        assert_eq!(keep, vec!["a", "e", "f"]);
+
+        // The newest snapshot of today and of the two days before
+        let daily = Retention {
+            keep_daily: Some(3),
+            ..Default::default()
+        };
+        let mut keep: Vec<String> = retained(&snapshots, &daily, &[], now).into_iter().collect();
+        keep.sort();
+        assert_eq!(keep, vec!["b", "c", "f"]);
+    }
+
+    #[test]
+    fn test_relink() {
+        let mut all = Vec::new();
+        for (blob_id, previous) in [
+            ("a", Some("outside")),
+            ("b", Some("a")),
+            ("c", Some("b")),
+            ("d", Some("c")),
+            ("e", Some("b")),
+        ] {
+            let mut snapshot = snapshot(blob_id, 0, None);
+            snapshot.previous = previous.map(|previous| previous.to_string());
+            all.push(snapshot);
+        }
+
+        let kept: Vec<SnapshotInfo> = all
+            .iter()
+            .filter(|snapshot| ["a", "d", "e"].contains(&snapshot.blob_id.as_str()))
+            .cloned()
+            .collect();
+        let previous: Vec<(String, Option<String>)> = relink(kept, &all)
+            .into_iter()
+            .map(|snapshot| (snapshot.blob_id, snapshot.previous))
+            .collect();
+        assert_eq!(
+            previous,
+            vec![
+                ("a".to_string(), Some("outside".to_string())),
+                ("d".to_string(), Some("a".to_string())),
+                ("e".to_string(), Some("a".to_string())),
+            ]
+        );
+
+        // With every ancestor in the log dropped, it points past the log
+        let kept = vec![all[3].clone()];
+        assert_eq!(relink(kept, &all)[0].previous.as_deref(), Some("outside"));
+    }
+
+    #[test]
+    fn test_plan() {
+        let snapshots = vec![
+            snapshot("old", 1, Some(20)),
+            snapshot("gone", 2, Some(9)),
+            snapshot("head", 3, Some(11)),
+            snapshot("fresh", 4, Some(30)),
+        ];
+        let policy = Retention {
+            keep_last: Some(1),
+            keep_daily: None,
+            epochs: Some(5),
+        };
+
+        let actions: Vec<Action> = plan(&snapshots, &policy, &["head".to_string()], 4, Some(10))
+            .into_iter()
+            .map(|(_, action)| action)
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                Action::Delete,
+                Action::Expired,
+                Action::Extend(4),
+                Action::Keep
+            ]
+        );
+
+        // Without the current epoch nothing is extended or known to be expired
+        let actions: Vec<Action> = plan(&snapshots, &policy, &["head".to_string()], 4, None)
+            .into_iter()
+            .map(|(_, action)| action)
+            .collect();
+        assert_eq!(
+            actions,
+            vec![Action::Delete, Action::Delete, Action::Keep, Action::Keep]
+        );
+    }
+}
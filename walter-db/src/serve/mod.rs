@@ -0,0 +1,519 @@
+use anyhow::anyhow;
+use rusqlite::hooks::{AuthAction, Authorization};
+use rusqlite::{Connection, OpenFlags};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::batch;
+use crate::heads::{self, HeadPointer};
+use crate::output;
+use crate::workspace::Workspace;
+
+const SERVE_DIR: &str = "serve";
+const MAX_BODY: usize = 1024 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest a query may run, since the snapshot is locked while it does
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Most rows a query may return
+const MAX_ROWS: usize = 10_000;
+/// SQLite virtual machine steps between checks of the query deadline
+const PROGRESS_STEPS: i32 = 10_000;
+
+/// A snapshot downloaded for serving, opened read-only.
+struct Snapshot {
+    blob_id: String,
+    path: PathBuf,
+    conn: Connection,
+}
+
+impl Snapshot {
+    fn open(blob_id: &str, path: PathBuf) -> anyhow::Result<Self> {
+        let conn = Connection::open_with_flags(
+            &path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        conn.pragma_update(None, "query_only", true)?;
+        // SQLite counts ATTACH as read-only, but it would let a client read
+        // any database file on the machine
+        conn.authorizer(Some(
+            |context: rusqlite::hooks::AuthContext<'_>| match context.action {
+                AuthAction::Attach { .. } | AuthAction::Detach { .. } => Authorization::Deny,
+                _ => Authorization::Allow,
+            },
+        ));
+        Ok(Snapshot {
+            blob_id: blob_id.to_string(),
+            path,
+            conn,
+        })
+    }
+
+    /// The status and JSON body answering `sql`.
+    fn query(&self, sql: &str) -> (u16, String) {
+        match query_json(&self.conn, sql, QUERY_TIMEOUT, MAX_ROWS) {
+            Ok((columns, rows)) => (
+                200,
+                format!(
+                    "{{\"snapshot\":{},\"columns\":{},\"rows\":[{}]}}",
+                    serde_json::Value::from(self.blob_id.as_str()),
+                    serde_json::Value::from(columns),
+                    rows.join(",")
+                ),
+            ),
+            Err(err) => (400, error_body(&err.to_string())),
+        }
+    }
+}
+
+/// Answers read-only SQL queries over HTTP against one snapshot of a
+/// database. Unless a snapshot is pinned, the head is followed and the
+/// served copy is swapped for the new one whenever it moves.
+pub struct Server {
+    workspace: Workspace,
+    heads: Box<dyn HeadPointer>,
+    pinned: Option<String>,
+    current: Mutex<Snapshot>,
+    /// Lets web pages on other origins read the responses
+    cors: bool,
+}
+
+impl Server {
+    /// Downloads `blob_id`, or the current head of the database when none is
+    /// given. Cross-origin requests are only answered with `cors`, since any
+    /// page open in the browser could otherwise read the database.
+    pub fn open(workspace: Workspace, blob_id: Option<String>, cors: bool) -> anyhow::Result<Self> {
+        let heads = heads::from_env();
+        let head = match &blob_id {
+            Some(blob_id) => blob_id.clone(),
+            None => latest(&workspace, heads.as_ref())
+                .ok_or_else(|| anyhow!("'{}' has no snapshot to serve", workspace.name))?,
+        };
+        let snapshot = fetch(&workspace, &head)?;
+        Ok(Server {
+            workspace,
+            heads,
+            pinned: blob_id,
+            current: Mutex::new(snapshot),
+            cors,
+        })
+    }
+
+    /// Serves requests on `address` until the process is stopped, checking
+    /// for a new head every `interval`.
+    pub fn run(self, address: &str, interval: Duration) -> anyhow::Result<()> {
+        let listener = TcpListener::bind(address)
+            .map_err(|err| anyhow!("Failed to listen on {}: {}", address, err))?;
+        let server = Arc::new(self);
+        println!(
+            "Serving '{}' at snapshot {} on http://{}",
+            server.workspace.name,
+            server.snapshot_id(),
+            listener.local_addr()?
+        );
+
+        if server.pinned.is_none() {
+            let refresher = Arc::clone(&server);
+            std::thread::spawn(move || loop {
+                std::thread::sleep(interval);
+                if let Err(err) = refresher.refresh() {
+                    eprintln!("Failed to refresh: {}", err);
+                }
+            });
+        }
+
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let server = Arc::clone(&server);
+            std::thread::spawn(move || {
+                if let Err(err) = server.handle(stream) {
+                    eprintln!("Request failed: {}", err);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn snapshot_id(&self) -> String {
+        self.current.lock().unwrap().blob_id.clone()
+    }
+
+    /// Swaps in the head snapshot when it has moved. Queries keep being
+    /// answered from the old copy while the new one downloads.
+    fn refresh(&self) -> anyhow::Result<()> {
+        let Some(head) = latest(&self.workspace, self.heads.as_ref()) else {
+            return Ok(());
+        };
+        if head == self.snapshot_id() {
+            return Ok(());
+        }
+
+        let snapshot = fetch(&self.workspace, &head)?;
+        let old = std::mem::replace(&mut *self.current.lock().unwrap(), snapshot);
+        drop(old.conn);
+        let _ = fs::remove_file(&old.path);
+        println!("Now serving snapshot {}", head);
+        Ok(())
+    }
+
+    fn handle(&self, stream: TcpStream) -> anyhow::Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut reader = BufReader::new(&stream);
+        let (status, body) = match read_request(&mut reader)? {
+            Ok(request) => self.respond(&request),
+            Err((status, message)) => (status, error_body(&message)),
+        };
+
+        let cors = if self.cors {
+            "Access-Control-Allow-Origin: *\r\n"
+        } else {
+            ""
+        };
+        let mut stream = &stream;
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+            status,
+            reason(status),
+            body.len(),
+            cors,
+            body
+        )?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    fn respond(&self, request: &Request) -> (u16, String) {
+        let (path, query) = request
+            .target
+            .split_once('?')
+            .unwrap_or((request.target.as_str(), ""));
+
+        match (request.method.as_str(), path) {
+            ("GET", "/" | "/health") => {
+                let status = serde_json::json!({
+                    "name": self.workspace.name,
+                    "snapshot": self.snapshot_id(),
+                    "follow_head": self.pinned.is_none(),
+                });
+                (200, status.to_string())
+            }
+            ("GET", "/query") => match query_param(query, "sql") {
+                Some(sql) => self.query(&sql),
+                None => (400, error_body("Missing the sql parameter")),
+            },
+            ("POST", "/query") => match request_sql(&request.body) {
+                Ok(sql) => self.query(&sql),
+                Err(message) => (400, error_body(&message)),
+            },
+            (_, "/" | "/health" | "/query") => (405, error_body("Method not allowed")),
+            _ => (404, error_body("Not found")),
+        }
+    }
+
+    fn query(&self, sql: &str) -> (u16, String) {
+        self.current.lock().unwrap().query(sql)
+    }
+}
+
+/// The latest snapshot of the current branch: the shared head when one is
+/// recorded, otherwise the last one saved from this machine.
+fn latest(workspace: &Workspace, heads: &dyn HeadPointer) -> Option<String> {
+    heads
+        .get(&workspace.head_key())
+        .ok()
+        .flatten()
+        .or_else(|| workspace.head())
+}
+
+fn fetch(workspace: &Workspace, blob_id: &str) -> anyhow::Result<Snapshot> {
+    let dir = workspace.dir.join(SERVE_DIR);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.db", blob_id));
+    workspace.download(blob_id, &path)?;
+    Snapshot::open(blob_id, path)
+}
+
+/// Runs a single read-only statement, returning its columns and its rows
+/// as JSON objects. It is stopped once it runs longer than `timeout` or
+/// returns more than `max_rows` rows.
+fn query_json(
+    conn: &Connection,
+    sql: &str,
+    timeout: Duration,
+    max_rows: usize,
+) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+    let statements = batch::split_statements(sql);
+    let [(_, statement)] = statements.as_slice() else {
+        return Err(anyhow!("Send exactly one SQL statement"));
+    };
+
+    let deadline = Instant::now() + timeout;
+    conn.progress_handler(PROGRESS_STEPS, Some(move || Instant::now() >= deadline));
+    let result = collect_json_rows(conn, statement, max_rows);
+    conn.progress_handler(PROGRESS_STEPS, None::<fn() -> bool>);
+
+    result.map_err(|err| match err.downcast_ref::<rusqlite::Error>() {
+        Some(rusqlite::Error::SqliteFailure(failure, _))
+            if failure.code == rusqlite::ErrorCode::OperationInterrupted =>
+        {
+            anyhow!(
+                "The query ran longer than {} seconds and was stopped",
+                timeout.as_secs_f64()
+            )
+        }
+        _ => err,
+    })
+}
+
+fn collect_json_rows(
+    conn: &Connection,
+    statement: &str,
+    max_rows: usize,
+) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+    let mut stmt = conn.prepare(statement)?;
+    if !stmt.readonly() {
+        return Err(anyhow!("Only read-only statements are allowed"));
+    }
+    let columns: Vec<String> = stmt
+        .column_names()
+        .into_iter()
+        .map(|name| name.to_string())
+        .collect();
+
+    let mut rows = stmt.query([])?;
+    let mut json_rows = Vec::new();
+    while let Some(row) = rows.next()? {
+        if json_rows.len() == max_rows {
+            return Err(anyhow!(
+                "The query returns more than {} rows, add a LIMIT",
+                max_rows
+            ));
+        }
+        let values = (0..columns.len())
+            .map(|i| row.get(i))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        json_rows.push(output::json_row(&columns, &values));
+    }
+    Ok((columns, json_rows))
+}
+
+struct Request {
+    method: String,
+    target: String,
+    body: String,
+}
+
+/// Reads one HTTP/1.1 request, or the status and message to refuse it with.
+fn read_request(reader: &mut impl BufRead) -> std::io::Result<Result<Request, (u16, String)>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(Err((400, "Malformed request".to_string())));
+    };
+    let (method, target) = (method.to_string(), target.to_string());
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                match value.trim().parse() {
+                    Ok(length) => content_length = length,
+                    Err(_) => return Ok(Err((400, "Invalid Content-Length".to_string()))),
+                }
+            }
+        }
+    }
+    if content_length > MAX_BODY {
+        return Ok(Err((413, "Request body is too large".to_string())));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let Ok(body) = String::from_utf8(body) else {
+        return Ok(Err((400, "Request body is not UTF-8".to_string())));
+    };
+    Ok(Ok(Request {
+        method,
+        target,
+        body,
+    }))
+}
+
+/// The SQL of a POST body: either `{"sql": "..."}` or the statement itself.
+fn request_sql(body: &str) -> Result<String, String> {
+    if !body.trim_start().starts_with('{') {
+        return Ok(body.to_string());
+    }
+    let value: serde_json::Value =
+        serde_json::from_str(body).map_err(|err| format!("Invalid JSON body: {}", err))?;
+    value
+        .get("sql")
+        .and_then(|sql| sql.as_str())
+        .map(|sql| sql.to_string())
+        .ok_or_else(|| "The JSON body needs an \"sql\" string".to_string())
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| percent_decode(value))
+}
+
+/// Decodes a URL query value, where `+` stands for a space.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn error_body(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_request() {
+        let raw = "POST /query HTTP/1.1\r\nHost: localhost\r\ncontent-length: 8\r\n\r\nSELECT 1";
+        let request = read_request(&mut raw.as_bytes()).unwrap().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.target, "/query");
+        assert_eq!(request.body, "SELECT 1");
+
+        let raw = "POST /query HTTP/1.1\r\nContent-Length: 99999999\r\n\r\n";
+        assert_eq!(
+            read_request(&mut raw.as_bytes()).unwrap().err().unwrap().0,
+            413
+        );
+
+        assert_eq!(
+            query_param("limit=5&sql=SELECT+*+FROM+t%20WHERE+x%3D%271%27", "sql").unwrap(),
+            "SELECT * FROM t WHERE x='1'"
+        );
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(request_sql("{\"sql\": \"SELECT 2\"}").unwrap(), "SELECT 2");
+        assert_eq!(request_sql("SELECT 3").unwrap(), "SELECT 3");
+    }
+
+    #[test]
+    fn test_query_json() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (id INTEGER, name TEXT, data BLOB); INSERT INTO t VALUES (1, 'a', x'ff'), (2, NULL, NULL);",
+        )
+        .unwrap();
+
+        let (columns, rows) = query_json(
+            &conn,
+            "SELECT * FROM t ORDER BY id;",
+            QUERY_TIMEOUT,
+            MAX_ROWS,
+        )
+        .unwrap();
+        assert_eq!(columns, vec!["id", "name", "data"]);
+        assert_eq!(
+            rows,
+            vec![
+                r#"{"id":1,"name":"a","data":"ff"}"#,
+                r#"{"id":2,"name":null,"data":null}"#
+            ]
+        );
+
+        assert!(query_json(&conn, "DELETE FROM t", QUERY_TIMEOUT, MAX_ROWS).is_err());
+        assert!(query_json(&conn, "SELECT 1; DELETE FROM t", QUERY_TIMEOUT, MAX_ROWS).is_err());
+        assert_eq!(
+            query_json(&conn, "SELECT count(*) FROM t", QUERY_TIMEOUT, MAX_ROWS)
+                .unwrap()
+                .1
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_query_limits() {
+        let conn = Connection::open_in_memory().unwrap();
+        let endless = "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n) SELECT count(*) FROM n";
+        let started = Instant::now();
+        let err = query_json(&conn, endless, Duration::from_millis(200), MAX_ROWS).unwrap_err();
+        assert!(err.to_string().contains("ran longer"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let many = "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n LIMIT 11) SELECT x FROM n";
+        let err = query_json(&conn, many, QUERY_TIMEOUT, 10).unwrap_err();
+        assert!(err.to_string().contains("more than 10 rows"), "{}", err);
+        assert_eq!(
+            query_json(&conn, many, QUERY_TIMEOUT, 11).unwrap().1.len(),
+            11
+        );
+
+        // The deadline only applies to the query it was set for
+        std::thread::sleep(Duration::from_millis(250));
+        assert!(query_json(&conn, "SELECT 1", Duration::from_millis(200), MAX_ROWS).is_ok());
+    }
+
+    #[test]
+    fn test_attach_refused() {
+        let dir = std::env::temp_dir().join("walter_db_serve_attach_test");
+        fs::create_dir_all(&dir).unwrap();
+        let served = dir.join("served.db");
+        let other = dir.join("other.db");
+        for path in [&served, &other] {
+            let _ = fs::remove_file(path);
+            Connection::open(path)
+                .unwrap()
+                .execute_batch("CREATE TABLE secret (x TEXT); INSERT INTO secret VALUES ('s');")
+                .unwrap();
+        }
+
+        let snapshot = Snapshot::open("blob", served).unwrap();
+        assert_eq!(snapshot.query("SELECT x FROM secret").0, 200);
+        let attach = format!("ATTACH '{}' AS other", other.display());
+        let (status, body) = snapshot.query(&attach);
+        assert_eq!(status, 400, "{}", body);
+        assert_eq!(snapshot.query("DETACH other").0, 400);
+        assert_eq!(snapshot.query("SELECT x FROM other.secret").0, 400);
+    }
+}
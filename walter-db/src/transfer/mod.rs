@@ -0,0 +1,399 @@
+use anyhow::anyhow;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use std::fs;
+use std::path::Path;
+
+use crate::output::{self, Mode};
+
+const ROWS_PER_INSERT: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    JsonLines,
+}
+
+impl Format {
+    /// Picks the format from a file extension or a format name.
+    pub fn from_name(name: &str) -> anyhow::Result<Format> {
+        let extension = Path::new(name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_string())
+            .unwrap_or_else(|| name.to_string());
+        match extension.to_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "json" | "jsonl" | "ndjson" => Ok(Format::JsonLines),
+            _ => Err(anyhow!(
+                "Unknown format for '{}': use .csv or .jsonl files",
+                name
+            )),
+        }
+    }
+}
+
+/// Reads `file` into `table`, creating the table with inferred column types
+/// if it does not exist yet. Returns the number of rows and the SQL that was
+/// run, so it can be journaled like any other change.
+pub fn import(conn: &Connection, file: &Path, table: &str) -> anyhow::Result<(usize, String)> {
+    let text = fs::read_to_string(file)
+        .map_err(|err| anyhow!("Failed to read {}: {}", file.display(), err))?;
+    let (columns, rows) = match Format::from_name(&file.to_string_lossy())? {
+        Format::Csv => parse_csv(&text)?,
+        Format::JsonLines => parse_json_lines(&text)?,
+    };
+    if columns.is_empty() {
+        return Err(anyhow!("{} has no columns", file.display()));
+    }
+
+    let sql = import_sql(conn, table, &columns, &rows)?;
+    if let Err(err) = conn.execute_batch(&sql) {
+        // Undo the rows already inserted and close the savepoint, which
+        // would otherwise leave a transaction open
+        let _ = conn.execute_batch("ROLLBACK TO walter_import; RELEASE walter_import;");
+        return Err(err.into());
+    }
+    Ok((rows.len(), sql))
+}
+
+/// Writes every row of `table` to `file`, returning the number of rows.
+pub fn export(
+    conn: &Connection,
+    table: &str,
+    file: &Path,
+    format: Format,
+) -> anyhow::Result<usize> {
+    let mut stmt = conn.prepare(&format!("SELECT * FROM {}", quote_name(table)))?;
+    let (columns, rows) = output::collect_rows(&mut stmt)?;
+    let mode = match format {
+        Format::Csv => Mode::Csv,
+        Format::JsonLines => Mode::Json,
+    };
+    fs::write(file, output::render(mode, true, &columns, &rows))
+        .map_err(|err| anyhow!("Failed to write {}: {}", file.display(), err))?;
+    Ok(rows.len())
+}
+
+fn import_sql(
+    conn: &Connection,
+    table: &str,
+    columns: &[String],
+    rows: &[Vec<Value>],
+) -> anyhow::Result<String> {
+    let quoted: Vec<String> = columns
+        .iter()
+        .map(|column| quote_identifier(column))
+        .collect();
+    // A savepoint works both on its own and inside an open transaction
+    let mut sql = String::from("SAVEPOINT walter_import;\n");
+
+    if !table_exists(conn, table)? {
+        let definitions: Vec<String> = quoted
+            .iter()
+            .enumerate()
+            .map(|(i, column)| format!("{} {}", column, column_type(rows, i)))
+            .collect();
+        sql.push_str(&format!(
+            "CREATE TABLE {} ({});\n",
+            quote_name(table),
+            definitions.join(", ")
+        ));
+    }
+
+    for chunk in rows.chunks(ROWS_PER_INSERT) {
+        let values: Vec<String> = chunk
+            .iter()
+            .map(|row| {
+                let literals: Vec<String> = row.iter().map(sql_literal).collect();
+                format!("({})", literals.join(", "))
+            })
+            .collect();
+        sql.push_str(&format!(
+            "INSERT INTO {} ({}) VALUES {};\n",
+            quote_name(table),
+            quoted.join(", "),
+            values.join(", ")
+        ));
+    }
+
+    sql.push_str("RELEASE walter_import;");
+    Ok(sql)
+}
+
+fn table_exists(conn: &Connection, table: &str) -> anyhow::Result<bool> {
+    let (schema, name) = match table.split_once('.') {
+        Some((schema, name)) => (schema, name),
+        None => ("main", table),
+    };
+    let count: i64 = conn.query_row(
+        &format!(
+            "SELECT count(*) FROM {}.sqlite_master WHERE type IN ('table', 'view') AND name = ?1",
+            quote_identifier(schema)
+        ),
+        [name],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// The declared type of column `index`, from the values it holds.
+fn column_type(rows: &[Vec<Value>], index: usize) -> &'static str {
+    let mut column_type = None;
+    for value in rows.iter().filter_map(|row| row.get(index)) {
+        let value_type = match value {
+            Value::Null => continue,
+            Value::Integer(_) => "INTEGER",
+            Value::Real(_) => "REAL",
+            Value::Text(_) => "TEXT",
+            Value::Blob(_) => "BLOB",
+        };
+        column_type = match (column_type, value_type) {
+            (None, value_type) => Some(value_type),
+            (Some(a), b) if a == b => Some(a),
+            (Some("INTEGER"), "REAL") | (Some("REAL"), "INTEGER") => Some("REAL"),
+            _ => Some("TEXT"),
+        };
+    }
+    column_type.unwrap_or("TEXT")
+}
+
+/// Types CSV cells: integers, reals and `x'..'` hex BLOBs are recognised,
+/// empty cells are NULL and everything else is text.
+fn infer_value(cell: &str) -> Value {
+    if cell.is_empty() {
+        Value::Null
+    } else if let Ok(i) = cell.parse::<i64>() {
+        Value::Integer(i)
+    } else if let Some(bytes) = parse_hex_literal(cell) {
+        Value::Blob(bytes)
+    } else {
+        match cell.parse::<f64>() {
+            Ok(r) if r.is_finite() => Value::Real(r),
+            _ => Value::Text(cell.to_string()),
+        }
+    }
+}
+
+fn parse_hex_literal(cell: &str) -> Option<Vec<u8>> {
+    let hex = cell
+        .strip_prefix("x'")
+        .or_else(|| cell.strip_prefix("X'"))?
+        .strip_suffix('\'')?;
+    if hex.len() % 2 != 0 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn sql_literal(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(r) => format!("{:?}", r),
+        Value::Text(t) => format!("'{}'", t.replace('\'', "''")),
+        Value::Blob(b) => output::value_to_string(&Value::Blob(b.clone())),
+    }
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Quotes a table name, keeping a `schema.` prefix for attached databases.
+fn quote_name(table: &str) -> String {
+    match table.split_once('.') {
+        Some((schema, name)) => format!("{}.{}", quote_identifier(schema), quote_identifier(name)),
+        None => quote_identifier(table),
+    }
+}
+
+/// Parses CSV with a header row, following RFC 4180 quoting.
+fn parse_csv(text: &str) -> anyhow::Result<(Vec<String>, Vec<Vec<Value>>)> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(anyhow!("Unterminated quoted field in CSV"));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records.retain(|record| !(record.len() == 1 && record[0].is_empty()));
+
+    let mut records = records.into_iter();
+    let columns = records.next().unwrap_or_default();
+    let mut rows = Vec::new();
+    for (line, record) in records.enumerate() {
+        if record.len() != columns.len() {
+            return Err(anyhow!(
+                "CSV record {} has {} field(s), the header has {}",
+                line + 1,
+                record.len(),
+                columns.len()
+            ));
+        }
+        rows.push(record.iter().map(|cell| infer_value(cell)).collect());
+    }
+    Ok((columns, rows))
+}
+
+/// Parses one JSON object per line. Columns are every key seen, nested
+/// objects and arrays are kept as JSON text.
+fn parse_json_lines(text: &str) -> anyhow::Result<(Vec<String>, Vec<Vec<Value>>)> {
+    let mut objects = Vec::new();
+    for (line, text) in text.lines().enumerate() {
+        if text.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(serde_json::Value::Object(object)) => objects.push(object),
+            Ok(_) => return Err(anyhow!("Line {} is not a JSON object", line + 1)),
+            Err(err) => return Err(anyhow!("Line {} is not valid JSON: {}", line + 1, err)),
+        }
+    }
+
+    let mut columns: Vec<String> = Vec::new();
+    for object in &objects {
+        for key in object.keys() {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+
+    let rows = objects
+        .iter()
+        .map(|object| {
+            columns
+                .iter()
+                .map(|column| match object.get(column) {
+                    None | Some(serde_json::Value::Null) => Value::Null,
+                    Some(serde_json::Value::Bool(b)) => Value::Integer(*b as i64),
+                    Some(serde_json::Value::Number(n)) => match n.as_i64() {
+                        Some(i) => Value::Integer(i),
+                        None => Value::Real(n.as_f64().unwrap_or_default()),
+                    },
+                    Some(serde_json::Value::String(s)) => Value::Text(s.clone()),
+                    Some(other) => Value::Text(other.to_string()),
+                })
+                .collect()
+        })
+        .collect();
+    Ok((columns, rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let (columns, rows) =
+            parse_csv("id,name,score,data\r\n1,\"Wal, \"\"rus\"\"\",2.5,x'cafe'\n2,,3,\n").unwrap();
+        assert_eq!(columns, vec!["id", "name", "score", "data"]);
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    Value::Integer(1),
+                    Value::Text("Wal, \"rus\"".to_string()),
+                    Value::Real(2.5),
+                    Value::Blob(vec![0xca, 0xfe]),
+                ],
+                vec![
+                    Value::Integer(2),
+                    Value::Null,
+                    Value::Integer(3),
+                    Value::Null
+                ],
+            ]
+        );
+        assert_eq!(column_type(&rows, 0), "INTEGER");
+        assert_eq!(column_type(&rows, 1), "TEXT");
+        assert_eq!(column_type(&rows, 2), "REAL");
+        assert_eq!(column_type(&rows, 3), "BLOB");
+
+        assert!(parse_csv("a,b\n1\n").is_err());
+        assert!(parse_csv("a\n\"open\n").is_err());
+    }
+
+    #[test]
+    fn test_import_and_export() {
+        let dir = Path::new("/tmp/walter_db_test/transfer_test");
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+
+        let jsonl = dir.join("people.jsonl");
+        fs::write(
+            &jsonl,
+            "{\"name\": \"O'Neil\", \"age\": 30, \"tags\": [\"a\"]}\n\n{\"name\": \"Ann\", \"active\": true}\n",
+        )
+        .unwrap();
+        let (count, sql) = import(&conn, &jsonl, "people").unwrap();
+        assert_eq!(count, 2);
+        assert!(sql.contains("CREATE TABLE \"people\""));
+
+        // Importing again appends to the existing table
+        import(&conn, &jsonl, "people").unwrap();
+        let total: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM people WHERE name = 'O''Neil'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(total, 2);
+
+        let csv = dir.join("people.csv");
+        assert_eq!(export(&conn, "people", &csv, Format::Csv).unwrap(), 4);
+        let conn2 = Connection::open_in_memory().unwrap();
+        assert_eq!(import(&conn2, &csv, "copy").unwrap().0, 4);
+        let age: i64 = conn2
+            .query_row("SELECT sum(age) FROM copy", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(age, 60);
+
+        // A failed import leaves neither rows nor an open transaction
+        conn.execute_batch("CREATE TABLE strict (id INTEGER UNIQUE, name TEXT NOT NULL)")
+            .unwrap();
+        let bad = dir.join("bad.csv");
+        fs::write(&bad, "id,name\n1,a\n2,\n").unwrap();
+        assert!(import(&conn, &bad, "strict").is_err());
+        fs::write(&bad, "id,name\n1,a\n1,b\n").unwrap();
+        assert!(import(&conn, &bad, "strict").is_err());
+        assert!(conn.is_autocommit());
+        let count: i64 = conn
+            .query_row("SELECT count(*) FROM strict", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+
+        assert!(Format::from_name("table.xml").is_err());
+        assert_eq!(Format::from_name("jsonl").unwrap(), Format::JsonLines);
+    }
+}
@@ -1,118 +1,129 @@
+use serde::de::IgnoredAny;
 use std::fs::File;
 use std::io::{Read, Write};
-
-mod types;
-use types::*;
+use walter_core::client::run_walrus_json;
+use walter_core::encryptor::{decrypt_file, encrypt_file};
+use walter_core::types::*;
 
 //Input: fileName: String
 //Process: Reads from the filesystem and uploads using "walrus store <fileNamewithpath>" installed on OS
-//Output: None
-pub fn upload_to_walrus(file_name: String, epochs: Option<u16>) -> Option<serde_json::Value> {
+//Output: the store response
+pub fn upload_to_walrus(file_name: String, epochs: Option<u16>) -> Option<WalrusResponse> {
     // epochs if not specified, then 1
-    let epochs = epochs.unwrap_or(1);
-    let command_json = serde_json::json!({
-        "command": {
-            "store": {
-                "file": file_name,
-                "epochs": epochs,
-                "deletable": true,
-            },
-        }
-    });
-
-    let output = std::process::Command::new("walrus")
-        .arg("json")
-        .arg(command_json.to_string())
-        .output()
-        .expect("failed to execute process");
-
-    // check if output execution is successful gracefully
-    if output.status.success() {
-        println!("Successfully uploaded file to walrus");
-    } else {
-        println!("Failed to upload file to walrus");
-        return None;
-    }
-
-    // serialize stdout in output to JSON
-    let output = String::from_utf8_lossy(&output.stdout);
-    let output: serde_json::Value = match serde_json::from_str(&output) {
-        Ok(val) => val,
-        Err(_) => {
-            println!("Failed to parse output JSON");
-            return None;
-        }
+    let request = StoreRequest {
+        file: file_name,
+        epochs: epochs.unwrap_or(1),
+        deletable: Some(true),
     };
 
-    Some(output)
+    match run_walrus_json(&request) {
+        Ok(response) => {
+            println!("Successfully uploaded file to walrus");
+            Some(response)
+        }
+        Err(e) => {
+            println!("Failed to upload file to walrus: {}", e);
+            None
+        }
+    }
 }
 
 //Input: blobId: String
 //Process: Reads from Walrus and outputs into a file at given path
 //Output: success or failure bool
 pub fn download_from_walrus(blob_id: String, file_location: String) -> Option<bool> {
-    let command_json = serde_json::json!({
-        "command": {
-            "read": {
-                "blobId": blob_id,
-                "out": file_location,
-            },
+    let request = ReadRequest {
+        blobId: blob_id,
+        out: Some(file_location),
+    };
+
+    match run_walrus_json::<_, ReadResponse>(&request) {
+        Ok(_) => {
+            println!("Successfully downloaded file from walrus");
+            Some(true)
+        }
+        Err(e) => {
+            println!("Failed to download file from walrus: {}", e);
+            None
         }
-    });
-
-    let output = std::process::Command::new("walrus")
-        .arg("json")
-        .arg(command_json.to_string())
-        .output()
-        .expect("failed to execute process");
-
-    if output.status.success() {
-        println!("Successfully downloaded file from walrus");
-    } else {
-        println!("Failed to download file from walrus");
-        return None;
     }
+}
 
-    let output = String::from_utf8_lossy(&output.stdout);
-    let output: serde_json::Value = match serde_json::from_str(&output) {
-        Ok(val) => val,
-        Err(_) => {
-            println!("Failed to parse output JSON");
-            return None;
-        }
-    };
+pub fn get_blob_id(response: WalrusResponse) -> String {
+    response.blob_id().to_string()
+}
 
-    Some(output["success"].as_bool().unwrap_or(true))
+// object id and end epoch of a newly created blob, needed to extend or delete it later
+pub fn get_object_info(response: &WalrusResponse) -> Option<(String, u64)> {
+    match response {
+        WalrusResponse::NewlyCreated(created) => Some((
+            created.blobObject.id.clone(),
+            u64::from(created.blobObject.storage.endEpoch),
+        )),
+        WalrusResponse::AlreadyCertified(_) => None,
+    }
 }
 
-fn get_blob_id_from_response(response: NewlyCreated) -> Option<String> {
-    Some(response.blobObject.blobId)
+//Input: blobId: String
+//Process: Deletes the deletable blob objects holding blobId with "walrus delete"
+//Output: success or failure bool
+pub fn delete_from_walrus(blob_id: String) -> bool {
+    let request = DeleteRequest {
+        blobId: blob_id,
+        yes: true,
+    };
+
+    match run_walrus_json::<_, Vec<DeleteOutput>>(&request) {
+        Ok(_) => true,
+        Err(e) => {
+            println!("{}", e);
+            false
+        }
+    }
 }
 
-fn get_blob_id_from_already_certified(response: serde_json::Value) -> Option<String> {
-    response.get("blobId").and_then(|blob_id| blob_id.as_str().map(|s| s.to_string()))
+//Input: objectId: String, epochs: u64
+//Process: Extends the storage of the blob object by the given number of epochs with "walrus extend"
+//Output: success or failure bool
+pub fn extend_on_walrus(object_id: String, epochs: u64) -> bool {
+    let request = ExtendRequest {
+        blobObjId: object_id,
+        epochsAhead: epochs,
+    };
+
+    match run_walrus_json::<_, IgnoredAny>(&request) {
+        Ok(_) => true,
+        Err(e) => {
+            println!("{}", e);
+            false
+        }
+    }
 }
 
-// decide if response is from BlobResponse or AlreadyCertified
-pub fn get_blob_id(response: serde_json::Value) -> Option<String> {
-    if response.get("alreadyCertified").is_some() {
-        get_blob_id_from_already_certified(response.get("alreadyCertified").unwrap().clone())
-    } else {
-        match serde_json::from_value(response.get("newlyCreated").unwrap().clone()) {
-            Ok(newly_created) => get_blob_id_from_response(newly_created),
-            Err(e) => {
-                println!("Failed to deserialize newlyCreated: {}", e.to_string());
-                None
-            }
+//Input: None
+//Process: Reads the current epoch from "walrus info"
+//Output: current epoch
+pub fn current_epoch() -> Option<u64> {
+    match run_walrus_json::<_, InfoResponse>(&InfoRequest {}) {
+        Ok(info) => Some(info.epochInfo.currentEpoch),
+        Err(e) => {
+            println!("{}", e);
+            None
         }
     }
 }
 
-pub fn append_id_and_upload(file_location: String, blob_id: String, epochs: Option<u16>) -> Option<serde_json::Value> {
+pub fn append_id_and_upload(
+    file_location: String,
+    blob_id: String,
+    epochs: Option<u16>,
+    key: Option<&str>,
+) -> Option<WalrusResponse> {
     // Read the file content
     let mut file = File::open(&file_location).expect("Unable to open file");
     let mut contents = Vec::new();
-    file.read_to_end(&mut contents).expect("Unable to read file");
+    file.read_to_end(&mut contents)
+        .expect("Unable to read file");
 
     // Append the blob_id to the file content
     contents.extend_from_slice(blob_id.as_bytes());
@@ -121,30 +132,73 @@ pub fn append_id_and_upload(file_location: String, blob_id: String, epochs: Opti
     let mut file = File::create(&file_location).expect("Unable to create file");
     file.write_all(&contents).expect("Unable to write to file");
 
+    // Encrypt the file, blob_id included, when a key is given
+    if let Some(key) = key {
+        if let Err(e) = encrypt_file(&file_location, &file_location, key) {
+            println!("Failed to encrypt file: {}", e);
+            return None;
+        }
+    }
+
     // Upload the modified file to walrus
     upload_to_walrus(file_location, epochs)
 }
 
-pub fn download_and_extract_id(blob_id: String, file_location: String) -> Option<String> {
+pub fn download_and_extract_id(
+    blob_id: String,
+    file_location: String,
+    key: Option<&str>,
+) -> Option<String> {
     // Download the file from walrus
-    let success = download_from_walrus(blob_id.clone(), file_location.clone());
+    download_from_walrus(blob_id.clone(), file_location.clone())?;
+
+    // Anything that is not a SQLite file was encrypted on upload
+    if !is_sqlite_file(&file_location) {
+        let Some(key) = key else {
+            println!(
+                "Blob {} is encrypted, a key is required to read it",
+                blob_id
+            );
+            return None;
+        };
+        if let Err(e) = decrypt_file(&file_location, &file_location, key) {
+            println!("Failed to decrypt blob {}: {}", blob_id, e);
+            return None;
+        }
+        if !is_sqlite_file(&file_location) {
+            println!("Failed to decrypt blob {}: wrong key", blob_id);
+            return None;
+        }
+    }
 
     // Read the file content
     let mut file = File::open(&file_location).expect("Unable to open file");
     let mut contents = Vec::new();
-    file.read_to_end(&mut contents).expect("Unable to read file");
-
-    // Extract the blob_id from the file content
-    if contents.len() >= blob_id.len() {
-        let extracted_blob_id = String::from_utf8_lossy(&contents[contents.len() - blob_id.len()..]).to_string();
-        println!("Extracted blob_id: {}", extracted_blob_id);
-        Some(extracted_blob_id)
-    } else {
-        println!("Failed to extract blob_id: content length is less than blob_id length");
-        None
-    }
+    file.read_to_end(&mut contents)
+        .expect("Unable to read file");
+
+    // SQLite files are a whole number of pages of at least 512 bytes, so
+    // anything past the last 512 byte boundary is the appended blob_id
+    let trailer_len = contents.len() % 512;
+    let extracted_blob_id =
+        String::from_utf8_lossy(&contents[contents.len() - trailer_len..]).to_string();
+
+    // Strip the blob_id so the file is a plain database again
+    contents.truncate(contents.len() - trailer_len);
+    let mut file = File::create(&file_location).expect("Unable to create file");
+    file.write_all(&contents).expect("Unable to write to file");
+
+    println!("Extracted blob_id: {}", extracted_blob_id);
+    Some(extracted_blob_id)
 }
 
+fn is_sqlite_file(file_location: &str) -> bool {
+    let mut header = [0u8; 16];
+    match File::open(file_location) {
+        Ok(mut file) => file.read_exact(&mut header).is_ok() && &header == b"SQLite format 3\0",
+        Err(_) => false,
+    }
+}
 
 // WRITE TESTS FOR THE LAST TWO FUNCTIONS
 #[cfg(test)]
@@ -157,9 +211,11 @@ mod tests {
         let file_location = "/tmp/test_file".to_string();
         let content = "test_content".to_string();
         let mut file = File::create(&file_location).expect("Unable to create file");
-        file.write_all(content.as_bytes()).expect("Unable to write to file");
+        file.write_all(content.as_bytes())
+            .expect("Unable to write to file");
 
-        let extracted_blob_id = download_and_extract_id(blob_id.clone(), file_location.clone());
+        let extracted_blob_id =
+            download_and_extract_id(blob_id.clone(), file_location.clone(), None);
         assert_eq!(extracted_blob_id, Some(blob_id));
     }
 
@@ -169,10 +225,11 @@ mod tests {
         let blob_id = "Saih8gqlyGPC4LZhP5Co3KmJsJ1DWWTyyd-pdY9jYx0".to_string();
         let content = "test_content".to_string();
         let mut file = File::create(&file_location).expect("Unable to create file");
-        file.write_all(content.as_bytes()).expect("Unable to write to file");
+        file.write_all(content.as_bytes())
+            .expect("Unable to write to file");
 
-        let uploaded = append_id_and_upload(file_location.clone(), blob_id.clone(), None);
+        let uploaded = append_id_and_upload(file_location.clone(), blob_id.clone(), None, None);
         println!("{:?}", uploaded);
         assert!(uploaded.is_some());
     }
-}
\ No newline at end of file
+}
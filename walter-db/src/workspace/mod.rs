@@ -0,0 +1,1069 @@
+use anyhow::anyhow;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::heads::{Conflict, HeadPointer};
+use crate::refs::{self, Refs, DEFAULT_BRANCH};
+use crate::retention::{self, Action, GcReport, Retention};
+use crate::walrus_io;
+use walter_core::encryptor::{decrypt_file, encrypt_file};
+
+const DB_ROOT: &str = "~/.walter/db";
+const DB_ROOT_ENV: &str = "WALTER_DB_ROOT";
+const WORKING_FILE: &str = "working.db";
+const HEAD_FILE: &str = "HEAD";
+const SNAPSHOTS_FILE: &str = "SNAPSHOTS";
+const JOURNAL_FILE: &str = "JOURNAL";
+const RETENTION_FILE: &str = "RETENTION";
+const PINS_FILE: &str = "PINS";
+const REFS_FILE: &str = "REFS";
+const KEY_CHECK_FILE: &str = "KEYCHECK";
+const KEY_CHECK_TEXT: &str = "walter-db";
+
+/// A snapshot saved from a workspace, as recorded in its `SNAPSHOTS` log.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SnapshotInfo {
+    pub blob_id: String,
+    pub previous: Option<String>,
+    pub timestamp: u64,
+    pub message: Option<String>,
+    /// Sui object holding the blob, needed to extend its storage
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_id: Option<String>,
+    /// Epoch the blob is stored until
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_epoch: Option<u64>,
+    /// Result of `PRAGMA integrity_check` on the uploaded database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_count: Option<u64>,
+}
+
+/// A named Walrus-backed database with its working copy kept in `~/.walter/db/<name>/`.
+pub struct Workspace {
+    pub name: String,
+    pub dir: PathBuf,
+    key: Option<String>,
+}
+
+impl Workspace {
+    /// Directory holding every workspace, overridable through `WALTER_DB_ROOT`.
+    pub fn root() -> PathBuf {
+        let root = std::env::var(DB_ROOT_ENV).unwrap_or_else(|_| DB_ROOT.to_string());
+        PathBuf::from(shellexpand::tilde(&root).to_string())
+    }
+
+    pub fn open(name: &str) -> anyhow::Result<Workspace> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(anyhow!(
+                "Invalid database name '{}': use letters, digits, '-' and '_'",
+                name
+            ));
+        }
+
+        let dir = Self::root().join(name);
+        fs::create_dir_all(&dir)?;
+        Ok(Workspace {
+            name: name.to_string(),
+            dir,
+            key: None,
+        })
+    }
+
+    pub fn list() -> Vec<String> {
+        let mut names: Vec<String> = match fs::read_dir(Self::root()) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().join(WORKING_FILE).exists())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        names.sort();
+        names
+    }
+
+    pub fn db_path(&self) -> PathBuf {
+        self.dir.join(WORKING_FILE)
+    }
+
+    pub fn has_working_copy(&self) -> bool {
+        self.db_path().exists()
+    }
+
+    /// Creates an empty database as the working copy.
+    pub fn init(&self) -> anyhow::Result<()> {
+        self.ensure_new()?;
+        // SQLite only writes the file header once something is stored
+        let conn = Connection::open(self.db_path())?;
+        conn.pragma_update(None, "user_version", 0)?;
+        Ok(())
+    }
+
+    /// Copies the SQLite database at `file` in as the working copy.
+    pub fn import(&self, file: &Path) -> anyhow::Result<()> {
+        self.ensure_new()?;
+        if !file.is_file() {
+            return Err(anyhow!("{} does not exist", file.display()));
+        }
+        // VACUUM INTO folds in any WAL content and rejects files that are not databases
+        let imported =
+            Connection::open_with_flags(file, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY).and_then(
+                |conn| {
+                    conn.execute(
+                        "VACUUM INTO ?1",
+                        rusqlite::params![self.db_path().to_string_lossy()],
+                    )
+                },
+            );
+        if let Err(err) = imported {
+            let _ = fs::remove_file(self.db_path());
+            return Err(anyhow!("Failed to import {}: {}", file.display(), err));
+        }
+        Ok(())
+    }
+
+    fn ensure_new(&self) -> anyhow::Result<()> {
+        if self.has_working_copy() {
+            return Err(anyhow!(
+                "'{}' already exists in {}",
+                self.name,
+                self.dir.display()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether snapshots of this database are encrypted before upload.
+    pub fn is_encrypted(&self) -> bool {
+        self.dir.join(KEY_CHECK_FILE).exists()
+    }
+
+    /// Sets the key snapshots are encrypted with. An encrypted database only
+    /// accepts the key encryption was turned on with, an unencrypted one
+    /// becomes encrypted from its next checkout or save.
+    pub fn set_key(&mut self, key: String) -> anyhow::Result<()> {
+        if self.is_encrypted() {
+            let check_path = self.dir.join(KEY_CHECK_FILE);
+            let temp_path = self.dir.join("keycheck.tmp");
+            let decrypted = decrypt_file(
+                &check_path.to_string_lossy(),
+                &temp_path.to_string_lossy(),
+                &key,
+            )
+            .ok()
+            .and_then(|_| fs::read_to_string(&temp_path).ok());
+            let _ = fs::remove_file(&temp_path);
+
+            if decrypted.as_deref() != Some(KEY_CHECK_TEXT) {
+                return Err(anyhow!("Wrong key for '{}'", self.name));
+            }
+        }
+        self.key = Some(key);
+        Ok(())
+    }
+
+    /// Records that this database is encrypted, keeping a sample encrypted
+    /// with the key so later keys can be checked without downloading anything.
+    fn persist_key(&self) -> anyhow::Result<()> {
+        let Some(key) = self.key.as_deref().filter(|_| !self.is_encrypted()) else {
+            return Ok(());
+        };
+        let temp_path = self.dir.join("keycheck.tmp");
+        fs::write(&temp_path, KEY_CHECK_TEXT)?;
+        let encrypted = encrypt_file(
+            &temp_path.to_string_lossy(),
+            &self.dir.join(KEY_CHECK_FILE).to_string_lossy(),
+            key,
+        );
+        let _ = fs::remove_file(&temp_path);
+
+        encrypted.map_err(|e| anyhow!("Failed to enable encryption: {}", e))
+    }
+
+    /// Last snapshot ID this working copy was checked out from or saved to.
+    pub fn head(&self) -> Option<String> {
+        fs::read_to_string(self.dir.join(HEAD_FILE))
+            .ok()
+            .map(|head| head.trim().to_string())
+            .filter(|head| !head.is_empty())
+    }
+
+    pub fn set_head(&self, blob_id: &str) -> std::io::Result<()> {
+        fs::write(self.dir.join(HEAD_FILE), blob_id)
+    }
+
+    /// Replaces the working copy with the snapshot `blob_id` and returns the
+    /// ID of the snapshot it was saved on top of, if any.
+    ///
+    /// Encrypted snapshots need the key to be set first.
+    /// The working file must not be open while this runs.
+    pub fn checkout(&self, blob_id: &str) -> anyhow::Result<Option<String>> {
+        let temp_path = self.dir.join("checkout.tmp");
+        let previous = self.download(blob_id, &temp_path)?;
+
+        fs::rename(&temp_path, self.db_path())?;
+        self.set_head(blob_id)?;
+        self.clear_journal()?;
+        self.persist_key()?;
+        Ok(previous)
+    }
+
+    /// Downloads the snapshot `blob_id` as a plain database at `path`,
+    /// returning the ID of the snapshot it was saved on top of, if any.
+    pub fn download(&self, blob_id: &str, path: &Path) -> anyhow::Result<Option<String>> {
+        let Some(previous) = walrus_io::download_and_extract_id(
+            blob_id.to_string(),
+            path.to_string_lossy().to_string(),
+            self.key.as_deref(),
+        ) else {
+            let _ = fs::remove_file(path);
+            return Err(anyhow!("Failed to download snapshot {}", blob_id));
+        };
+        Ok(Some(previous).filter(|previous| !previous.is_empty()))
+    }
+
+    /// Uploads the working copy as a new snapshot on top of the current head
+    /// and moves the head to it, failing with a [`Conflict`] when `heads`
+    /// shows the database was saved elsewhere in the meantime.
+    pub fn save(
+        &self,
+        heads: &dyn HeadPointer,
+        epochs: Option<u16>,
+        vacuum: bool,
+        message: Option<&str>,
+    ) -> anyhow::Result<String> {
+        if self.is_encrypted() && self.key.is_none() {
+            return Err(anyhow!("'{}' is encrypted, a key is required", self.name));
+        }
+        let base = self.head();
+        let head_key = self.head_key();
+        if let Some(head) = heads.get(&head_key)? {
+            if base.as_ref() != Some(&head) {
+                return Err(self.conflict(base, head));
+            }
+        }
+        self.persist_key()?;
+        let temp_path = self.dir.join("upload.tmp");
+        let page_count = self.copy_for_upload(&temp_path, vacuum)?;
+
+        let response = walrus_io::append_id_and_upload(
+            temp_path.to_string_lossy().to_string(),
+            self.head().unwrap_or_default(),
+            epochs.or(self.retention()?.epochs),
+            self.key.as_deref(),
+        );
+        let _ = fs::remove_file(&temp_path);
+
+        let (blob_id, object_info) = response
+            .map(|response| {
+                let object_info = walrus_io::get_object_info(&response);
+                (walrus_io::get_blob_id(response), object_info)
+            })
+            .ok_or_else(|| anyhow!("Failed to upload snapshot of '{}'", self.name))?;
+        let (object_id, end_epoch) = object_info.unzip();
+        // Someone may have saved while the upload was running
+        if let Err(head) = heads.compare_and_swap(&head_key, base.as_deref(), &blob_id)? {
+            return Err(self.conflict(base, head));
+        }
+        self.record_snapshot(&SnapshotInfo {
+            blob_id: blob_id.clone(),
+            previous: base,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs(),
+            message: message.map(|message| message.to_string()),
+            object_id,
+            end_epoch,
+            integrity: Some("ok".to_string()),
+            page_count: Some(page_count),
+        })?;
+        self.set_head(&blob_id)?;
+        let mut refs = self.refs();
+        refs.branches
+            .insert(refs.current_branch().to_string(), blob_id.clone());
+        self.write_refs(&refs)?;
+        self.clear_journal()?;
+        Ok(blob_id)
+    }
+
+    /// Writes a consistent copy of the working copy to `path` and returns its
+    /// page count. The database must pass `PRAGMA integrity_check`, any WAL is
+    /// checkpointed first, and with `vacuum` the copy is compacted with
+    /// `VACUUM INTO`. Changes still uncommitted elsewhere are left out.
+    fn copy_for_upload(&self, path: &Path, vacuum: bool) -> anyhow::Result<u64> {
+        let _ = fs::remove_file(path);
+        let conn = Connection::open(self.db_path())?;
+        let problems = conn
+            .prepare("PRAGMA integrity_check")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        if problems != ["ok"] {
+            return Err(anyhow!(
+                "'{}' failed its integrity check and was not saved:\n{}",
+                self.name,
+                problems.join("\n")
+            ));
+        }
+        if vacuum {
+            conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+        } else {
+            // VACUUM INTO reads through the WAL, a plain copy needs it emptied first
+            checkpoint(&conn, &self.name)?;
+            // The read transaction keeps writers from changing the file mid-copy
+            conn.execute_batch("BEGIN; SELECT count(*) FROM sqlite_master;")?;
+            let copied = fs::copy(self.db_path(), path);
+            conn.execute_batch("COMMIT")?;
+            copied?;
+        }
+        let page_size: u64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+        Ok(fs::metadata(path)?.len() / page_size)
+    }
+
+    /// Uploads `file` as a standalone blob, encrypted if this database is,
+    /// and returns its blob ID.
+    pub fn upload_file(&self, file: &Path) -> anyhow::Result<String> {
+        if self.is_encrypted() && self.key.is_none() {
+            return Err(anyhow!("'{}' is encrypted, a key is required", self.name));
+        }
+        let file = file.to_string_lossy().to_string();
+        if let Some(key) = self.key.as_deref() {
+            encrypt_file(&file, &file, key)
+                .map_err(|e| anyhow!("Failed to encrypt {}: {}", file, e))?;
+        }
+        walrus_io::upload_to_walrus(file.clone(), self.retention()?.epochs)
+            .map(walrus_io::get_blob_id)
+            .ok_or_else(|| anyhow!("Failed to upload {}", file))
+    }
+
+    fn conflict(&self, base: Option<String>, head: String) -> anyhow::Error {
+        Conflict {
+            name: self.head_key(),
+            base,
+            head,
+        }
+        .into()
+    }
+
+    /// Checks out `onto` and replays the statements journaled since the last
+    /// snapshot on top of it, returning how many were replayed. If any of
+    /// them fails, the working copy is put back as it was.
+    ///
+    /// The working file must not be open while this runs.
+    pub fn rebase(&self, onto: &str) -> anyhow::Result<usize> {
+        let statements = self.journal_entries();
+        let base = self.head();
+        let backup_path = self.dir.join("rebase.bak");
+        fs::copy(self.db_path(), &backup_path)?;
+
+        let restore = |err: anyhow::Error| -> anyhow::Error {
+            let restored = fs::rename(&backup_path, self.db_path())
+                .and_then(|_| self.set_head(base.as_deref().unwrap_or_default()))
+                .map_err(anyhow::Error::from)
+                .and_then(|_| self.write_journal(&statements));
+            match restored {
+                Ok(()) => err,
+                Err(restore_err) => anyhow!(
+                    "{} (restoring the working copy also failed: {}; it is kept in {})",
+                    err,
+                    restore_err,
+                    backup_path.display()
+                ),
+            }
+        };
+
+        if let Err(err) = self.checkout(onto) {
+            return Err(restore(err));
+        }
+        let conn = Connection::open(self.db_path())?;
+        for (index, statement) in statements.iter().enumerate() {
+            if let Err(err) = conn.execute_batch(statement) {
+                drop(conn);
+                return Err(restore(anyhow!(
+                    "Change {} does not apply on top of {}: {}\n  {}",
+                    index + 1,
+                    onto,
+                    err,
+                    statement
+                )));
+            }
+        }
+        drop(conn);
+
+        self.write_journal(&statements)?;
+        fs::remove_file(&backup_path)?;
+        Ok(statements.len())
+    }
+
+    /// Records a statement that changed the working copy since its head
+    /// snapshot, to be replayed by [`Workspace::rebase`].
+    pub fn journal(&self, statement: &str) -> anyhow::Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(JOURNAL_FILE))?;
+        writeln!(file, "{}", serde_json::to_string(statement)?)?;
+        Ok(())
+    }
+
+    /// Statements run since the head snapshot, oldest first.
+    pub fn journal_entries(&self) -> Vec<String> {
+        fs::read_to_string(self.dir.join(JOURNAL_FILE))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    fn write_journal(&self, statements: &[String]) -> anyhow::Result<()> {
+        self.clear_journal()?;
+        for statement in statements {
+            self.journal(statement)?;
+        }
+        Ok(())
+    }
+
+    fn clear_journal(&self) -> std::io::Result<()> {
+        match fs::remove_file(self.dir.join(JOURNAL_FILE)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Snapshots saved from this workspace, oldest first.
+    pub fn snapshots(&self) -> Vec<SnapshotInfo> {
+        fs::read_to_string(self.dir.join(SNAPSHOTS_FILE))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    fn write_snapshots(&self, snapshots: &[SnapshotInfo]) -> anyhow::Result<()> {
+        let mut log = String::new();
+        for snapshot in snapshots {
+            log.push_str(&serde_json::to_string(snapshot)?);
+            log.push('\n');
+        }
+        let temp_path = self.dir.join("snapshots.tmp");
+        fs::write(&temp_path, log)?;
+        fs::rename(&temp_path, self.dir.join(SNAPSHOTS_FILE))?;
+        Ok(())
+    }
+
+    /// The retention policy, the default one if none was set.
+    pub fn retention(&self) -> anyhow::Result<Retention> {
+        let path = self.dir.join(RETENTION_FILE);
+        match fs::read_to_string(&path) {
+            Ok(policy) => serde_json::from_str(&policy)
+                .map_err(|e| anyhow!("{} is not a valid retention policy: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Retention::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn set_retention(&self, policy: &Retention) -> anyhow::Result<()> {
+        fs::write(
+            self.dir.join(RETENTION_FILE),
+            serde_json::to_string_pretty(policy)?,
+        )?;
+        Ok(())
+    }
+
+    /// Snapshots `gc` always keeps.
+    pub fn pins(&self) -> Vec<String> {
+        fs::read_to_string(self.dir.join(PINS_FILE))
+            .unwrap_or_default()
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+
+    pub fn pin(&self, blob_id: &str) -> anyhow::Result<()> {
+        let mut pins = self.pins();
+        if !pins.iter().any(|pin| pin == blob_id) {
+            pins.push(blob_id.to_string());
+        }
+        fs::write(self.dir.join(PINS_FILE), pins.join("\n"))?;
+        Ok(())
+    }
+
+    pub fn unpin(&self, blob_id: &str) -> anyhow::Result<()> {
+        let pins: Vec<String> = self
+            .pins()
+            .into_iter()
+            .filter(|pin| pin != blob_id)
+            .collect();
+        fs::write(self.dir.join(PINS_FILE), pins.join("\n"))?;
+        Ok(())
+    }
+
+    /// Branches and tags of this database. The current branch points at the
+    /// head when it has not been saved on yet.
+    pub fn refs(&self) -> Refs {
+        let mut refs: Refs = fs::read_to_string(self.dir.join(REFS_FILE))
+            .ok()
+            .and_then(|refs| serde_json::from_str(&refs).ok())
+            .unwrap_or_default();
+        if let Some(head) = self.head() {
+            refs.branches
+                .entry(refs.current_branch().to_string())
+                .or_insert(head);
+        }
+        refs
+    }
+
+    fn write_refs(&self, refs: &Refs) -> anyhow::Result<()> {
+        let temp_path = self.dir.join("refs.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(refs)?)?;
+        fs::rename(&temp_path, self.dir.join(REFS_FILE))?;
+        Ok(())
+    }
+
+    /// Key of the current branch in the shared head pointers: the database
+    /// name for the default branch, `<name>@<branch>` for the others.
+    pub fn head_key(&self) -> String {
+        self.branch_key(self.refs().current_branch())
+    }
+
+    pub fn branch_key(&self, branch: &str) -> String {
+        match branch {
+            DEFAULT_BRANCH => self.name.clone(),
+            branch => format!("{}@{}", self.name, branch),
+        }
+    }
+
+    /// Creates the branch `name` at `target` (a branch, tag or blob ID), or
+    /// at the head, and returns the snapshot it points at.
+    pub fn create_branch(&self, name: &str, target: Option<&str>) -> anyhow::Result<String> {
+        let mut refs = self.refs();
+        if refs.branches.contains_key(name) {
+            return Err(anyhow!("Branch '{}' already exists", name));
+        }
+        let blob_id = self.ref_target(&refs, name, target)?;
+        refs.branches.insert(name.to_string(), blob_id.clone());
+        self.write_refs(&refs)?;
+        Ok(blob_id)
+    }
+
+    /// Tags `target` (a branch, tag or blob ID), or the head, as `name`.
+    pub fn tag(&self, name: &str, target: Option<&str>) -> anyhow::Result<String> {
+        let mut refs = self.refs();
+        if refs.tags.contains_key(name) {
+            return Err(anyhow!("Tag '{}' already exists", name));
+        }
+        let blob_id = self.ref_target(&refs, name, target)?;
+        refs.tags.insert(name.to_string(), blob_id.clone());
+        self.write_refs(&refs)?;
+        Ok(blob_id)
+    }
+
+    fn ref_target(&self, refs: &Refs, name: &str, target: Option<&str>) -> anyhow::Result<String> {
+        refs::check_name(name)?;
+        match target {
+            Some(target) => Ok(refs.resolve(target)),
+            None => self
+                .head()
+                .ok_or_else(|| anyhow!("'{}' has no saved snapshot", self.name)),
+        }
+    }
+
+    pub fn delete_branch(&self, name: &str) -> anyhow::Result<()> {
+        let mut refs = self.refs();
+        if name == refs.current_branch() {
+            return Err(anyhow!("Cannot delete '{}', the current branch", name));
+        }
+        refs.branches
+            .remove(name)
+            .ok_or_else(|| anyhow!("No branch named '{}'", name))?;
+        self.write_refs(&refs)
+    }
+
+    pub fn delete_tag(&self, name: &str) -> anyhow::Result<()> {
+        let mut refs = self.refs();
+        refs.tags
+            .remove(name)
+            .ok_or_else(|| anyhow!("No tag named '{}'", name))?;
+        self.write_refs(&refs)
+    }
+
+    /// Checks out the tip of `branch` and makes it the branch later saves
+    /// move. Refused while there are unsaved changes, which would be lost.
+    ///
+    /// The working file must not be open while this runs.
+    pub fn switch(&self, branch: &str) -> anyhow::Result<String> {
+        let mut refs = self.refs();
+        let tip = refs
+            .branches
+            .get(branch)
+            .cloned()
+            .ok_or_else(|| anyhow!("No branch named '{}'", branch))?;
+        if !self.journal_entries().is_empty() {
+            return Err(anyhow!(
+                "'{}' has unsaved changes: save or rollback before switching",
+                self.name
+            ));
+        }
+
+        if self.head().as_ref() != Some(&tip) {
+            self.checkout(&tip)?;
+        }
+        refs.current = Some(branch.to_string()).filter(|branch| branch != DEFAULT_BRANCH);
+        self.write_refs(&refs)?;
+        Ok(tip)
+    }
+
+    /// Uploads the branches and tags as a blob, encrypted if this database
+    /// is, so they can be pulled elsewhere, and returns its blob ID.
+    pub fn push_refs(&self) -> anyhow::Result<String> {
+        let mut refs = self.refs();
+        refs.current = None;
+        let temp_path = self.dir.join("refs.upload");
+        fs::write(&temp_path, serde_json::to_string_pretty(&refs)?)?;
+        let blob_id = self.upload_file(&temp_path);
+        let _ = fs::remove_file(&temp_path);
+        blob_id
+    }
+
+    /// Merges the branches and tags of a blob made by [`Workspace::push_refs`]
+    /// into the local ones, fast-forwarding branches only. Returns the refs
+    /// left as they were because the pulled ones disagree, see
+    /// [`Refs::merge`].
+    pub fn pull_refs(&self, blob_id: &str) -> anyhow::Result<Vec<String>> {
+        let temp_path = self.dir.join("refs.download");
+        let file = temp_path.to_string_lossy().to_string();
+        let downloaded = walrus_io::download_from_walrus(blob_id.to_string(), file.clone());
+        let parse = || {
+            fs::read_to_string(&temp_path)
+                .ok()
+                .and_then(|refs| serde_json::from_str::<Refs>(&refs).ok())
+        };
+        let remote = downloaded.and_then(|_| {
+            parse().or_else(|| {
+                let key = self.key.as_deref()?;
+                decrypt_file(&file, &file, key).ok()?;
+                parse()
+            })
+        });
+        let _ = fs::remove_file(&temp_path);
+        let remote = remote.ok_or_else(|| anyhow!("Blob {} does not hold refs", blob_id))?;
+
+        let mut refs = self.refs();
+        let conflicts = refs.merge(remote, |ancestor, descendant| {
+            self.is_ancestor(ancestor, descendant)
+        });
+        self.write_refs(&refs)?;
+        Ok(conflicts)
+    }
+
+    /// Whether `descendant` was saved on top of `ancestor`, following the
+    /// snapshot log and downloading the snapshots saved elsewhere to read
+    /// what they were saved on. False when the chain can't be followed.
+    pub fn is_ancestor(&self, ancestor: &str, descendant: &str) -> bool {
+        let log: HashMap<String, Option<String>> = self
+            .snapshots()
+            .into_iter()
+            .map(|snapshot| (snapshot.blob_id, snapshot.previous))
+            .collect();
+        let temp_path = self.dir.join("ancestry.tmp");
+        let mut seen = HashSet::new();
+        let mut current = Some(descendant.to_string());
+        while let Some(blob_id) = current {
+            if blob_id == ancestor {
+                return true;
+            }
+            if !seen.insert(blob_id.clone()) {
+                return false;
+            }
+            current = match log.get(&blob_id) {
+                Some(previous) => previous.clone(),
+                None => {
+                    let previous = self.download(&blob_id, &temp_path);
+                    let _ = fs::remove_file(&temp_path);
+                    match previous {
+                        Ok(previous) => previous,
+                        Err(_) => return false,
+                    }
+                }
+            };
+        }
+        false
+    }
+
+    /// Applies the retention policy: deletes the snapshots it no longer keeps
+    /// and extends the storage of the ones it does. The head, pinned, branch,
+    /// tagged and `protected` snapshots are always kept, and the snapshots
+    /// left in the log point back past the deleted ones. A dry run only
+    /// reports.
+    pub fn gc(&self, protected: &[String], dry_run: bool) -> anyhow::Result<GcReport> {
+        let mut protected = protected.to_vec();
+        protected.extend(self.head());
+        protected.extend(self.pins());
+        protected.extend(self.refs().targets());
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        let current_epoch = walrus_io::current_epoch();
+        let planned = retention::plan(
+            &self.snapshots(),
+            &self.retention()?,
+            &protected,
+            now,
+            current_epoch,
+        );
+        if dry_run {
+            return Ok(GcReport {
+                current_epoch,
+                actions: planned,
+            });
+        }
+
+        let mut actions = Vec::new();
+        for (mut snapshot, action) in planned {
+            let done = match &action {
+                Action::Keep | Action::Expired => true,
+                Action::Extend(epochs) => {
+                    let object_id = snapshot.object_id.clone().unwrap_or_default();
+                    let extended = walrus_io::extend_on_walrus(object_id, *epochs);
+                    if extended {
+                        snapshot.end_epoch = snapshot.end_epoch.map(|end| end + epochs);
+                    }
+                    extended
+                }
+                Action::Delete => walrus_io::delete_from_walrus(snapshot.blob_id.clone()),
+            };
+            actions.push((snapshot, if done { action } else { Action::Keep }));
+        }
+
+        let remaining: Vec<SnapshotInfo> = actions
+            .iter()
+            .filter(|(_, action)| matches!(action, Action::Keep | Action::Extend(_)))
+            .map(|(snapshot, _)| snapshot.clone())
+            .collect();
+        self.write_snapshots(&retention::relink(remaining, &self.snapshots()))?;
+        Ok(GcReport {
+            current_epoch,
+            actions,
+        })
+    }
+
+    fn record_snapshot(&self, snapshot: &SnapshotInfo) -> anyhow::Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(SNAPSHOTS_FILE))?;
+        writeln!(file, "{}", serde_json::to_string(snapshot)?)?;
+        Ok(())
+    }
+}
+
+/// Moves everything in the WAL into the database file. The connection's
+/// busy timeout lets other connections finish first, but a read transaction
+/// held open past it leaves the WAL in use.
+fn checkpoint(conn: &Connection, name: &str) -> anyhow::Result<()> {
+    let busy: i64 = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))?;
+    if busy != 0 {
+        return Err(anyhow!(
+            "'{}' is busy in another connection and could not be checkpointed, close it and save again",
+            name
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_head() {
+        std::env::set_var(DB_ROOT_ENV, "/tmp/walter_db_test");
+        let workspace = Workspace::open("head_test").unwrap();
+        assert_eq!(
+            workspace.dir,
+            PathBuf::from("/tmp/walter_db_test/head_test")
+        );
+
+        workspace.set_head("").unwrap();
+        assert_eq!(workspace.head(), None);
+
+        workspace
+            .set_head("NKzOvrC2ksXDwOqTdk5NdqZ5aglAW5_dCS4GtbEChZ0\n")
+            .unwrap();
+        assert_eq!(
+            workspace.head(),
+            Some("NKzOvrC2ksXDwOqTdk5NdqZ5aglAW5_dCS4GtbEChZ0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_workspace_init_and_import() {
+        std::env::set_var(DB_ROOT_ENV, "/tmp/walter_db_test");
+        let _ = fs::remove_dir_all("/tmp/walter_db_test/init_test");
+        let _ = fs::remove_dir_all("/tmp/walter_db_test/import_test");
+
+        let workspace = Workspace::open("init_test").unwrap();
+        workspace.init().unwrap();
+        assert!(workspace.init().is_err());
+        assert_eq!(fs::metadata(workspace.db_path()).unwrap().len() % 512, 0);
+
+        let conn = Connection::open(workspace.db_path()).unwrap();
+        conn.execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (42);")
+            .unwrap();
+        drop(conn);
+
+        let imported = Workspace::open("import_test").unwrap();
+        imported.import(&workspace.db_path()).unwrap();
+        let conn = Connection::open(imported.db_path()).unwrap();
+        let x: i64 = conn
+            .query_row("SELECT x FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(x, 42);
+
+        let not_a_db = Path::new("/tmp/walter_db_test/not_a_db.txt");
+        fs::write(not_a_db, "hello world!").unwrap();
+        let _ = fs::remove_dir_all("/tmp/walter_db_test/import_bad_test");
+        let workspace = Workspace::open("import_bad_test").unwrap();
+        assert!(workspace.import(not_a_db).is_err());
+        assert!(!workspace.has_working_copy());
+        assert!(workspace
+            .import(Path::new("/tmp/walter_db_test/missing.db"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_workspace_encryption_key() {
+        std::env::set_var(DB_ROOT_ENV, "/tmp/walter_db_test");
+        let _ = fs::remove_dir_all("/tmp/walter_db_test/key_test");
+
+        let mut workspace = Workspace::open("key_test").unwrap();
+        assert!(!workspace.is_encrypted());
+        workspace.set_key("Password@123".to_string()).unwrap();
+        workspace.persist_key().unwrap();
+        assert!(workspace.is_encrypted());
+
+        let mut reopened = Workspace::open("key_test").unwrap();
+        assert!(reopened.set_key("password".to_string()).is_err());
+        assert!(reopened.set_key("Password@123".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_workspace_snapshot_log() {
+        std::env::set_var(DB_ROOT_ENV, "/tmp/walter_db_test");
+        let _ = fs::remove_dir_all("/tmp/walter_db_test/log_test");
+
+        let workspace = Workspace::open("log_test").unwrap();
+        assert!(workspace.snapshots().is_empty());
+
+        let root = SnapshotInfo {
+            blob_id: "NKzOvrC2ksXDwOqTdk5NdqZ5aglAW5_dCS4GtbEChZ0".to_string(),
+            previous: None,
+            timestamp: 1733961600,
+            message: Some("Initial snapshot".to_string()),
+            object_id: None,
+            end_epoch: None,
+            integrity: None,
+            page_count: None,
+        };
+        let next = SnapshotInfo {
+            blob_id: "Saih8gqlyGPC4LZhP5Co3KmJsJ1DWWTyyd-pdY9jYx0".to_string(),
+            previous: Some(root.blob_id.clone()),
+            timestamp: 1733965200,
+            message: None,
+            object_id: Some(
+                "0x5ad2e5a4a9a47a9c5d4ffed50c5b7b5ea0ad5a3f0e7a6e1bd0f6e6b1ac2b8a21".to_string(),
+            ),
+            end_epoch: Some(12),
+            integrity: Some("ok".to_string()),
+            page_count: Some(2),
+        };
+        workspace.record_snapshot(&root).unwrap();
+        workspace.record_snapshot(&next).unwrap();
+        assert!(workspace.is_ancestor(&root.blob_id, &next.blob_id));
+        assert!(workspace.is_ancestor(&root.blob_id, &root.blob_id));
+        assert!(!workspace.is_ancestor(&next.blob_id, &root.blob_id));
+        assert_eq!(workspace.snapshots(), vec![root.clone(), next]);
+
+        workspace
+            .write_snapshots(std::slice::from_ref(&root))
+            .unwrap();
+        assert_eq!(workspace.snapshots(), vec![root]);
+    }
+
+    #[test]
+    fn test_workspace_journal() {
+        std::env::set_var(DB_ROOT_ENV, "/tmp/walter_db_test");
+        let _ = fs::remove_dir_all("/tmp/walter_db_test/journal_test");
+
+        let workspace = Workspace::open("journal_test").unwrap();
+        assert!(workspace.journal_entries().is_empty());
+        workspace.journal("CREATE TABLE t (x);").unwrap();
+        workspace
+            .journal("INSERT INTO t VALUES ('two\nlines');")
+            .unwrap();
+        assert_eq!(
+            workspace.journal_entries(),
+            vec![
+                "CREATE TABLE t (x);".to_string(),
+                "INSERT INTO t VALUES ('two\nlines');".to_string()
+            ]
+        );
+        workspace.clear_journal().unwrap();
+        workspace.clear_journal().unwrap();
+        assert!(workspace.journal_entries().is_empty());
+    }
+
+    #[test]
+    fn test_copy_for_upload() {
+        std::env::set_var(DB_ROOT_ENV, "/tmp/walter_db_test");
+        let _ = fs::remove_dir_all("/tmp/walter_db_test/upload_test");
+
+        let workspace = Workspace::open("upload_test").unwrap();
+        workspace.init().unwrap();
+        let conn = Connection::open(workspace.db_path()).unwrap();
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE t (data TEXT);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 500)
+             INSERT INTO t SELECT hex(randomblob(100)) FROM n;
+             DELETE FROM t WHERE rowid > 10;",
+        )
+        .unwrap();
+
+        // Committed changes still in the WAL make it into the copy
+        let copy = workspace.dir.join("copy.db");
+        let pages = workspace.copy_for_upload(&copy, false).unwrap();
+        let count: i64 = Connection::open(&copy)
+            .unwrap()
+            .query_row("SELECT count(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 10);
+
+        let vacuumed = workspace.copy_for_upload(&copy, true).unwrap();
+        assert!(vacuumed < pages);
+
+        // A reader still on an older snapshot blocks the checkpoint
+        let reader = Connection::open(workspace.db_path()).unwrap();
+        reader
+            .execute_batch("BEGIN; SELECT count(*) FROM t;")
+            .unwrap();
+        conn.execute("INSERT INTO t VALUES ('new')", []).unwrap();
+        assert!(workspace.copy_for_upload(&copy, false).is_err());
+        reader.execute_batch("COMMIT").unwrap();
+        workspace.copy_for_upload(&copy, false).unwrap();
+        drop(conn);
+
+        // A corrupted database is refused
+        let mut bytes = fs::read(workspace.db_path()).unwrap();
+        for byte in &mut bytes[4096..8192] {
+            *byte = 0xff;
+        }
+        fs::write(workspace.db_path(), bytes).unwrap();
+        assert!(workspace.copy_for_upload(&copy, false).is_err());
+    }
+
+    #[test]
+    fn test_save_rejects_stale_head() {
+        std::env::set_var(DB_ROOT_ENV, "/tmp/walter_db_test");
+        let _ = fs::remove_dir_all("/tmp/walter_db_test/stale_test");
+        let _ = fs::remove_dir_all("/tmp/walter_db_test/stale_heads");
+        let heads = crate::heads::LocalHeads::new(PathBuf::from("/tmp/walter_db_test/stale_heads"));
+
+        let workspace = Workspace::open("stale_test").unwrap();
+        workspace.init().unwrap();
+        workspace.set_head("root").unwrap();
+        heads
+            .compare_and_swap("stale_test", None, "theirs")
+            .unwrap()
+            .unwrap();
+
+        // Rejected before anything is uploaded
+        let err = workspace.save(&heads, Some(1), false, None).unwrap_err();
+        let conflict = err.downcast_ref::<Conflict>().unwrap();
+        assert_eq!(conflict.head, "theirs");
+        assert_eq!(conflict.base.as_deref(), Some("root"));
+        assert_eq!(workspace.head(), Some("root".to_string()));
+    }
+
+    #[test]
+    fn test_workspace_refs() {
+        std::env::set_var(DB_ROOT_ENV, "/tmp/walter_db_test");
+        let _ = fs::remove_dir_all("/tmp/walter_db_test/refs_test");
+
+        let workspace = Workspace::open("refs_test").unwrap();
+        assert!(workspace.create_branch("exp", None).is_err());
+        workspace.set_head("root").unwrap();
+        assert_eq!(workspace.head_key(), "refs_test");
+
+        assert_eq!(workspace.create_branch("exp", None).unwrap(), "root");
+        assert!(workspace.create_branch("exp", None).is_err());
+        assert_eq!(workspace.tag("v1", Some("exp")).unwrap(), "root");
+        assert_eq!(workspace.tag("old", Some("abc")).unwrap(), "abc");
+        let refs = workspace.refs();
+        assert_eq!(refs.resolve("main"), "root");
+        assert_eq!(refs.names_of("root"), vec!["exp", "main", "v1"]);
+
+        // Already at the tip, so nothing needs downloading
+        assert_eq!(workspace.switch("exp").unwrap(), "root");
+        assert_eq!(workspace.head_key(), "refs_test@exp");
+        assert!(workspace.delete_branch("exp").is_err());
+        assert!(workspace.switch("nope").is_err());
+
+        workspace.journal("INSERT INTO t VALUES (1)").unwrap();
+        assert!(workspace.switch("main").is_err());
+        workspace.clear_journal().unwrap();
+        workspace.switch("main").unwrap();
+        workspace.delete_branch("exp").unwrap();
+        workspace.delete_tag("old").unwrap();
+        assert_eq!(workspace.refs().targets(), vec!["root", "root"]);
+    }
+
+    #[test]
+    fn test_workspace_retention_and_pins() {
+        std::env::set_var(DB_ROOT_ENV, "/tmp/walter_db_test");
+        let _ = fs::remove_dir_all("/tmp/walter_db_test/retention_test");
+
+        let workspace = Workspace::open("retention_test").unwrap();
+        assert_eq!(workspace.retention().unwrap(), Retention::default());
+        let policy = Retention {
+            keep_last: Some(10),
+            keep_daily: None,
+            epochs: Some(5),
+        };
+        workspace.set_retention(&policy).unwrap();
+        assert_eq!(workspace.retention().unwrap(), policy);
+
+        fs::write(
+            workspace.dir.join(RETENTION_FILE),
+            "{\n  \"keep_last\": \"ten\"\n}",
+        )
+        .unwrap();
+        let err = workspace.retention().unwrap_err().to_string();
+        assert!(err.contains(RETENTION_FILE), "{}", err);
+        assert!(err.contains("line 2"), "{}", err);
+        workspace.set_retention(&policy).unwrap();
+
+        workspace.pin("a").unwrap();
+        workspace.pin("b").unwrap();
+        workspace.pin("a").unwrap();
+        assert_eq!(workspace.pins(), vec!["a", "b"]);
+        workspace.unpin("a").unwrap();
+        assert_eq!(workspace.pins(), vec!["b"]);
+    }
+
+    #[test]
+    fn test_invalid_workspace_name() {
+        assert!(Workspace::open("../etc").is_err());
+        assert!(Workspace::open("").is_err());
+    }
+}
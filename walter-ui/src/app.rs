@@ -1,9 +1,31 @@
-use ratatui::widgets::{ScrollbarState, TableState};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
-use walter_core::client::{download_blob, upload_blob, WalrusClient};
-use walter_core::config::WalterConfig;
-use walter_core::epoch_extender::extend_epoch;
-use walter_core::types::BlobInfo;
+use crossterm::event::KeyEvent;
+use ratatui::widgets::{ListState, ScrollbarState, TableState};
+use serde::de::IgnoredAny;
+use tokio::sync::oneshot;
+
+use walter_core::client::{
+    download_blob_cancellable, run_walrus_json_async, upload_blob_cancellable, WalrusClient,
+};
+use walter_core::config::{Setting, WalterConfig, MAX_EPOCHS};
+use walter_core::epoch_extender::extend_epoch_cancellable;
+use walter_core::migrator::migrate_files_with_progress;
+use walter_core::system_info::SystemInfo;
+use walter_core::types::{
+    format_size, BlobInfo, BlobStatusRequest, BlobStatusResponse, DeleteOutput, DeleteRequest,
+    ExtendRequest,
+};
+
+use crate::file_picker::FilePicker;
+use crate::input::TextInput;
+use crate::jobs::{JobKind, JobStatus, Jobs};
+use crate::utils::{self, Snapshot};
+
+/// How often the session and blobs are fetched again with auto-refresh on.
+pub const AUTO_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
 
 pub enum CurrentScreen {
     Splash,
@@ -11,6 +33,62 @@ pub enum CurrentScreen {
     Uploader,
     Migrator,
     SharderAndEpochExtender,
+    Jobs,
+    Downloader,
+    Settings,
+}
+
+/// The Downloader input that typing goes to.
+#[derive(Clone, Copy, PartialEq)]
+pub enum DownloadField {
+    BlobId,
+    Destination,
+    Password,
+}
+
+/// The Sharder & Epoch Extender input that typing goes to.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SharderField {
+    File,
+    Password,
+    ConfirmPassword,
+    BlobId,
+}
+
+/// A Dashboard column the blobs can be sorted on.
+#[derive(Clone, Copy, PartialEq)]
+pub enum BlobColumn {
+    BlobId,
+    Size,
+    Certified,
+    Deletable,
+    Expiry,
+    ObjectId,
+    File,
+}
+
+impl BlobColumn {
+    pub const ALL: [BlobColumn; 7] = [
+        BlobColumn::BlobId,
+        BlobColumn::Size,
+        BlobColumn::Certified,
+        BlobColumn::Deletable,
+        BlobColumn::Expiry,
+        BlobColumn::ObjectId,
+        BlobColumn::File,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlobColumn::BlobId => "Blob ID",
+            BlobColumn::Size => "Unencoded size",
+            BlobColumn::Certified => "Certified",
+            BlobColumn::Deletable => "Deletable",
+            BlobColumn::Expiry => "Expiry epoch",
+            BlobColumn::ObjectId => "Object ID",
+            BlobColumn::File => "File",
+        }
+    }
 }
 
 pub struct App {
@@ -22,13 +100,15 @@ pub struct App {
     pub table_state: TableState,
     pub scrollbar_state: ScrollbarState,
     pub user_blobs: Vec<BlobInfo>,
-    pub walrus_system_info: String,
+    pub system_info: Option<SystemInfo>,
     pub is_editing: bool,
 
-    pub filename: String,
-    pub pinata_api_key: String,
-    pub shard_pass: String,
-    pub extender_blob_id: String,
+    pub filename: TextInput,
+    pub pinata_api_key: TextInput,
+    pub sharder_field: SharderField,
+    pub shard_pass: TextInput,
+    pub shard_pass_confirm: TextInput,
+    pub extender_blob_id: TextInput,
     pub walrus_client: WalrusClient,
 
     pub file_upload_status: String,
@@ -37,10 +117,63 @@ pub struct App {
     pub migration_status: String,
 
     pub epochs: u16,
+    pub jobs: Jobs,
+
+    /// Row 0 is a raw blob ID, the rest are the tracked files in name order
+    pub download_list_state: ListState,
+    pub download_field: DownloadField,
+    pub download_blob_id: TextInput,
+    pub download_destination: TextInput,
+    pub download_password: TextInput,
+    pub download_status: String,
+    pub download_job: Option<usize>,
+
+    pub show_blob_detail: bool,
+    pub blob_action_status: String,
+    /// The job started by the last action in the blob detail pane
+    pub blob_action_job: Option<usize>,
+    pub confirm_delete: bool,
+
+    /// Indices into `user_blobs` of the Dashboard rows, in display order
+    pub blob_view: Vec<usize>,
+    /// Tracked file name for each blob ID in the config
+    pub blob_files: HashMap<String, String>,
+    /// None keeps the order the walrus CLI listed the blobs in
+    pub blob_sort: Option<BlobColumn>,
+    pub blob_sort_descending: bool,
+    pub blob_search: String,
+    pub deletable_only: bool,
+    pub expiring_only: bool,
+    pub expiring_within: u64,
+
+    /// Set while a refresh is fetching in the background
+    pub refresh: Option<oneshot::Receiver<Snapshot>>,
+    pub last_refresh: Option<Instant>,
+    pub auto_refresh: bool,
+    pub session_updated: Option<Instant>,
+    pub blobs_updated: Option<Instant>,
+    pub system_info_updated: Option<Instant>,
+    /// What failed in the last refresh, shown in the degraded banner
+    pub refresh_errors: Vec<String>,
+
+    /// Open over the Uploader or Sharder while choosing files
+    pub file_picker: Option<FilePicker>,
+    /// Files chosen together in the picker, uploaded instead of `filename`
+    pub batch_files: Vec<String>,
+
+    /// Rows are `Setting::ALL`
+    pub settings_table_state: TableState,
+    /// The selected setting's value in the config file, edited in place
+    pub settings_input: TextInput,
+    pub settings_status: String,
 }
 
 impl App {
     pub fn new() -> Self {
+        App::with_config(WalterConfig::load_config_file())
+    }
+
+    fn with_config(config: WalterConfig) -> Self {
         App {
             current_screen: CurrentScreen::Splash,
             should_quit: false,
@@ -49,25 +182,59 @@ impl App {
             scrollbar_state: ScrollbarState::new(0),
             sui_active_address: String::new(),
             sui_active_env: String::new(),
-            walrus_system_info: String::new(),
+            system_info: None,
             is_editing: false,
-            filename: String::new(),
-            pinata_api_key: String::new(),
-            shard_pass: String::new(),
-            extender_blob_id: String::new(),
-            walrus_client: WalrusClient::new(WalterConfig::load_config_file()),
+            filename: TextInput::new(),
+            pinata_api_key: TextInput::masked(),
+            sharder_field: SharderField::File,
+            shard_pass: TextInput::masked(),
+            shard_pass_confirm: TextInput::masked(),
+            extender_blob_id: TextInput::new(),
+            walrus_client: WalrusClient::new(config),
             file_upload_status: String::new(),
             sharder_status: String::new(),
             extender_status: String::new(),
             migration_status: String::new(),
             epochs: 1,
+            jobs: Jobs::new(),
+            download_list_state: ListState::default().with_selected(Some(0)),
+            download_field: DownloadField::BlobId,
+            download_blob_id: TextInput::new(),
+            download_destination: TextInput::new(),
+            download_password: TextInput::masked(),
+            download_status: String::new(),
+            download_job: None,
+            show_blob_detail: false,
+            blob_action_status: String::new(),
+            blob_action_job: None,
+            confirm_delete: false,
+            blob_view: Vec::new(),
+            blob_files: HashMap::new(),
+            blob_sort: None,
+            blob_sort_descending: false,
+            blob_search: String::new(),
+            deletable_only: false,
+            expiring_only: false,
+            expiring_within: 5,
+            refresh: None,
+            last_refresh: None,
+            auto_refresh: false,
+            session_updated: None,
+            blobs_updated: None,
+            system_info_updated: None,
+            refresh_errors: Vec::new(),
+            file_picker: None,
+            batch_files: Vec::new(),
+            settings_table_state: TableState::default().with_selected(0),
+            settings_input: TextInput::new(),
+            settings_status: String::new(),
         }
     }
     pub fn next_row(&mut self) {
-        if !self.user_blobs.is_empty() {
+        if !self.blob_view.is_empty() {
             let i = match self.table_state.selected() {
                 Some(i) => {
-                    if i >= self.user_blobs.len() - 1 {
+                    if i >= self.blob_view.len() - 1 {
                         0
                     } else {
                         i + 1
@@ -80,11 +247,11 @@ impl App {
         }
     }
     pub fn prev_row(&mut self) {
-        if !self.user_blobs.is_empty() {
+        if !self.blob_view.is_empty() {
             let i = match self.table_state.selected() {
                 Some(i) => {
-                    if i <= 0 {
-                        self.user_blobs.len() - 1
+                    if i == 0 {
+                        self.blob_view.len() - 1
                     } else {
                         i - 1
                     }
@@ -96,64 +263,1103 @@ impl App {
         }
     }
 
-    pub async fn upload_file(&mut self) -> String {
-        let result = upload_blob(&self.filename, self.epochs).await;
+    pub fn open_file_picker(&mut self) {
+        self.file_picker = Some(FilePicker::open(self.filename.value()));
+    }
+
+    /// Takes the picker's selection as the file, or batch of files, to
+    /// upload. Keeps the picker open if nothing is selected.
+    pub fn choose_picked_files(&mut self) {
+        let Some(picker) = &self.file_picker else {
+            return;
+        };
+        let mut files = picker.selection();
+        match files.len() {
+            0 => return,
+            1 => {
+                self.filename.set(files.remove(0));
+                self.batch_files.clear();
+            }
+            _ => {
+                self.filename.clear();
+                self.batch_files = files;
+            }
+        }
+        self.file_picker = None;
+    }
 
-        match result {
-            Ok(b) => "success".to_string(),
-            Err(e) => "failure".to_string(),
+    pub fn upload_file(&mut self) {
+        if !self.batch_files.is_empty() {
+            self.upload_batch();
+            return;
         }
+
+        let filename = self.filename.value().to_string();
+        let epochs = self.epochs;
+
+        self.file_upload_status = "Uploading...".to_string();
+        self.jobs.spawn(
+            JobKind::Upload,
+            filename.clone(),
+            move |progress, cancel| async move {
+                progress.report(0, 1);
+                let blob_id = upload_blob_cancellable(&filename, epochs, &cancel)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                progress.report(1, 1);
+                Ok(blob_id)
+            },
+        );
     }
 
-    pub async fn upload_shard(&mut self) -> String {
-        let password = match self.shard_pass.len() {
-            0 => None,
-            _ => Some(self.shard_pass.clone()),
+    /// Uploads each file of the batch as a blob, one after another.
+    fn upload_batch(&mut self) {
+        let files = self.batch_files.clone();
+        let epochs = self.epochs;
+
+        self.file_upload_status = "Uploading...".to_string();
+        self.jobs.spawn(
+            JobKind::Upload,
+            format!("{} files", files.len()),
+            move |progress, cancel| async move {
+                for (i, file) in files.iter().enumerate() {
+                    progress.report(i, files.len());
+                    upload_blob_cancellable(file, epochs, &cancel)
+                        .await
+                        .map_err(|e| {
+                            format!("Uploaded {} of {}, {}: {}", i, files.len(), file, e)
+                        })?;
+                }
+                progress.report(files.len(), files.len());
+                Ok(format!("Uploaded {} files", files.len()))
+            },
+        );
+    }
+
+    /// The password to encrypt shards with, if one was entered and
+    /// confirmed.
+    fn shard_password(&self) -> Result<Option<String>, String> {
+        // A confirmation typed without the password is a mismatch too, not a
+        // request to upload unencrypted
+        if self.shard_pass.value() != self.shard_pass_confirm.value() {
+            Err("The passwords don't match".to_string())
+        } else if self.shard_pass.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(self.shard_pass.value().to_string()))
+        }
+    }
+
+    pub fn upload_shard(&mut self) {
+        let password = match self.shard_password() {
+            Ok(password) => password,
+            Err(e) => {
+                self.sharder_status = e;
+                return;
+            }
         };
+        let filename = self.filename.value().to_string();
+        let config = self.walrus_client.config.clone();
 
-        let result = self
-            .walrus_client
-            .upload_file(&self.filename, password)
-            .await;
+        // Walrus would reject every shard, so don't start uploading any
+        if let Some(system_info) = &self.system_info {
+            if let Err(e) = system_info.check_shard_size(config.default_shard_size) {
+                self.sharder_status = e;
+                return;
+            }
+        }
 
-        match result {
-            Ok(b) => "success".to_string(),
-            Err(e) => "failure".to_string(),
+        self.sharder_status = "running".to_string();
+        if !self.batch_files.is_empty() {
+            self.upload_shard_batch(config, password);
+            return;
         }
+        self.jobs.spawn(
+            JobKind::Shard,
+            filename.clone(),
+            move |progress, cancel| async move {
+                let mut client = WalrusClient::new(config).with_cancel(cancel);
+                client
+                    .upload_file_with_progress(&filename, password, |done, total| {
+                        progress.report(done, total)
+                    })
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok(filename)
+            },
+        );
+    }
+
+    /// Shards and uploads each file of the batch in turn, tracking every one
+    /// in the config.
+    fn upload_shard_batch(&mut self, config: WalterConfig, password: Option<String>) {
+        let files = self.batch_files.clone();
+
+        self.jobs.spawn(
+            JobKind::ShardBatch,
+            format!("{} files", files.len()),
+            move |progress, cancel| async move {
+                let mut client = WalrusClient::new(config).with_cancel(cancel);
+                for (i, file) in files.iter().enumerate() {
+                    progress.report(i, files.len());
+                    client
+                        .upload_file_with_progress(file, password.clone(), |_, _| {})
+                        .await
+                        .map_err(|e| {
+                            format!("Sharded {} of {}, {}: {}", i, files.len(), file, e)
+                        })?;
+                }
+                progress.report(files.len(), files.len());
+                Ok(format!("Sharded {} files", files.len()))
+            },
+        );
+    }
+
+    pub fn migrate(&mut self) {
+        let jwt = self.pinata_api_key.value().to_string();
+
+        self.migration_status = "Migrating...".into();
+        self.jobs.spawn(
+            JobKind::Migrate,
+            "Pinata files".into(),
+            move |progress, cancel| async move {
+                migrate_files_with_progress(&jwt, &cancel, |done, total| {
+                    progress.report(done, total)
+                })
+                .await
+                .map_err(|e| e.to_string())?;
+                Ok("Migration successful".into())
+            },
+        );
     }
 
-    pub async fn download_file(&mut self) -> String {
-        let result = download_blob(&self.extender_blob_id, &self.filename).await;
+    /// Hands the results of finished jobs to the screens that started them.
+    pub fn poll_jobs(&mut self) {
+        for (kind, label, status) in self.jobs.poll() {
+            match (kind, status) {
+                (JobKind::Upload, JobStatus::Succeeded(_)) => {
+                    self.file_upload_status = "File uploaded successfully!".to_string();
+                }
+                (JobKind::Upload, JobStatus::Failed(e)) => {
+                    self.file_upload_status = format!("File upload Failed: {e}");
+                }
+                (JobKind::Upload, _) => self.file_upload_status = "Upload cancelled".to_string(),
+                (JobKind::Shard | JobKind::ShardBatch, status) => {
+                    // The job saved the file's blobs to the config on disk
+                    self.walrus_client.config = WalterConfig::load_config_file();
+                    self.update_blob_view();
+                    self.sharder_status = status_name(&status);
+                }
+                (JobKind::Extend, status) => self.extender_status = status_name(&status),
+                (JobKind::Download, status) => {
+                    self.download_status = match status {
+                        JobStatus::Succeeded(message) => message,
+                        JobStatus::Failed(e) => format!("Download failed: {}", e),
+                        _ => "Download cancelled".to_string(),
+                    };
+                }
+                (JobKind::Delete, JobStatus::Succeeded(_)) => {
+                    self.user_blobs.retain(|blob| blob.blob_id != label);
+                    self.update_blob_view();
+                }
+                (JobKind::Delete, _) | (JobKind::Status, _) => {}
+                (JobKind::Migrate, status) => {
+                    // Files migrated before a failure or cancel are kept
+                    self.walrus_client.config = WalterConfig::load_config_file();
+                    self.update_blob_view();
+                    self.migration_status = match status {
+                        JobStatus::Succeeded(message) => message,
+                        JobStatus::Failed(e) => format!("Migration failed: {}", e),
+                        _ => "Migration cancelled, run it again to resume".into(),
+                    };
+                }
+            }
+        }
+    }
+
+    /// Fetches the session and blobs again in the background, unless a
+    /// refresh is already running.
+    pub fn refresh(&mut self) {
+        if self.refresh.is_some() {
+            return;
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        tokio::spawn(async move {
+            let _ = sender.send(utils::fetch_snapshot().await);
+        });
+        self.refresh = Some(receiver);
+        self.last_refresh = Some(Instant::now());
+    }
+
+    pub fn toggle_auto_refresh(&mut self) {
+        self.auto_refresh = !self.auto_refresh;
+    }
+
+    /// Applies a finished refresh and starts the next one when auto-refresh
+    /// is due.
+    pub fn poll_refresh(&mut self) {
+        if let Some(receiver) = &mut self.refresh {
+            match receiver.try_recv() {
+                Ok(snapshot) => {
+                    self.refresh = None;
+                    self.apply_snapshot(snapshot);
+                }
+                Err(oneshot::error::TryRecvError::Empty) => {}
+                Err(oneshot::error::TryRecvError::Closed) => self.refresh = None,
+            }
+        }
 
-        match result {
-            Ok(b) => "success".to_string(),
-            Err(e) => "failure".to_string(),
+        let due = self
+            .last_refresh
+            .is_none_or(|at| at.elapsed() >= AUTO_REFRESH_INTERVAL);
+        if self.auto_refresh && due {
+            self.refresh();
         }
     }
 
-    pub async fn download_sharded_file(&mut self) -> String {
-        let password = match self.shard_pass.len() {
+    /// Takes what the refresh managed to fetch and keeps the last good data
+    /// for the rest.
+    fn apply_snapshot(&mut self, snapshot: Snapshot) {
+        let now = Instant::now();
+        let mut errors = Vec::new();
+
+        match (snapshot.sui_active_address, snapshot.sui_active_env) {
+            (Ok(address), Ok(env)) => {
+                self.sui_active_address = address;
+                self.sui_active_env = env;
+                self.session_updated = Some(now);
+            }
+            (Err(e), _) | (_, Err(e)) => errors.push(format!("sui: {}", e)),
+        }
+
+        match snapshot.user_blobs {
+            Ok(blobs) => {
+                self.user_blobs = blobs;
+                self.blobs_updated = Some(now);
+            }
+            Err(e) => errors.push(format!("walrus blobs: {}", e)),
+        }
+
+        match snapshot.system_info {
+            Ok(system_info) => {
+                self.system_info = Some(system_info);
+                self.system_info_updated = Some(now);
+                self.epochs = self.epochs.min(self.max_epochs());
+            }
+            Err(e) => errors.push(format!("walrus info: {}", e)),
+        }
+
+        self.refresh_errors = errors;
+        self.update_blob_view();
+    }
+
+    /// Whether the current screen has inputs that edit mode types into.
+    pub fn has_inputs(&self) -> bool {
+        matches!(
+            self.current_screen,
+            CurrentScreen::Uploader
+                | CurrentScreen::Migrator
+                | CurrentScreen::SharderAndEpochExtender
+                | CurrentScreen::Downloader
+                | CurrentScreen::Settings
+        )
+    }
+
+    /// The input typing goes to in edit mode on the current screen.
+    fn focused_input(&mut self) -> Option<&mut TextInput> {
+        match self.current_screen {
+            CurrentScreen::Uploader => Some(&mut self.filename),
+            CurrentScreen::Migrator => Some(&mut self.pinata_api_key),
+            CurrentScreen::SharderAndEpochExtender => Some(match self.sharder_field {
+                SharderField::File => &mut self.filename,
+                SharderField::Password => &mut self.shard_pass,
+                SharderField::ConfirmPassword => &mut self.shard_pass_confirm,
+                SharderField::BlobId => &mut self.extender_blob_id,
+            }),
+            CurrentScreen::Downloader => Some(self.download_input()),
+            CurrentScreen::Settings => Some(&mut self.settings_input),
+            _ => None,
+        }
+    }
+
+    /// Whether the file path is the focused input, since typing a path
+    /// replaces a batch chosen in the file picker.
+    fn editing_filename(&self) -> bool {
+        match self.current_screen {
+            CurrentScreen::Uploader => true,
+            CurrentScreen::SharderAndEpochExtender => self.sharder_field == SharderField::File,
+            _ => false,
+        }
+    }
+
+    /// Edits the focused input. Returns false if `key` isn't an editing key.
+    pub fn input_key(&mut self, key: KeyEvent) -> bool {
+        let editing_filename = self.editing_filename();
+        let Some(input) = self.focused_input() else {
+            return false;
+        };
+        if !input.handle_key(key) {
+            return false;
+        }
+        if editing_filename {
+            self.batch_files.clear();
+        }
+        true
+    }
+
+    /// Pastes into the focused input, or the Dashboard search.
+    pub fn paste(&mut self, text: &str) {
+        if !self.is_editing {
+            return;
+        }
+        if matches!(self.current_screen, CurrentScreen::Dashboard) {
+            self.blob_search.push_str(text.trim());
+            self.update_blob_view();
+            return;
+        }
+        let editing_filename = self.editing_filename();
+        if let Some(input) = self.focused_input() {
+            input.paste(text);
+            if editing_filename {
+                self.batch_files.clear();
+            }
+        }
+    }
+
+    pub fn next_sharder_field(&mut self) {
+        self.sharder_field = match self.sharder_field {
+            SharderField::File => SharderField::Password,
+            SharderField::Password => SharderField::ConfirmPassword,
+            SharderField::ConfirmPassword => SharderField::BlobId,
+            SharderField::BlobId => SharderField::File,
+        };
+    }
+
+    /// Most epochs a blob can be stored or extended for, lower than
+    /// `MAX_EPOCHS` when the network says so.
+    pub fn max_epochs(&self) -> u16 {
+        self.system_info
+            .as_ref()
+            .and_then(|info| info.max_epochs_ahead)
+            .map_or(MAX_EPOCHS, |max| {
+                MAX_EPOCHS.min(u16::try_from(max).unwrap_or(u16::MAX))
+            })
+    }
+
+    pub fn selected_blob(&self) -> Option<&BlobInfo> {
+        let index = self.blob_view.get(self.table_state.selected()?)?;
+        self.user_blobs.get(*index)
+    }
+
+    fn clamp_selection(&mut self) {
+        let selected = match self.blob_view.len() {
             0 => None,
-            _ => Some(self.shard_pass.clone()),
+            len => Some(self.table_state.selected().unwrap_or(0).min(len - 1)),
         };
+        self.table_state.select(selected);
+        self.scrollbar_state = ScrollbarState::new(self.blob_view.len().saturating_sub(1))
+            .position(selected.unwrap_or(0));
+    }
+
+    /// Rebuilds the Dashboard rows from `user_blobs` with the current search,
+    /// filters and sort, keeping the selected blob selected if it is still
+    /// shown.
+    pub fn update_blob_view(&mut self) {
+        let selected = self.selected_blob().map(|blob| blob.blob_id.clone());
+        self.blob_files = tracked_blob_files(&self.walrus_client.config);
+
+        let search = self.blob_search.to_lowercase();
+        let current_epoch = self.system_info.as_ref().map(|info| info.current_epoch);
+        let mut view: Vec<usize> = (0..self.user_blobs.len())
+            .filter(|&i| {
+                let blob = &self.user_blobs[i];
+                let matches_search = search.is_empty()
+                    || blob.blob_id.to_lowercase().contains(&search)
+                    || blob.object_id.to_lowercase().contains(&search);
+                let expiring = match current_epoch {
+                    Some(epoch) if self.expiring_only => {
+                        blob.expiration_epoch.saturating_sub(epoch) <= self.expiring_within
+                    }
+                    _ => true,
+                };
+                matches_search && expiring && (blob.is_deletable || !self.deletable_only)
+            })
+            .collect();
+
+        if let Some(column) = self.blob_sort {
+            // Stable, so blobs that compare equal stay in CLI order
+            view.sort_by(|&a, &b| {
+                let ordering = self.compare_blobs(column, a, b);
+                if self.blob_sort_descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+        self.blob_view = view;
+
+        let position = selected.and_then(|blob_id| {
+            self.blob_view
+                .iter()
+                .position(|&i| self.user_blobs[i].blob_id == blob_id)
+        });
+        self.table_state.select(Some(position.unwrap_or(0)));
+        self.clamp_selection();
+        if self.blob_view.is_empty() {
+            self.show_blob_detail = false;
+        }
+    }
+
+    fn compare_blobs(&self, column: BlobColumn, a: usize, b: usize) -> Ordering {
+        let (a, b) = (&self.user_blobs[a], &self.user_blobs[b]);
+        match column {
+            BlobColumn::BlobId => a.blob_id.cmp(&b.blob_id),
+            BlobColumn::Size => a.size.cmp(&b.size),
+            BlobColumn::Certified => a.is_certified.cmp(&b.is_certified),
+            BlobColumn::Deletable => a.is_deletable.cmp(&b.is_deletable),
+            BlobColumn::Expiry => a.expiration_epoch.cmp(&b.expiration_epoch),
+            BlobColumn::ObjectId => a.object_id.cmp(&b.object_id),
+            // Untracked blobs go last
+            BlobColumn::File => match (
+                self.blob_files.get(&a.blob_id),
+                self.blob_files.get(&b.blob_id),
+            ) {
+                (Some(a), Some(b)) => a.cmp(b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        }
+    }
 
-        let result = self
+    /// Sorts on the next column, going back to CLI order after the last one.
+    pub fn next_blob_sort(&mut self) {
+        self.blob_sort = match self.blob_sort {
+            None => Some(BlobColumn::ALL[0]),
+            Some(column) => BlobColumn::ALL
+                .iter()
+                .position(|&c| c == column)
+                .and_then(|i| BlobColumn::ALL.get(i + 1))
+                .copied(),
+        };
+        self.update_blob_view();
+    }
+
+    pub fn reverse_blob_sort(&mut self) {
+        self.blob_sort_descending = !self.blob_sort_descending;
+        self.update_blob_view();
+    }
+
+    pub fn toggle_deletable_only(&mut self) {
+        self.deletable_only = !self.deletable_only;
+        self.update_blob_view();
+    }
+
+    /// Only turns the filter on once the current epoch is known, there is
+    /// nothing to compare expiry epochs against before that.
+    pub fn toggle_expiring_only(&mut self) {
+        self.expiring_only = !self.expiring_only && self.system_info.is_some();
+        self.update_blob_view();
+    }
+
+    pub fn change_expiring_within(&mut self, more: bool) {
+        if more {
+            self.expiring_within += 1;
+        } else if self.expiring_within > 0 {
+            self.expiring_within -= 1;
+        }
+        self.update_blob_view();
+    }
+
+    pub fn clear_blob_search(&mut self) {
+        self.blob_search.clear();
+        self.update_blob_view();
+    }
+
+    pub fn copy_selected_blob_id(&mut self) {
+        let Some(blob_id) = self.selected_blob().map(|blob| blob.blob_id.clone()) else {
+            return;
+        };
+        self.blob_action_status = match utils::copy_to_clipboard(&blob_id) {
+            Ok(via) => format!("Copied the blob ID with {}", via),
+            Err(e) => format!("Could not copy the blob ID: {}", e),
+        };
+    }
+
+    pub fn download_selected_blob(&mut self) {
+        let Some(blob_id) = self.selected_blob().map(|blob| blob.blob_id.clone()) else {
+            return;
+        };
+        self.download_list_state.select(Some(0));
+        self.download_field = DownloadField::BlobId;
+        self.download_blob_id.set(blob_id);
+        self.download_destination.clear();
+        self.download_file();
+
+        self.blob_action_job = self.download_job;
+        self.blob_action_status = self.download_status.clone();
+    }
+
+    /// Extends the storage of the selected blob object by `epochs` with
+    /// `walrus extend`, keeping its blob ID.
+    pub fn extend_selected_blob(&mut self) {
+        let Some(blob) = self.selected_blob().cloned() else {
+            return;
+        };
+        let epochs = u64::from(self.epochs);
+
+        self.blob_action_status = format!("Extending by {} epochs...", epochs);
+        let id = self.jobs.spawn(
+            JobKind::Extend,
+            blob.blob_id.clone(),
+            move |progress, cancel| async move {
+                progress.report(0, 1);
+                let request = ExtendRequest {
+                    blobObjId: blob.object_id,
+                    epochsAhead: epochs,
+                };
+                run_walrus_json_async::<_, IgnoredAny>(&request, &cancel)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                progress.report(1, 1);
+                Ok(format!(
+                    "Extended by {} epochs, refresh the list to see the new expiry",
+                    epochs
+                ))
+            },
+        );
+        self.blob_action_job = Some(id);
+    }
+
+    /// Deletes the selected blob, asking for a second press first.
+    pub fn delete_selected_blob(&mut self) {
+        let Some(blob) = self.selected_blob().cloned() else {
+            return;
+        };
+        if !blob.is_deletable {
+            self.blob_action_status = "This blob is not deletable".to_string();
+            return;
+        }
+        if !self.confirm_delete {
+            self.confirm_delete = true;
+            self.blob_action_status = "Press [X] again to delete this blob".to_string();
+            return;
+        }
+
+        self.confirm_delete = false;
+        self.blob_action_status = "Deleting...".to_string();
+        let id = self.jobs.spawn(
+            JobKind::Delete,
+            blob.blob_id.clone(),
+            move |progress, cancel| async move {
+                progress.report(0, 1);
+                let request = DeleteRequest {
+                    blobId: blob.blob_id,
+                    yes: true,
+                };
+                let deleted: Vec<DeleteOutput> = run_walrus_json_async(&request, &cancel)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                progress.report(1, 1);
+                let objects: usize = deleted.iter().map(|output| output.deletedBlobs.len()).sum();
+                Ok(format!("Deleted {} blob objects", objects))
+            },
+        );
+        self.blob_action_job = Some(id);
+    }
+
+    /// Looks up the live status of the selected blob with `walrus blob-status`.
+    pub fn check_selected_blob_status(&mut self) {
+        let Some(blob_id) = self.selected_blob().map(|blob| blob.blob_id.clone()) else {
+            return;
+        };
+
+        self.blob_action_status = "Checking status...".to_string();
+        let id = self.jobs.spawn(
+            JobKind::Status,
+            blob_id.clone(),
+            move |progress, cancel| async move {
+                progress.report(0, 1);
+                let response: BlobStatusResponse =
+                    run_walrus_json_async(&BlobStatusRequest { blobId: blob_id }, &cancel)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                progress.report(1, 1);
+                Ok(utils::describe_blob_status(&response.status))
+            },
+        );
+        self.blob_action_job = Some(id);
+    }
+
+    pub fn tracked_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = self
+            .walrus_client
+            .config
+            .get_files()
+            .keys()
+            .cloned()
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// The tracked file picked on the Downloader, `None` for a raw blob ID.
+    pub fn selected_tracked_file(&self) -> Option<String> {
+        let i = self.download_list_state.selected()?.checked_sub(1)?;
+        self.tracked_files().into_iter().nth(i)
+    }
+
+    /// Inputs the Downloader shows for the current selection, in tab order.
+    pub fn download_fields(&self) -> &'static [DownloadField] {
+        match self.selected_tracked_file() {
+            Some(_) => &[DownloadField::Destination, DownloadField::Password],
+            None => &[DownloadField::BlobId, DownloadField::Destination],
+        }
+    }
+
+    pub fn next_download_row(&mut self) {
+        let len = self.tracked_files().len() + 1;
+        let i = match self.download_list_state.selected() {
+            Some(i) if i + 1 < len => i + 1,
+            _ => 0,
+        };
+        self.select_download_row(i);
+    }
+
+    pub fn prev_download_row(&mut self) {
+        let len = self.tracked_files().len() + 1;
+        let i = match self.download_list_state.selected() {
+            Some(i) if i > 0 => i - 1,
+            _ => len - 1,
+        };
+        self.select_download_row(i);
+    }
+
+    fn select_download_row(&mut self, i: usize) {
+        self.download_list_state.select(Some(i));
+        self.download_field = self.download_fields()[0];
+        self.download_password.clear();
+    }
+
+    pub fn next_download_field(&mut self) {
+        let fields = self.download_fields();
+        let i = fields
+            .iter()
+            .position(|field| *field == self.download_field)
+            .map_or(0, |i| (i + 1) % fields.len());
+        self.download_field = fields[i];
+    }
+
+    pub fn open_settings(&mut self) {
+        self.current_screen = CurrentScreen::Settings;
+        self.settings_status.clear();
+        self.reset_setting_input();
+    }
+
+    pub fn selected_setting(&self) -> Setting {
+        Setting::ALL[self.settings_table_state.selected().unwrap_or(0)]
+    }
+
+    pub fn next_setting_row(&mut self) {
+        let i = self.settings_table_state.selected().unwrap_or(0);
+        self.settings_table_state
+            .select(Some((i + 1) % Setting::ALL.len()));
+        self.reset_setting_input();
+    }
+
+    pub fn prev_setting_row(&mut self) {
+        let len = Setting::ALL.len();
+        let i = self.settings_table_state.selected().unwrap_or(0);
+        self.settings_table_state.select(Some((i + len - 1) % len));
+        self.reset_setting_input();
+    }
+
+    /// Puts the selected setting's saved value back in the input.
+    pub fn reset_setting_input(&mut self) {
+        let value = self
             .walrus_client
-            .download_file(&self.filename, password)
-            .await;
+            .config
+            .file_value(self.selected_setting());
+        self.settings_input.set(value);
+    }
+
+    /// Checks the edited value and saves the config with it. Nothing is
+    /// changed if it is invalid or the config can't be written.
+    pub fn save_setting(&mut self) {
+        let setting = self.selected_setting();
+        let value = self.settings_input.value().to_string();
+
+        // The shard size and epoch limits depend on the network, so they are
+        // checked here
+        if let Some(system_info) = &self.system_info {
+            let checked = match setting {
+                Setting::ShardSize => value
+                    .trim()
+                    .parse()
+                    .map_or(Ok(()), |size| system_info.check_shard_size(size)),
+                Setting::DefaultEpochs | Setting::RenewalValue => value
+                    .trim()
+                    .parse()
+                    .map_or(Ok(()), |epochs| system_info.check_epochs(epochs)),
+                _ => Ok(()),
+            };
+            if let Err(e) = checked {
+                self.settings_status = format!("Not saved: {}", e);
+                return;
+            }
+        }
+
+        let config = &mut self.walrus_client.config;
+        if let Err(e) = config.save_change(|config| Ok(config.update(setting, &value)?)) {
+            self.settings_status = format!("Not saved: {}", e);
+            return;
+        }
+
+        self.settings_status = match config.overridden_by(setting) {
+            Some(var) => format!(
+                "Saved {}, {} still overrides it until Walter is restarted without it",
+                setting.name(),
+                var
+            ),
+            None => format!("Saved {}", setting.name()),
+        };
+        self.is_editing = false;
+        self.reset_setting_input();
+    }
+
+    pub fn download_input(&mut self) -> &mut TextInput {
+        match self.download_field {
+            DownloadField::BlobId => &mut self.download_blob_id,
+            DownloadField::Destination => &mut self.download_destination,
+            DownloadField::Password => &mut self.download_password,
+        }
+    }
+
+    /// The destination typed in, else `name` in the default download directory.
+    pub fn download_destination_for(&self, name: &str) -> String {
+        match self.download_destination.value().trim() {
+            "" => self.walrus_client.config.download_path(name),
+            destination => destination.to_string(),
+        }
+    }
+
+    pub fn start_download(&mut self) {
+        match self.selected_tracked_file() {
+            Some(_) => self.download_sharded_file(),
+            None => self.download_file(),
+        }
+    }
+
+    /// Downloads the raw blob typed into the Downloader.
+    pub fn download_file(&mut self) {
+        let blob_id = self.download_blob_id.value().trim().to_string();
+        if blob_id.is_empty() {
+            self.download_status = "Enter a blob ID to download".to_string();
+            return;
+        }
+        let destination = self.download_destination_for(&blob_id);
+
+        self.download_status = format!("Downloading {}...", blob_id);
+        let id = self.jobs.spawn(
+            JobKind::Download,
+            blob_id.clone(),
+            move |progress, cancel| async move {
+                progress.report(0, 1);
+                create_parent_dir(&destination)?;
+                if let Err(e) = download_blob_cancellable(&blob_id, &destination, &cancel).await {
+                    // walrus may have written part of the blob before it stopped
+                    let _ = std::fs::remove_file(&destination);
+                    return Err(e.to_string());
+                }
+                progress.report(1, 1);
+                let size = std::fs::metadata(&destination)
+                    .map_err(|e| e.to_string())?
+                    .len();
+                Ok(format!(
+                    "Saved {} to {}, verified by walrus against the blob ID",
+                    format_size(size),
+                    destination
+                ))
+            },
+        );
+        self.download_job = Some(id);
+    }
+
+    /// Downloads the tracked file selected on the Downloader and checks it
+    /// against the checksum recorded when it was uploaded.
+    pub fn download_sharded_file(&mut self) {
+        let Some(file_path) = self.selected_tracked_file() else {
+            return;
+        };
+        let is_encrypted = self.walrus_client.config.get_files()[&file_path].is_encrypted;
+        let password = match (is_encrypted, self.download_password.is_empty()) {
+            (false, _) => None,
+            (true, true) => {
+                self.download_status = format!("{} is encrypted, enter its password", file_path);
+                return;
+            }
+            (true, false) => Some(self.download_password.value().to_string()),
+        };
+        let destination = self.download_destination_for(&file_path);
+        let config = self.walrus_client.config.clone();
+
+        self.download_status = format!("Downloading {}...", file_path);
+        let id = self.jobs.spawn(
+            JobKind::Download,
+            file_path.clone(),
+            move |progress, cancel| async move {
+                let client = WalrusClient::new(config).with_cancel(cancel);
+                create_parent_dir(&destination)?;
+                client
+                    .download_file_with_progress(
+                        &file_path,
+                        &destination,
+                        password,
+                        |done, total| progress.report(done, total),
+                    )
+                    .await
+                    .map_err(|e| e.to_string())?;
+                match client
+                    .verify_file(&file_path, &destination)
+                    .map_err(|e| e.to_string())?
+                {
+                    Some(true) => Ok(format!("Saved to {}, checksum verified", destination)),
+                    Some(false) => Err(format!(
+                        "saved to {}, but it does not match the checksum of the uploaded file",
+                        destination
+                    )),
+                    None => Ok(format!(
+                        "Saved to {}, no checksum was recorded to verify it against",
+                        destination
+                    )),
+                }
+            },
+        );
+        self.download_job = Some(id);
+    }
+
+    pub fn extend_blob_epoch(&mut self) {
+        let blob_id = self.extender_blob_id.value().trim().to_string();
+        let epochs = self.epochs;
+
+        self.extender_status = "running".to_string();
+        self.jobs.spawn(
+            JobKind::Extend,
+            blob_id.clone(),
+            move |progress, cancel| async move {
+                progress.report(0, 1);
+                extend_epoch_cancellable(&blob_id, epochs, &cancel)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                progress.report(1, 1);
+                Ok(blob_id)
+            },
+        );
+    }
+}
+
+fn status_name(status: &JobStatus) -> String {
+    match status {
+        JobStatus::Running => "running".to_string(),
+        JobStatus::Succeeded(_) => "success".to_string(),
+        JobStatus::Failed(_) => "failure".to_string(),
+        JobStatus::Cancelled => "cancelled".to_string(),
+    }
+}
+
+/// Maps each blob ID in the config to the file it belongs to, numbering the
+/// shards of files split over several blobs.
+fn tracked_blob_files(config: &WalterConfig) -> HashMap<String, String> {
+    let mut files = HashMap::new();
+    for (name, info) in config.get_files() {
+        for (i, blob_id) in info.blobs.iter().enumerate() {
+            let label = match info.blobs.len() {
+                1 => name.clone(),
+                shards => format!("{} ({}/{})", name, i + 1, shards),
+            };
+            files.insert(blob_id.clone(), label);
+        }
+    }
+    files
+}
+
+fn create_parent_dir(path: &str) -> Result<(), String> {
+    match std::path::Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())
+        }
+        _ => Ok(()),
+    }
+}
 
-        match result {
-            Ok(b) => "success".to_string(),
-            Err(e) => "failure".to_string(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blob(blob_id: &str, size: u64, is_deletable: bool, expiration_epoch: u64) -> BlobInfo {
+        BlobInfo {
+            blob_id: blob_id.to_string(),
+            unencoded_size: format_size(size),
+            size,
+            is_certified: true,
+            is_deletable,
+            expiration_epoch,
+            object_id: format!("0x{}", blob_id.to_lowercase()),
         }
     }
 
-    pub async fn extend_blob_epoch(&self) -> String {
-        let result = extend_epoch(&self.extender_blob_id, self.epochs).await;
+    fn test_app() -> App {
+        let mut app = App::with_config(WalterConfig::default());
+        app.user_blobs = vec![
+            blob("Alpha", 300, true, 60),
+            blob("Beta", 100, false, 52),
+            blob("Gamma", 200, true, 90),
+            blob("Delta", 100, false, 70),
+        ];
+        app.update_blob_view();
+        app
+    }
+
+    fn shown(app: &App) -> Vec<&str> {
+        app.blob_view
+            .iter()
+            .map(|&i| app.user_blobs[i].blob_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_blob_search() {
+        let mut app = test_app();
+        assert_eq!(shown(&app), ["Alpha", "Beta", "Gamma", "Delta"]);
+
+        app.blob_search = "ta".to_string();
+        app.update_blob_view();
+        assert_eq!(shown(&app), ["Beta", "Delta"]);
+
+        // Object IDs match too
+        app.blob_search = "0XGAM".to_string();
+        app.update_blob_view();
+        assert_eq!(shown(&app), ["Gamma"]);
+
+        app.blob_search = "nothing".to_string();
+        app.update_blob_view();
+        assert!(shown(&app).is_empty());
+        assert_eq!(app.table_state.selected(), None);
+
+        app.clear_blob_search();
+        assert_eq!(shown(&app).len(), 4);
+        assert_eq!(app.table_state.selected(), Some(0));
+    }
+
+    #[test]
+    fn test_blob_filters() {
+        let mut app = test_app();
+        app.toggle_deletable_only();
+        assert_eq!(shown(&app), ["Alpha", "Gamma"]);
+        app.toggle_deletable_only();
+
+        // Not available until the current epoch is known
+        app.toggle_expiring_only();
+        assert!(!app.expiring_only);
+        assert_eq!(shown(&app).len(), 4);
 
-        match result {
-            Ok(b) => "success".to_string(),
-            Err(e) => "failure".to_string(),
+        app.system_info = Some(SystemInfo {
+            current_epoch: 50,
+            ..SystemInfo::default()
+        });
+        app.toggle_expiring_only();
+        assert!(app.expiring_only);
+        assert_eq!(shown(&app), ["Beta"]);
+        for _ in 0..10 {
+            app.change_expiring_within(true);
         }
+        assert_eq!(shown(&app), ["Alpha", "Beta"]);
+
+        app.toggle_deletable_only();
+        assert_eq!(shown(&app), ["Alpha"]);
+        app.toggle_expiring_only();
+        assert_eq!(shown(&app), ["Alpha", "Gamma"]);
+    }
+
+    #[test]
+    fn test_blob_sort() {
+        let mut app = test_app();
+        app.blob_sort = Some(BlobColumn::Size);
+        app.update_blob_view();
+        // Equal sizes keep the order walrus listed them in
+        assert_eq!(shown(&app), ["Beta", "Delta", "Gamma", "Alpha"]);
+
+        app.reverse_blob_sort();
+        assert_eq!(shown(&app), ["Alpha", "Gamma", "Beta", "Delta"]);
+
+        app.blob_sort = Some(BlobColumn::Expiry);
+        app.blob_sort_descending = false;
+        app.update_blob_view();
+        assert_eq!(shown(&app), ["Beta", "Alpha", "Delta", "Gamma"]);
+
+        // The last column goes back to walrus order
+        app.blob_sort = BlobColumn::ALL.last().copied();
+        app.next_blob_sort();
+        assert!(app.blob_sort.is_none());
+        assert_eq!(shown(&app), ["Alpha", "Beta", "Gamma", "Delta"]);
+    }
+
+    #[test]
+    fn test_network_epoch_limit() {
+        let mut app = test_app();
+        assert_eq!(app.max_epochs(), MAX_EPOCHS);
+
+        app.epochs = 190;
+        app.apply_snapshot(Snapshot {
+            sui_active_address: Err("no sui".to_string()),
+            sui_active_env: Err("no sui".to_string()),
+            user_blobs: Err("no walrus".to_string()),
+            system_info: Ok(SystemInfo {
+                current_epoch: 50,
+                max_epochs_ahead: Some(183),
+                ..SystemInfo::default()
+            }),
+        });
+        assert_eq!(app.max_epochs(), 183);
+        assert_eq!(app.epochs, 183);
+        assert_eq!(app.user_blobs.len(), 4);
+    }
+
+    #[test]
+    fn test_shard_password() {
+        let mut app = test_app();
+        assert_eq!(app.shard_password(), Ok(None));
+
+        app.shard_pass_confirm.set("secret");
+        assert!(app.shard_password().is_err());
+
+        app.shard_pass.set("secret");
+        assert_eq!(app.shard_password(), Ok(Some("secret".to_string())));
+
+        app.shard_pass_confirm.set("secrets");
+        assert!(app.shard_password().is_err());
+    }
+
+    #[test]
+    fn test_blob_selection_kept() {
+        let mut app = test_app();
+        app.next_row();
+        app.next_row();
+        assert_eq!(app.selected_blob().unwrap().blob_id, "Gamma");
+
+        app.blob_sort = Some(BlobColumn::BlobId);
+        app.update_blob_view();
+        assert_eq!(shown(&app), ["Alpha", "Beta", "Delta", "Gamma"]);
+        assert_eq!(app.selected_blob().unwrap().blob_id, "Gamma");
+
+        app.toggle_deletable_only();
+        assert_eq!(app.selected_blob().unwrap().blob_id, "Gamma");
+
+        // A refresh that drops the blob selects the first row
+        app.user_blobs.retain(|blob| blob.blob_id != "Gamma");
+        app.update_blob_view();
+        assert_eq!(app.selected_blob().unwrap().blob_id, "Alpha");
     }
 }
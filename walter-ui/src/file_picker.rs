@@ -0,0 +1,309 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ratatui::widgets::ListState;
+
+pub struct Entry {
+    pub name: String,
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+/// A directory browser for choosing the files to upload. Typed characters
+/// narrow the listing with a fuzzy filter and any number of files or
+/// directories can be marked before confirming.
+pub struct FilePicker {
+    pub dir: PathBuf,
+    pub entries: Vec<Entry>,
+    pub filter: String,
+    pub show_hidden: bool,
+    pub marked: BTreeSet<PathBuf>,
+    pub list_state: ListState,
+    /// Why the directory could not be listed
+    pub error: Option<String>,
+}
+
+impl FilePicker {
+    /// Opens in the directory of `path`, else the working directory.
+    pub fn open(path: &str) -> Self {
+        let path = Path::new(path);
+        let dir = if path.is_dir() {
+            path.to_path_buf()
+        } else {
+            path.parent()
+                .filter(|parent| parent.is_dir())
+                .map(Path::to_path_buf)
+                .or_else(|| std::env::current_dir().ok())
+                .unwrap_or_else(|| PathBuf::from("."))
+        };
+
+        let mut picker = FilePicker {
+            dir: dir.canonicalize().unwrap_or(dir),
+            entries: Vec::new(),
+            filter: String::new(),
+            show_hidden: false,
+            marked: BTreeSet::new(),
+            list_state: ListState::default(),
+            error: None,
+        };
+        picker.reload();
+        picker
+    }
+
+    /// Lists `dir` again, directories first.
+    pub fn reload(&mut self) {
+        self.entries.clear();
+        self.error = None;
+        match fs::read_dir(&self.dir) {
+            Ok(read_dir) => {
+                for entry in read_dir.flatten() {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if name.starts_with('.') && !self.show_hidden {
+                        continue;
+                    }
+                    // Follows symlinks, so a link to a directory can be entered
+                    let metadata = fs::metadata(entry.path()).ok();
+                    self.entries.push(Entry {
+                        name,
+                        path: entry.path(),
+                        is_dir: metadata.as_ref().is_some_and(|m| m.is_dir()),
+                        size: metadata.map_or(0, |m| m.len()),
+                    });
+                }
+                self.entries
+                    .sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name)));
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+        self.list_state.select(if self.visible().is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+    }
+
+    /// The entries matching the filter.
+    pub fn visible(&self) -> Vec<&Entry> {
+        self.entries
+            .iter()
+            .filter(|entry| fuzzy_match(&self.filter, &entry.name))
+            .collect()
+    }
+
+    pub fn highlighted(&self) -> Option<&Entry> {
+        self.visible().get(self.list_state.selected()?).copied()
+    }
+
+    pub fn next_row(&mut self) {
+        let len = self.visible().len();
+        if len > 0 {
+            let i = self.list_state.selected().map_or(0, |i| (i + 1) % len);
+            self.list_state.select(Some(i));
+        }
+    }
+
+    pub fn prev_row(&mut self) {
+        let len = self.visible().len();
+        if len > 0 {
+            let i = self
+                .list_state
+                .selected()
+                .map_or(0, |i| (i + len - 1) % len);
+            self.list_state.select(Some(i));
+        }
+    }
+
+    /// Goes into the highlighted directory. Returns false if a file is
+    /// highlighted instead.
+    pub fn enter(&mut self) -> bool {
+        match self.highlighted() {
+            Some(entry) if entry.is_dir => {
+                self.dir = entry.path.clone();
+                self.filter.clear();
+                self.reload();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn parent(&mut self) {
+        if let Some(parent) = self.dir.parent() {
+            let previous = self.dir.clone();
+            self.dir = parent.to_path_buf();
+            self.filter.clear();
+            self.reload();
+            // Keep the directory we came out of highlighted
+            if let Some(i) = self
+                .visible()
+                .iter()
+                .position(|entry| entry.path == previous)
+            {
+                self.list_state.select(Some(i));
+            }
+        }
+    }
+
+    pub fn toggle_hidden(&mut self) {
+        self.show_hidden = !self.show_hidden;
+        self.reload();
+    }
+
+    pub fn toggle_mark(&mut self) {
+        if let Some(path) = self.highlighted().map(|entry| entry.path.clone()) {
+            if !self.marked.remove(&path) {
+                self.marked.insert(path);
+            }
+            self.next_row();
+        }
+    }
+
+    pub fn push_filter(&mut self, c: char) {
+        self.filter.push(c);
+        self.list_state.select(Some(0));
+    }
+
+    pub fn pop_filter(&mut self) {
+        self.filter.pop();
+        self.list_state.select(Some(0));
+    }
+
+    /// The files chosen: every marked file plus the files anywhere under
+    /// marked directories, else the highlighted file. Hidden entries under a
+    /// marked directory are left out unless hidden entries are shown.
+    pub fn selection(&self) -> Vec<String> {
+        let paths: Vec<PathBuf> = if self.marked.is_empty() {
+            self.highlighted()
+                .filter(|entry| !entry.is_dir)
+                .map(|entry| vec![entry.path.clone()])
+                .unwrap_or_default()
+        } else {
+            self.marked
+                .iter()
+                .flat_map(|path| {
+                    if path.is_dir() {
+                        self.files_in(path)
+                    } else {
+                        vec![path.clone()]
+                    }
+                })
+                .collect()
+        };
+
+        paths
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect()
+    }
+
+    fn files_in(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return files;
+        };
+        for entry in read_dir.flatten() {
+            if !self.show_hidden && entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            // Symlinked directories aren't followed, they could loop
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                files.extend(self.files_in(&path));
+            } else if path.is_file() {
+                files.push(path);
+            }
+        }
+        files.sort();
+        files
+    }
+}
+
+/// Whether the characters of `filter` appear in `name` in order, ignoring
+/// case.
+fn fuzzy_match(filter: &str, name: &str) -> bool {
+    let mut name = name.chars().flat_map(char::to_lowercase);
+    filter
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|c| name.any(|n| n == c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert!(fuzzy_match("", "anything"));
+        assert!(fuzzy_match("rdm", "README.md"));
+        assert!(fuzzy_match("README", "readme.md"));
+        assert!(fuzzy_match("ÄB", "äpfel.bin"));
+        assert!(!fuzzy_match("mdr", "README.md"));
+        assert!(!fuzzy_match("readmes", "README.md"));
+    }
+
+    #[test]
+    fn test_selection() {
+        let dir = std::env::temp_dir().join(format!("walter_picker_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("photos/2024")).unwrap();
+        fs::create_dir_all(dir.join(".cache")).unwrap();
+        for file in [
+            "notes.txt",
+            ".secret",
+            "photos/a.jpg",
+            "photos/.thumbs",
+            "photos/2024/b.jpg",
+            ".cache/c.bin",
+        ] {
+            fs::write(dir.join(file), file).unwrap();
+        }
+        let path = |file: &str| dir.join(file).to_string_lossy().to_string();
+
+        let mut picker = FilePicker::open(dir.to_str().unwrap());
+        let names: Vec<&str> = picker.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["photos", "notes.txt"]);
+
+        // A highlighted directory alone is not a selection
+        assert!(picker.selection().is_empty());
+        picker.push_filter('n');
+        picker.push_filter('t');
+        assert_eq!(picker.selection(), [path("notes.txt")]);
+
+        picker.pop_filter();
+        picker.pop_filter();
+        picker.toggle_mark();
+        assert_eq!(
+            picker.selection(),
+            [path("photos/2024/b.jpg"), path("photos/a.jpg")]
+        );
+        picker.toggle_mark();
+        assert_eq!(
+            picker.selection(),
+            [
+                path("notes.txt"),
+                path("photos/2024/b.jpg"),
+                path("photos/a.jpg")
+            ]
+        );
+
+        picker.toggle_hidden();
+        picker.marked.clear();
+        picker.marked.insert(dir.clone());
+        assert_eq!(
+            picker.selection(),
+            [
+                path(".cache/c.bin"),
+                path(".secret"),
+                path("notes.txt"),
+                path("photos/.thumbs"),
+                path("photos/2024/b.jpg"),
+                path("photos/a.jpg"),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
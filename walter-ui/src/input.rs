@@ -0,0 +1,238 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    style::{Modifier, Style},
+    text::{Line, Span},
+};
+
+/// A single line text field with a cursor. Masked fields show a bullet per
+/// character, for passwords and API keys.
+#[derive(Default)]
+pub struct TextInput {
+    value: String,
+    /// In characters, not bytes
+    cursor: usize,
+    masked: bool,
+}
+
+impl TextInput {
+    pub fn new() -> Self {
+        TextInput::default()
+    }
+
+    pub fn masked() -> Self {
+        TextInput {
+            masked: true,
+            ..TextInput::default()
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+
+    /// Replaces the text and puts the cursor after it.
+    pub fn set(&mut self, value: impl Into<String>) {
+        self.value = value.into();
+        self.cursor = self.value.chars().count();
+    }
+
+    pub fn clear(&mut self) {
+        self.set(String::new());
+    }
+
+    /// Edits the text for `key`. Returns false for keys that aren't editing
+    /// keys, like Enter or Tab, so the screen can handle them.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.len(),
+            KeyCode::Char('u') if ctrl => self.clear(),
+            KeyCode::Char(_) if ctrl => return false,
+            KeyCode::Char(c) => self.insert(c),
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.value.remove(self.byte_index());
+            }
+            KeyCode::Delete if self.cursor < self.len() => {
+                self.value.remove(self.byte_index());
+            }
+            KeyCode::Backspace | KeyCode::Delete => {}
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.len(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Inserts pasted text at the cursor, dropping line breaks since the
+    /// field is a single line.
+    pub fn paste(&mut self, text: &str) {
+        for c in text.chars().filter(|c| *c != '\n' && *c != '\r') {
+            self.insert(c);
+        }
+    }
+
+    /// The text as shown, with the cursor drawn when `show_cursor` is set.
+    pub fn line(&self, show_cursor: bool) -> Line<'static> {
+        let shown: Vec<char> = if self.masked {
+            vec!['•'; self.len()]
+        } else {
+            self.value.chars().collect()
+        };
+        if !show_cursor {
+            return Line::from(shown.into_iter().collect::<String>());
+        }
+
+        let before: String = shown[..self.cursor].iter().collect();
+        let under = shown.get(self.cursor).copied().unwrap_or(' ');
+        let after: String = shown.iter().skip(self.cursor + 1).collect();
+        Line::from(vec![
+            Span::raw(before),
+            Span::styled(
+                under.to_string(),
+                Style::default().add_modifier(Modifier::REVERSED),
+            ),
+            Span::raw(after),
+        ])
+    }
+
+    fn insert(&mut self, c: char) {
+        let index = self.byte_index();
+        self.value.insert(index, c);
+        self.cursor += 1;
+    }
+
+    fn len(&self) -> usize {
+        self.value.chars().count()
+    }
+
+    fn byte_index(&self) -> usize {
+        self.value
+            .char_indices()
+            .nth(self.cursor)
+            .map_or(self.value.len(), |(i, _)| i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(input: &mut TextInput, code: KeyCode) -> bool {
+        input.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn ctrl(input: &mut TextInput, c: char) -> bool {
+        input.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL))
+    }
+
+    fn shown(line: &Line) -> String {
+        line.spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    #[test]
+    fn test_edit_multi_byte() {
+        let mut input = TextInput::new();
+        input.set("héllo wörld");
+
+        press(&mut input, KeyCode::Backspace);
+        assert_eq!(input.value(), "héllo wörl");
+
+        for _ in 0..3 {
+            press(&mut input, KeyCode::Left);
+        }
+        press(&mut input, KeyCode::Backspace);
+        assert_eq!(input.value(), "héllo örl");
+        press(&mut input, KeyCode::Delete);
+        assert_eq!(input.value(), "héllo rl");
+        press(&mut input, KeyCode::Char('ø'));
+        assert_eq!(input.value(), "héllo ørl");
+
+        press(&mut input, KeyCode::Home);
+        press(&mut input, KeyCode::Right);
+        press(&mut input, KeyCode::Delete);
+        assert_eq!(input.value(), "hllo ørl");
+        press(&mut input, KeyCode::Char('€'));
+        assert_eq!(input.value(), "h€llo ørl");
+
+        // Nothing to remove at either end
+        press(&mut input, KeyCode::Home);
+        press(&mut input, KeyCode::Backspace);
+        press(&mut input, KeyCode::End);
+        press(&mut input, KeyCode::Delete);
+        press(&mut input, KeyCode::Right);
+        assert_eq!(input.value(), "h€llo ørl");
+        press(&mut input, KeyCode::Char('!'));
+        assert_eq!(input.value(), "h€llo ørl!");
+    }
+
+    #[test]
+    fn test_cursor_keys() {
+        let mut input = TextInput::new();
+        input.set("abc");
+
+        assert!(ctrl(&mut input, 'a'));
+        press(&mut input, KeyCode::Char('>'));
+        assert!(ctrl(&mut input, 'e'));
+        press(&mut input, KeyCode::Char('<'));
+        assert_eq!(input.value(), ">abc<");
+
+        assert!(ctrl(&mut input, 'u'));
+        assert!(input.is_empty());
+        press(&mut input, KeyCode::Char('x'));
+        assert_eq!(input.value(), "x");
+
+        assert!(!ctrl(&mut input, 'c'));
+        assert!(!press(&mut input, KeyCode::Enter));
+        assert!(!press(&mut input, KeyCode::Tab));
+        assert_eq!(input.value(), "x");
+    }
+
+    #[test]
+    fn test_paste_drops_newlines() {
+        let mut input = TextInput::new();
+        input.set("ab");
+        press(&mut input, KeyCode::Left);
+        input.paste("1\r\n2\n");
+        assert_eq!(input.value(), "a12b");
+        press(&mut input, KeyCode::Char('3'));
+        assert_eq!(input.value(), "a123b");
+    }
+
+    #[test]
+    fn test_line() {
+        let mut input = TextInput::masked();
+        input.set("pässword");
+        assert_eq!(shown(&input.line(false)), "••••••••");
+        assert!(!shown(&input.line(true)).contains('s'));
+
+        let mut input = TextInput::new();
+        input.set("añb");
+        press(&mut input, KeyCode::Left);
+        press(&mut input, KeyCode::Left);
+        let line = input.line(true);
+        assert_eq!(line.spans.len(), 3);
+        assert_eq!(line.spans[0].content, "a");
+        assert_eq!(line.spans[1].content, "ñ");
+        assert!(line.spans[1]
+            .style
+            .add_modifier
+            .contains(Modifier::REVERSED));
+        assert_eq!(line.spans[2].content, "b");
+
+        // The cursor past the end is drawn on a space
+        press(&mut input, KeyCode::End);
+        assert_eq!(shown(&input.line(true)), "añb ");
+        assert_eq!(shown(&input.line(false)), "añb");
+    }
+}
@@ -0,0 +1,280 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use ratatui::widgets::TableState;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use walter_core::cancel::CancelToken;
+
+/// What a background job does, so its result can be routed back to the
+/// screen that started it.
+#[derive(Clone, Copy, PartialEq)]
+pub enum JobKind {
+    Upload,
+    Shard,
+    /// Several files sharded one after another
+    ShardBatch,
+    Extend,
+    Migrate,
+    Download,
+    Delete,
+    Status,
+}
+
+impl JobKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobKind::Upload => "Upload",
+            JobKind::Shard => "Shard",
+            JobKind::ShardBatch => "Shard batch",
+            JobKind::Extend => "Extend",
+            JobKind::Migrate => "Migrate",
+            JobKind::Download => "Download",
+            JobKind::Delete => "Delete",
+            JobKind::Status => "Status",
+        }
+    }
+
+    /// What `done` and `total` count for this kind of job.
+    pub fn unit(&self) -> &'static str {
+        match self {
+            JobKind::Shard | JobKind::Download => "shards",
+            JobKind::Migrate | JobKind::ShardBatch => "files",
+            JobKind::Upload | JobKind::Extend | JobKind::Delete | JobKind::Status => "blobs",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum JobStatus {
+    Running,
+    Succeeded(String),
+    Failed(String),
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_running(&self) -> bool {
+        *self == JobStatus::Running
+    }
+}
+
+/// Sent by running jobs to the event loop.
+pub enum JobEvent {
+    Progress {
+        id: usize,
+        done: usize,
+        total: usize,
+    },
+    Finished {
+        id: usize,
+        result: Result<String, String>,
+    },
+}
+
+/// Handed to a job so it can report how far along it is.
+#[derive(Clone)]
+pub struct Progress {
+    id: usize,
+    sender: UnboundedSender<JobEvent>,
+}
+
+impl Progress {
+    pub fn report(&self, done: usize, total: usize) {
+        // The receiver only goes away when the UI exits
+        let _ = self.sender.send(JobEvent::Progress {
+            id: self.id,
+            done,
+            total,
+        });
+    }
+}
+
+pub struct Job {
+    pub id: usize,
+    pub kind: JobKind,
+    pub label: String,
+    pub status: JobStatus,
+    pub done: usize,
+    pub total: usize,
+    pub started: Instant,
+    pub finished: Option<Instant>,
+    pub cancel: CancelToken,
+}
+
+impl Job {
+    /// Cancel was asked for but the job is still cleaning up.
+    pub fn is_cancelling(&self) -> bool {
+        self.status.is_running() && self.cancel.is_cancelled()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.finished.unwrap_or_else(Instant::now) - self.started
+    }
+
+    /// Fraction done, 0 until the job has reported a total.
+    pub fn ratio(&self) -> f64 {
+        match self.status {
+            JobStatus::Succeeded(_) => 1.0,
+            _ if self.total == 0 => 0.0,
+            _ => (self.done as f64 / self.total as f64).min(1.0),
+        }
+    }
+}
+
+/// Long running operations, run as tokio tasks so the UI keeps drawing and
+/// handling keys while they work.
+pub struct Jobs {
+    pub list: Vec<Job>,
+    pub table_state: TableState,
+    sender: UnboundedSender<JobEvent>,
+    receiver: UnboundedReceiver<JobEvent>,
+    next_id: usize,
+}
+
+impl Jobs {
+    pub fn new() -> Self {
+        let (sender, receiver) = unbounded_channel();
+        Jobs {
+            list: Vec::new(),
+            table_state: TableState::default(),
+            sender,
+            receiver,
+            next_id: 1,
+        }
+    }
+
+    /// Starts `task` in the background and returns the new job's id. The
+    /// task gets the token `cancel_selected` cancels and is expected to clean
+    /// up and return once it is.
+    pub fn spawn<F, Fut>(&mut self, kind: JobKind, label: String, task: F) -> usize
+    where
+        F: FnOnce(Progress, CancelToken) -> Fut,
+        Fut: Future<Output = Result<String, String>> + Send + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let cancel = CancelToken::new();
+        let future = task(
+            Progress {
+                id,
+                sender: self.sender.clone(),
+            },
+            cancel.clone(),
+        );
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            let result = future.await;
+            let _ = sender.send(JobEvent::Finished { id, result });
+        });
+
+        self.list.push(Job {
+            id,
+            kind,
+            label,
+            status: JobStatus::Running,
+            done: 0,
+            total: 0,
+            started: Instant::now(),
+            finished: None,
+            cancel,
+        });
+        self.table_state.select(Some(self.list.len() - 1));
+        id
+    }
+
+    /// Applies the events sent since the last call and returns the kind and
+    /// label of the jobs that finished in the meantime along with how they
+    /// ended.
+    pub fn poll(&mut self) -> Vec<(JobKind, String, JobStatus)> {
+        let mut finished = Vec::new();
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                JobEvent::Progress { id, done, total } => {
+                    if let Some(job) = self.get_mut(id) {
+                        job.done = done;
+                        job.total = total;
+                    }
+                }
+                JobEvent::Finished { id, result } => {
+                    if let Some(job) = self.get_mut(id) {
+                        // A job cancelled after its last step still succeeds
+                        job.status = match result {
+                            Ok(message) => JobStatus::Succeeded(message),
+                            Err(_) if job.cancel.is_cancelled() => JobStatus::Cancelled,
+                            Err(message) => JobStatus::Failed(message),
+                        };
+                        job.finished = Some(Instant::now());
+                        finished.push((job.kind, job.label.clone(), job.status.clone()));
+                    }
+                }
+            }
+        }
+        finished
+    }
+
+    /// Asks the selected job to stop. It shows as cancelled once it has
+    /// killed its walrus process and removed its temp files.
+    pub fn cancel_selected(&mut self) {
+        if let Some(job) = self.table_state.selected().and_then(|i| self.list.get(i)) {
+            job.cancel.cancel();
+        }
+    }
+
+    /// Cancels every running job and waits up to `timeout` for them to
+    /// finish, so no walrus process outlives the UI.
+    pub async fn shutdown(&mut self, timeout: Duration) {
+        for job in &self.list {
+            job.cancel.cancel();
+        }
+        let deadline = Instant::now() + timeout;
+        while self.running() > 0 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            self.poll();
+        }
+    }
+
+    pub fn running(&self) -> usize {
+        self.list
+            .iter()
+            .filter(|job| job.status.is_running())
+            .count()
+    }
+
+    pub fn next_row(&mut self) {
+        if !self.list.is_empty() {
+            let i = match self.table_state.selected() {
+                Some(i) if i + 1 < self.list.len() => i + 1,
+                _ => 0,
+            };
+            self.table_state.select(Some(i));
+        }
+    }
+
+    pub fn prev_row(&mut self) {
+        if !self.list.is_empty() {
+            let i = match self.table_state.selected() {
+                Some(i) if i > 0 => i - 1,
+                _ => self.list.len() - 1,
+            };
+            self.table_state.select(Some(i));
+        }
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.list.iter().find(|job| job.id == id)
+    }
+
+    fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.list.iter_mut().find(|job| job.id == id)
+    }
+}
+
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs >= 3600 {
+        format!("{}h{:02}m{:02}s", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
@@ -1,10 +1,16 @@
 mod app;
+mod file_picker;
+mod input;
+mod jobs;
 mod ui;
 mod utils;
 
 use app::{App, CurrentScreen};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -15,12 +21,18 @@ use ratatui::{
 use std::{
     error::Error,
     io::{self, Stdout},
+    time::Duration,
 };
 use ui::render_ui;
-use walter_core::client::{download_blob, upload_blob};
-use walter_core::migrator::migrate_files;
 use walter_core::updater;
-use walter_db;
+
+/// How long the event loop waits for a key before redrawing, so job
+/// progress keeps moving while nothing is pressed.
+const TICK_RATE: Duration = Duration::from_millis(100);
+
+/// How long jobs still running on exit get to stop their walrus processes
+/// and clean up.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -34,7 +46,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 println!("  \x1b[1;32m-c, --cli\x1b[0m     CLI mode");
                 println!("  \x1b[1;32m-s, --setup\x1b[0m   Setup Walter along with Walrus CLI, Walrus Site Builder and Sui Client");
                 println!("  \x1b[1;32m-u, --update\x1b[0m  Update Walter and its dependencies");
-                println!("  \x1b[1;32m-sql, --sqlite\x1b[0m Run Walrus SQLite shell with rollbacks. Requires blobID as argument");
+                println!("  \x1b[1;32m-sql, --sqlite\x1b[0m Run Walrus SQLite shell with rollbacks. Takes a database name and an optional blobID, or new <name> / import <file.db>");
                 println!("  \x1b[1;32m-bs, --build-site\x1b[0m Build Walrus site with the given app path");
                 println!(
                     "  \x1b[1;32m-rp, --run-pinner\x1b[0m Run Walrus Pinner in the background"
@@ -67,7 +79,10 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 updater::run();
             }
             "--sqlite" | "-sql" => {
-                walter_db::main().unwrap();
+                if let Err(e) = walter_db::run(&args[2..]) {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
                 return Ok(());
             }
             "--build-site" | "-bs" => {
@@ -122,36 +137,30 @@ async fn main() -> Result<(), Box<dyn Error>> {
     }
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
 
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
+    // Loads behind the splash screen, and a missing CLI shows a banner
+    // instead of stopping the UI from starting
     let mut app = App::new();
-
-    let sui_active_env = utils::sui_active_env().await?;
-    let sui_active_env = sui_active_env.trim().to_string();
-    app.sui_active_env = sui_active_env;
-
-    let sui_active_address = utils::sui_active_address().await?;
-    let sui_active_address = sui_active_address.trim().to_string();
-    app.sui_active_address = sui_active_address;
-
-    let user_blobs = utils::walrus_list_blobs().await?;
-    let user_blobs = serde_json::from_str(&user_blobs)?;
-    app.user_blobs = user_blobs;
-
-    let walrus_system_info = utils::walrus_info_system().await?;
-    let walrus_system_info = walrus_system_info.trim().to_string();
-    app.walrus_system_info = walrus_system_info;
+    app.refresh();
 
     let _res = run_app(&mut terminal, &mut app).await;
+    app.jobs.shutdown(SHUTDOWN_TIMEOUT).await;
 
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
     Ok(())
@@ -162,9 +171,27 @@ async fn run_app(
     app: &mut App,
 ) -> io::Result<bool> {
     loop {
+        app.poll_jobs();
+        app.poll_refresh();
         terminal.draw(|frame| render_ui(frame, app))?;
 
-        if let Event::Key(key) = event::read()? {
+        if !event::poll(TICK_RATE)? {
+            continue;
+        }
+
+        let event = event::read()?;
+        if let Event::Paste(text) = &event {
+            app.paste(text);
+            continue;
+        }
+
+        if let Event::Key(key) = event {
+            // The file picker takes every key while it is open
+            if key.kind == event::KeyEventKind::Press && app.file_picker.is_some() {
+                handle_file_picker_key(app, key.code);
+                continue;
+            }
+
             if key.kind == event::KeyEventKind::Press {
                 if key.code == KeyCode::Esc {
                     if app.is_editing {
@@ -172,13 +199,14 @@ async fn run_app(
                     }
                 }
                 if key.code == KeyCode::Char('e') || key.code == KeyCode::Char('E') {
-                    if !app.is_editing {
+                    if !app.is_editing && app.has_inputs() {
                         app.is_editing = true;
                         continue;
                     }
                 }
 
-                if !app.should_quit {
+                // While editing, letters and digits are typed into the input
+                if !app.should_quit && !app.is_editing {
                     match key.code {
                         KeyCode::Char('q') => app.should_quit = true,
                         _ => {}
@@ -193,21 +221,29 @@ async fn run_app(
                     }
                 }
 
-                if key.code == KeyCode::Char('1') {
-                    app.current_screen = CurrentScreen::Dashboard;
+                if key.code == KeyCode::F(5) {
+                    app.refresh();
                 }
 
-                if key.code == KeyCode::Char('2') {
-                    app.current_screen = CurrentScreen::Uploader;
-                }
-
-                if key.code == KeyCode::Char('3') {
-                    app.current_screen = CurrentScreen::Migrator;
+                if !app.is_editing {
+                    match key.code {
+                        KeyCode::Char('1') => app.current_screen = CurrentScreen::Dashboard,
+                        KeyCode::Char('2') => app.current_screen = CurrentScreen::Uploader,
+                        KeyCode::Char('3') => app.current_screen = CurrentScreen::Migrator,
+                        KeyCode::Char('4') => {
+                            app.current_screen = CurrentScreen::SharderAndEpochExtender
+                        }
+                        KeyCode::Char('5') => app.current_screen = CurrentScreen::Jobs,
+                        KeyCode::Char('6') => app.current_screen = CurrentScreen::Downloader,
+                        KeyCode::Char('7') => app.open_settings(),
+                        _ => {}
+                    }
                 }
+            }
 
-                if key.code == KeyCode::Char('4') {
-                    app.current_screen = CurrentScreen::SharderAndEpochExtender;
-                }
+            // Typing, cursor keys and the like go to the focused input
+            if key.kind == event::KeyEventKind::Press && app.is_editing && app.input_key(key) {
+                continue;
             }
 
             match app.current_screen {
@@ -220,19 +256,72 @@ async fn run_app(
                     }
                     _ => {}
                 },
-                CurrentScreen::Dashboard => match key.code {
-                    KeyCode::Char('c') => {}
-                    KeyCode::Up => {
-                        app.prev_row();
+                CurrentScreen::Dashboard if app.is_editing => match key.code {
+                    KeyCode::Up => app.prev_row(),
+                    KeyCode::Down => app.next_row(),
+                    KeyCode::Char(value) => {
+                        app.blob_search.push(value);
+                        app.update_blob_view();
                     }
-                    KeyCode::Down => {
-                        app.next_row();
+                    KeyCode::Backspace => {
+                        app.blob_search.pop();
+                        app.update_blob_view();
                     }
+                    KeyCode::Enter => app.is_editing = false,
                     _ => {}
                 },
+                CurrentScreen::Dashboard => {
+                    match key.code {
+                        KeyCode::Up | KeyCode::Down => {
+                            if key.code == KeyCode::Up {
+                                app.prev_row();
+                            } else {
+                                app.next_row();
+                            }
+                            app.blob_action_status.clear();
+                            app.blob_action_job = None;
+                        }
+                        KeyCode::Enter if app.selected_blob().is_some() => {
+                            app.show_blob_detail = true
+                        }
+                        KeyCode::Esc if app.show_blob_detail => app.show_blob_detail = false,
+                        // Also where Esc ends up while typing a search
+                        KeyCode::Esc => app.clear_blob_search(),
+                        _ => {}
+                    }
+                    if !app.show_blob_detail {
+                        match key.code {
+                            KeyCode::Char('/') => app.is_editing = true,
+                            KeyCode::Char('o') | KeyCode::Char('O') => app.next_blob_sort(),
+                            KeyCode::Char('r') | KeyCode::Char('R') => app.reverse_blob_sort(),
+                            KeyCode::Char('d') | KeyCode::Char('D') => app.toggle_deletable_only(),
+                            KeyCode::Char('w') | KeyCode::Char('W') => app.toggle_expiring_only(),
+                            KeyCode::Char('a') | KeyCode::Char('A') => app.toggle_auto_refresh(),
+                            KeyCode::Char('+') => app.change_expiring_within(true),
+                            KeyCode::Char('-') => app.change_expiring_within(false),
+                            _ => {}
+                        }
+                    } else {
+                        match key.code {
+                            KeyCode::Char('c') | KeyCode::Char('C') => app.copy_selected_blob_id(),
+                            KeyCode::Char('d') | KeyCode::Char('D') => app.download_selected_blob(),
+                            KeyCode::Char('t') | KeyCode::Char('T') => app.extend_selected_blob(),
+                            KeyCode::Char('x') | KeyCode::Char('X') => app.delete_selected_blob(),
+                            KeyCode::Char('s') | KeyCode::Char('S') => {
+                                app.check_selected_blob_status()
+                            }
+                            KeyCode::Char('+') if app.epochs < app.max_epochs() => app.epochs += 1,
+                            KeyCode::Char('-') if app.epochs > 1 => app.epochs -= 1,
+                            _ => {}
+                        }
+                    }
+                    if !matches!(key.code, KeyCode::Char('x') | KeyCode::Char('X')) {
+                        app.confirm_delete = false;
+                    }
+                }
                 CurrentScreen::Uploader => match key.code {
                     KeyCode::Up | KeyCode::Char('+') => {
-                        if app.epochs < 200 {
+                        if app.epochs < app.max_epochs() {
                             app.epochs += 1;
                         }
                     }
@@ -241,80 +330,50 @@ async fn run_app(
                             app.epochs -= 1;
                         }
                     }
-                    KeyCode::Char(value) => {
-                        if app.is_editing {
-                            app.filename += &value.to_string();
-                        }
-                    }
-                    KeyCode::Backspace => {
-                        if app.is_editing {
-                            if app.filename.len() > 0 {
-                                app.filename.pop();
-                            }
-                        }
-                    }
-                    KeyCode::Enter => {
-                        let res = upload_blob(&app.filename, app.epochs).await;
-                        match res {
-                            Ok(blob_id) => {
-                                app.file_upload_status = "File uploaded successfully!".to_string();
-                            }
-                            Err(e) => {
-                                app.file_upload_status =
-                                    format!("File upload Failed: {e}").to_string();
-                            }
-                        }
+                    KeyCode::Char('b') | KeyCode::Char('B') if !app.is_editing => {
+                        app.open_file_picker()
                     }
+                    KeyCode::Enter => app.upload_file(),
                     _ => {}
                 },
                 CurrentScreen::Migrator => match key.code {
-                    KeyCode::Char('v') | KeyCode::Char('V') => {
-                        app.pinata_api_key = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.eyJ1c2VySW5mb3JtYXRpb24iOnsiaWQiOiI4YzAxZGVjYy1iZmFiLTQ4Y2UtOTQyMy05NjJkMWNkYjlhODYiLCJlbWFpbCI6InByYW5lZXRoc2Fyb2RlQGdtYWlsLmNvbSIsImVtYWlsX3ZlcmlmaWVkIjp0cnVlLCJwaW5fcG9saWN5Ijp7InJlZ2lvbnMiOlt7ImRlc2lyZWRSZXBsaWNhdGlvbkNvdW50IjoxLCJpZCI6IkZSQTEifSx7ImRlc2lyZWRSZXBsaWNhdGlvbkNvdW50IjoxLCJpZCI6Ik5ZQzEifV0sInZlcnNpb24iOjF9LCJtZmFfZW5hYmxlZCI6ZmFsc2UsInN0YXR1cyI6IkFDVElWRSJ9LCJhdXRoZW50aWNhdGlvblR5cGUiOiJzY29wZWRLZXkiLCJzY29wZWRLZXlLZXkiOiJmOTg4MzJhZDZkZmI0Mzk0NWM3MyIsInNjb3BlZEtleVNlY3JldCI6IjhlMTE3NTFlMjE2ZTczYWI4MWIxYWQ5NDkwYjliYWYyN2RiNDVhNjU3NzQzNzVhZTNjMzI2N2U4NDMzODBhNDUiLCJleHAiOjE3NjUxMTQ2OTF9.Gl5_t61lvIF4jds9ZNnXiEZdE_O4E9_imFeuYPiJqEE".into();
-                    }
-                    KeyCode::Char('x') => {
-                        app.pinata_api_key = "".into();
-                    }
-                    KeyCode::Char('M') | KeyCode::Char('m') => {
-                        let res = migrate_files(&app.pinata_api_key).await;
-                        match res {
-                            Ok(_) => {
-                                app.migration_status = "Migration successful".into();
-                            }
-                            Err(e) => {
-                                app.migration_status = format!("Migration failed: {}", e);
-                            }
-                        }
+                    KeyCode::Char('x') | KeyCode::Char('X') => app.pinata_api_key.clear(),
+                    KeyCode::Char('M') | KeyCode::Char('m') => app.migrate(),
+                    KeyCode::Enter => {
+                        app.is_editing = false;
+                        app.migrate();
                     }
                     _ => {}
                 },
                 CurrentScreen::SharderAndEpochExtender => match key.code {
-                    KeyCode::Char('K') | KeyCode::Char('k') => {
-                        let status = app.upload_shard().await;
-                        app.sharder_status = status;
-                    }
-                    KeyCode::Char('Y') | KeyCode::Char('y') => {
-                        app.shard_pass = "password".into();
-                        let status = app.upload_shard().await;
-                        app.sharder_status = status;
-                    }
-                    KeyCode::Char('T') | KeyCode::Char('t') => {
-                        let status = app.extend_blob_epoch().await;
-                        app.extender_status = status;
-                    }
-                    KeyCode::Char('V') | KeyCode::Char('v') => {
-                        app.extender_blob_id = "A7Zy48JtR7Qid2V5eGhTizqzYjnooEE3Thi_RtTStOU".into();
-                    }
-                    KeyCode::Char(value) => {
-                        if app.is_editing {
-                            app.filename += &value.to_string();
-                        }
-                    }
-                    KeyCode::Backspace => {
-                        if app.is_editing {
-                            if app.filename.len() > 0 {
-                                app.filename.pop();
-                            }
-                        }
+                    KeyCode::Char('K') | KeyCode::Char('k') => app.upload_shard(),
+                    KeyCode::Char('T') | KeyCode::Char('t') => app.extend_blob_epoch(),
+                    KeyCode::Char('b') | KeyCode::Char('B') => app.open_file_picker(),
+                    KeyCode::Tab => app.next_sharder_field(),
+                    _ => {}
+                },
+                CurrentScreen::Jobs => match key.code {
+                    KeyCode::Up => app.jobs.prev_row(),
+                    KeyCode::Down => app.jobs.next_row(),
+                    KeyCode::Char('c') | KeyCode::Char('C') => app.jobs.cancel_selected(),
+                    _ => {}
+                },
+                CurrentScreen::Settings => match key.code {
+                    KeyCode::Up if !app.is_editing => app.prev_setting_row(),
+                    KeyCode::Down if !app.is_editing => app.next_setting_row(),
+                    KeyCode::Enter if app.is_editing => app.save_setting(),
+                    KeyCode::Enter => app.is_editing = true,
+                    // Editing was cancelled, drop the unsaved value
+                    KeyCode::Esc => app.reset_setting_input(),
+                    _ => {}
+                },
+                CurrentScreen::Downloader => match key.code {
+                    KeyCode::Up if !app.is_editing => app.prev_download_row(),
+                    KeyCode::Down if !app.is_editing => app.next_download_row(),
+                    KeyCode::Tab => app.next_download_field(),
+                    KeyCode::Enter => {
+                        app.is_editing = false;
+                        app.start_download();
                     }
                     _ => {}
                 },
@@ -322,3 +381,32 @@ async fn run_app(
         }
     }
 }
+
+fn handle_file_picker_key(app: &mut App, code: KeyCode) {
+    let Some(picker) = app.file_picker.as_mut() else {
+        return;
+    };
+    match code {
+        KeyCode::Esc => app.file_picker = None,
+        KeyCode::Up => picker.prev_row(),
+        KeyCode::Down => picker.next_row(),
+        KeyCode::Right => {
+            picker.enter();
+        }
+        KeyCode::Left => picker.parent(),
+        KeyCode::Backspace if picker.filter.is_empty() => picker.parent(),
+        KeyCode::Backspace => picker.pop_filter(),
+        KeyCode::Tab => picker.toggle_hidden(),
+        KeyCode::Char(' ') => picker.toggle_mark(),
+        KeyCode::Char(value) => picker.push_filter(value),
+        // With files marked, Enter chooses them rather than opening a directory
+        KeyCode::Enter if !picker.marked.is_empty() => app.choose_picked_files(),
+        KeyCode::Enter => {
+            let opened = picker.enter();
+            if !opened {
+                app.choose_picked_files();
+            }
+        }
+        _ => {}
+    }
+}
@@ -3,13 +3,20 @@ use ratatui::{
     style::{Color, Modifier, Style, Stylize},
     text::{Line, Span, Text},
     widgets::{
-        Block, BorderType, Borders, Cell, Clear, HighlightSpacing, Padding, Paragraph, Row,
+        Block, BorderType, Borders, Cell, Clear, Gauge, List, ListItem, Padding, Paragraph, Row,
         Scrollbar, ScrollbarOrientation, Table, Wrap,
     },
     Frame,
 };
 
-use crate::app::{App, CurrentScreen};
+use crate::app::{App, BlobColumn, CurrentScreen, DownloadField, SharderField};
+use crate::input::TextInput;
+use crate::jobs::{format_elapsed, JobStatus};
+use crate::utils::estimate_expiry;
+use std::time::{Duration, Instant};
+use walter_core::config::{Setting, MAX_EPOCHS};
+use walter_core::system_info::format_price;
+use walter_core::types::format_size;
 
 pub fn render_ui(frame: &mut Frame, app: &mut App) {
     let centered_rect = centered_rect(95, 95, frame.area());
@@ -27,6 +34,8 @@ pub fn render_ui(frame: &mut Frame, app: &mut App) {
         ])
         .split(centered_rect);
 
+    render_status_banner(frame, app, chunks[0]);
+
     match app.current_screen {
         CurrentScreen::Splash => render_splash_screen(frame, app, chunks[1]),
         CurrentScreen::Dashboard => {
@@ -74,10 +83,104 @@ pub fn render_ui(frame: &mut Frame, app: &mut App) {
             );
             render_sharder_and_extender(frame, app, chunks[1]);
         }
+        CurrentScreen::Jobs => {
+            frame.render_widget(
+                Paragraph::new("").block(
+                    Block::bordered()
+                        .title("~ [ Jobs ] ~")
+                        .title_alignment(Alignment::Center),
+                ),
+                frame.area(),
+            );
+            render_jobs(frame, app, chunks[1]);
+        }
+        CurrentScreen::Downloader => {
+            frame.render_widget(
+                Paragraph::new("").block(
+                    Block::bordered()
+                        .title("~ [ Downloader ] ~")
+                        .title_alignment(Alignment::Center),
+                ),
+                frame.area(),
+            );
+            render_downloader(frame, app, chunks[1]);
+        }
+        CurrentScreen::Settings => {
+            frame.render_widget(
+                Paragraph::new("").block(
+                    Block::bordered()
+                        .title("~ [ Settings ] ~")
+                        .title_alignment(Alignment::Center),
+                ),
+                frame.area(),
+            );
+            render_settings(frame, app, chunks[1]);
+        }
+    }
+
+    if matches!(app.current_screen, CurrentScreen::Dashboard) && app.show_blob_detail {
+        render_blob_detail(frame, app, centered_rect);
+    }
+
+    if app.file_picker.is_some() {
+        render_file_picker(frame, app, centered_rect);
     }
 
     if app.should_quit {
-        render_exit_popup(frame, centered_rect);
+        render_exit_popup(frame, app, centered_rect);
+    }
+}
+
+/// Says what could not be fetched, so a missing or failing CLI is obvious
+/// while the rest of the UI keeps working.
+fn render_status_banner(frame: &mut Frame, app: &App, area: Rect) {
+    let area = area.inner(Margin {
+        vertical: 0,
+        horizontal: 2,
+    });
+    let banner = if !app.refresh_errors.is_empty() {
+        Paragraph::new(format!(
+            "Degraded, could not fetch {} | [F5] Retry",
+            app.refresh_errors.join(" | ")
+        ))
+        .style(Style::default().fg(Color::Black).bg(Color::Red))
+    } else if app.refresh.is_some() {
+        Paragraph::new("Refreshing...").style(Style::default().fg(Color::Yellow))
+    } else {
+        return;
+    };
+    frame.render_widget(banner.alignment(Alignment::Center), area);
+}
+
+/// `label` followed by the input, with the cursor shown while editing.
+fn labelled_input(label: &str, input: &TextInput, editing: bool) -> Line<'static> {
+    let mut line = input.line(editing);
+    line.spans.insert(0, Span::raw(label.to_string()));
+    line
+}
+
+/// A Sharder & Epoch Extender input, highlighted when it has the focus.
+fn sharder_input(app: &App, field: SharderField, label: &str, input: &TextInput) -> Line<'static> {
+    let focused = app.sharder_field == field;
+    let line = labelled_input(label, input, focused && app.is_editing);
+    match (focused, app.is_editing) {
+        (true, true) => line.style(Style::default().fg(Color::Green)),
+        (true, false) => line.style(Style::default().fg(Color::Yellow)),
+        _ => line,
+    }
+}
+
+/// How long ago a panel's data was fetched.
+fn updated_ago(at: Option<Instant>) -> String {
+    let Some(at) = at else {
+        return "not loaded yet".to_string();
+    };
+    let secs = at.elapsed().as_secs();
+    match secs {
+        0..=4 => "updated just now".to_string(),
+        5..=59 => format!("updated {}s ago", secs),
+        60..=3599 => format!("updated {}m ago", secs / 60),
+        _ => format!("updated {}h ago", secs / 3600),
     }
 }
 
@@ -120,7 +223,10 @@ fn render_splash_screen(frame: &mut Frame, app: &mut App, area: Rect) {
     let details_block = Block::default()
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
-        .title("Session Details")
+        .title(format!(
+            "Session Details ({})",
+            updated_ago(app.session_updated)
+        ))
         .title_alignment(Alignment::Center)
         .padding(Padding::new(1, 1, 2, 2));
 
@@ -153,21 +259,22 @@ fn render_user_blobs(frame: &mut Frame, app: &mut App, area: Rect) {
     let selected_style = Style::default().fg(Color::Black).bg(Color::White).bold();
 
     if !app.user_blobs.is_empty() {
-        let header_cells = [
-            "Blob ID",
-            "Unencoded size",
-            "Certified",
-            "Deletable",
-            "Expiry epoch",
-            "Object ID",
-        ]
-        .iter()
-        .map(|&h| Cell::from(h).style(header_style))
-        .collect::<Vec<Cell>>();
+        let header_cells = BlobColumn::ALL
+            .iter()
+            .map(|&column| {
+                let arrow = match app.blob_sort {
+                    Some(sorted) if sorted == column && app.blob_sort_descending => " ▼",
+                    Some(sorted) if sorted == column => " ▲",
+                    _ => "",
+                };
+                Cell::from(format!("{}{}", column.name(), arrow)).style(header_style)
+            })
+            .collect::<Vec<Cell>>();
 
         let header = Row::new(header_cells).height(2);
 
-        let rows = app.user_blobs.iter().map(|data| {
+        let rows = app.blob_view.iter().map(|&i| {
+            let data = &app.user_blobs[i];
             let cells = [
                 data.blob_id.as_str(),
                 &data.unencoded_size.to_string(),
@@ -175,6 +282,10 @@ fn render_user_blobs(frame: &mut Frame, app: &mut App, area: Rect) {
                 &data.is_deletable.to_string(),
                 &data.expiration_epoch.to_string(),
                 data.object_id.as_str(),
+                app.blob_files
+                    .get(&data.blob_id)
+                    .map(String::as_str)
+                    .unwrap_or("-"),
             ]
             .iter()
             .map(|&content| Cell::from(truncate(content)))
@@ -186,12 +297,13 @@ fn render_user_blobs(frame: &mut Frame, app: &mut App, area: Rect) {
         });
 
         let widths = &[
-            Constraint::Percentage(16),
-            Constraint::Percentage(16),
-            Constraint::Percentage(16),
-            Constraint::Percentage(16),
-            Constraint::Percentage(16),
-            Constraint::Percentage(16),
+            Constraint::Percentage(18),
+            Constraint::Percentage(12),
+            Constraint::Percentage(10),
+            Constraint::Percentage(10),
+            Constraint::Percentage(12),
+            Constraint::Percentage(18),
+            Constraint::Percentage(20),
         ];
 
         let table = Table::new(rows, widths)
@@ -199,7 +311,8 @@ fn render_user_blobs(frame: &mut Frame, app: &mut App, area: Rect) {
             .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .border_style(Style::default().fg(Color::LightCyan)),
+                    .border_style(Style::default().fg(Color::LightCyan))
+                    .title_bottom(Line::from(blob_view_summary(app)).centered()),
             )
             .row_highlight_style(selected_style)
             .highlight_symbol(">> ");
@@ -223,15 +336,18 @@ fn render_user_blobs(frame: &mut Frame, app: &mut App, area: Rect) {
         let system_info_block = Block::default()
             .borders(Borders::ALL)
             .border_style(Style::default().fg(Color::LightCyan))
-            .title("System Info")
+            .title(format!(
+                "System Info ({})",
+                updated_ago(app.system_info_updated)
+            ))
             .title_alignment(Alignment::Center);
 
-        let system_info = Paragraph::new(app.walrus_system_info.clone())
+        let system_info = Paragraph::new(system_info_lines(app))
             .block(system_info_block)
             .style(Style::default().fg(Color::Yellow))
             .alignment(Alignment::Left);
 
-        if let Some(blob) = app.user_blobs.get(app.table_state.selected().unwrap_or(0)) {
+        if let Some(blob) = app.selected_blob() {
             let blob_info = vec![
                 Line::from(Span::styled(
                     format!("Blob ID: {}\n", blob.blob_id),
@@ -250,13 +366,32 @@ fn render_user_blobs(frame: &mut Frame, app: &mut App, area: Rect) {
                     Style::default().fg(Color::Yellow),
                 )),
                 Line::from(Span::styled(
-                    format!("Expiry epoch: {}\n", blob.expiration_epoch),
+                    match app
+                        .system_info
+                        .as_ref()
+                        .and_then(|info| estimate_expiry(blob.expiration_epoch, info))
+                    {
+                        Some(expiry) => {
+                            format!("Expiry epoch: {} ({})\n", blob.expiration_epoch, expiry)
+                        }
+                        None => format!("Expiry epoch: {}\n", blob.expiration_epoch),
+                    },
                     Style::default().fg(Color::Yellow),
                 )),
                 Line::from(Span::styled(
                     format!("Object ID: {}\n", blob.object_id),
                     Style::default().fg(Color::Yellow),
                 )),
+                Line::from(Span::styled(
+                    format!(
+                        "File: {}\n",
+                        app.blob_files
+                            .get(&blob.blob_id)
+                            .map(String::as_str)
+                            .unwrap_or("Not tracked")
+                    ),
+                    Style::default().fg(Color::Yellow),
+                )),
             ];
 
             let blob_block = Block::default()
@@ -280,12 +415,102 @@ fn render_user_blobs(frame: &mut Frame, app: &mut App, area: Rect) {
         frame.render_widget(title, chunks[0]);
         render_scrollbar(frame, app, chunks[0]);
     } else {
-        let text = Text::from("\n\n\nNo blobs found.");
+        let text = match app.blobs_updated {
+            None if app.refresh.is_some() => Text::from("\n\n\nLoading blobs..."),
+            None => Text::from("\n\n\nCould not load blobs, press [F5] to retry."),
+            Some(_) => Text::from("\n\n\nNo blobs found."),
+        };
         let paragraph = Paragraph::new(text).alignment(Alignment::Center);
         frame.render_widget(paragraph, area);
     }
 }
 
+/// The parsed `walrus info`, with the configured shard size checked against
+/// the maximum blob size.
+fn system_info_lines(app: &App) -> Vec<Line<'static>> {
+    let Some(info) = &app.system_info else {
+        return vec![Line::from("Not loaded yet")];
+    };
+    let known = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let unit = known(info.storage_unit_size.map(format_size));
+
+    let mut lines = vec![
+        Line::from(format!("Current epoch: {}", info.current_epoch)),
+        Line::from(format!(
+            "Epoch duration: {}",
+            known(info.epoch_duration.map(format_duration))
+        )),
+        Line::from(format!(
+            "Shards: {}",
+            known(info.n_shards.map(|n| n.to_string()))
+        )),
+        Line::from(format!(
+            "Committee size: {}",
+            known(info.committee_size.map(|n| format!("{} nodes", n)))
+        )),
+        Line::from(format!(
+            "Max blob size: {}",
+            known(info.max_blob_size.map(format_size))
+        )),
+        Line::from(format!(
+            "Storage price: {} per {} per epoch",
+            known(info.storage_price.map(format_price)),
+            unit
+        )),
+        Line::from(format!(
+            "Write price: {}",
+            known(info.write_price.map(format_price))
+        )),
+    ];
+
+    let shard_size = app.walrus_client.config.default_shard_size;
+    lines.push(match info.check_shard_size(shard_size) {
+        Ok(()) => Line::from(format!("Shard size: {}", format_size(shard_size as u64))),
+        Err(e) => Line::from(Span::styled(e, Style::default().fg(Color::Red))),
+    });
+    lines
+}
+
+fn format_duration(duration: Duration) -> String {
+    let (count, unit) = match duration.as_secs() {
+        s if s % 86400 == 0 => (s / 86400, "day"),
+        s if s % 3600 == 0 => (s / 3600, "hour"),
+        s => (s / 60, "minute"),
+    };
+    match count {
+        1 => format!("1 {}", unit),
+        n => format!("{} {}s", n, unit),
+    }
+}
+
+/// How many blobs are shown and which search, filters and sort apply.
+fn blob_view_summary(app: &App) -> String {
+    let mut parts = vec![format!(
+        " {} of {} blobs",
+        app.blob_view.len(),
+        app.user_blobs.len()
+    )];
+    if let Some(column) = app.blob_sort {
+        let order = if app.blob_sort_descending {
+            "descending"
+        } else {
+            "ascending"
+        };
+        parts.push(format!("sorted by {} {}", column.name(), order));
+    }
+    if !app.blob_search.is_empty() || app.is_editing {
+        parts.push(format!("search \"{}\"", app.blob_search));
+    }
+    if app.deletable_only {
+        parts.push("deletable only".to_string());
+    }
+    if app.expiring_only {
+        parts.push(format!("expiring within {} epochs", app.expiring_within));
+    }
+    parts.push(updated_ago(app.blobs_updated));
+    format!("{} ", parts.join(" | "))
+}
+
 fn render_scrollbar(frame: &mut Frame, app: &mut App, area: Rect) {
     frame.render_stateful_widget(
         Scrollbar::default()
@@ -327,7 +552,10 @@ fn render_uploader(frame: &mut Frame, app: &mut App, area: Rect) {
         ])
         .split(chunks[0]);
 
-    let filename_text = format!("File path: {}", app.filename);
+    let filename_text = match app.batch_files.len() {
+        0 => labelled_input("File path: ", &app.filename, app.is_editing),
+        n => Line::from(format!("Files: {} selected", n)),
+    };
     let filename_widget = Paragraph::new(filename_text)
         .style(Style::default().fg(Color::Cyan))
         .block(
@@ -339,8 +567,10 @@ fn render_uploader(frame: &mut Frame, app: &mut App, area: Rect) {
 
     frame.render_widget(filename_widget, left[0]);
 
-    let file_exists = std::path::Path::new(&app.filename).exists();
-    let file_info_text = if file_exists && !app.filename.is_empty() && !app.is_editing {
+    let file_exists = std::path::Path::new(app.filename.value()).exists();
+    let file_info_text = if !app.batch_files.is_empty() {
+        "Uploaded as a batch"
+    } else if file_exists && !app.filename.is_empty() && !app.is_editing {
         "File exists"
     } else {
         "File does not exist"
@@ -392,14 +622,19 @@ fn render_migrator(frame: &mut Frame, app: &mut App, area: Rect) {
         .constraints([Constraint::Length(5), Constraint::Length(5)])
         .split(content_chunks[0]);
 
+    let (api_key_title, api_key_color) = if app.is_editing {
+        ("Enter Pinata API Key (editing)", Color::Green)
+    } else {
+        ("Enter Pinata API Key", Color::Yellow)
+    };
     let api_key_block = Block::default()
-        .title("Enter Pinata API Key")
+        .title(api_key_title)
         .title_alignment(Alignment::Center)
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
-        .style(Style::default().fg(Color::Yellow));
+        .style(Style::default().fg(api_key_color));
 
-    let api_key_paragraph = Paragraph::new(app.pinata_api_key.clone())
+    let api_key_paragraph = Paragraph::new(app.pinata_api_key.line(app.is_editing))
         .block(api_key_block)
         .alignment(Alignment::Left)
         .wrap(Wrap { trim: false });
@@ -456,11 +691,43 @@ fn render_sharder_and_extender(frame: &mut Frame, app: &mut App, area: Rect) {
         .split(extender_area);
 
     let sharder_title = "Sharder";
-    let sharder_content = format!("File to shard: {}", app.filename);
+    let file_line = match app.batch_files.len() {
+        0 => sharder_input(app, SharderField::File, "File to shard: ", &app.filename),
+        n => Line::from(format!("Files to shard: {} selected", n)),
+    };
+    let mut sharder_content = vec![
+        file_line,
+        Line::from(""),
+        sharder_input(app, SharderField::Password, "Password: ", &app.shard_pass),
+        sharder_input(
+            app,
+            SharderField::ConfirmPassword,
+            "Confirm password: ",
+            &app.shard_pass_confirm,
+        ),
+    ];
+    sharder_content.push(if app.shard_pass.is_empty() {
+        Line::from("No password, shards are uploaded unencrypted")
+    } else if app.shard_pass.value() != app.shard_pass_confirm.value() {
+        Line::from(Span::styled(
+            "The passwords don't match",
+            Style::default().fg(Color::Red),
+        ))
+    } else {
+        Line::from(Span::styled(
+            "Shards are encrypted with this password",
+            Style::default().fg(Color::Green),
+        ))
+    });
     let sharder_status = match app.sharder_status.as_str() {
         "success" => Paragraph::new("Sharding succeeded").style(Style::default().fg(Color::Green)),
         "failure" => Paragraph::new("Sharding failed").style(Style::default().fg(Color::Red)),
-        _ => Paragraph::new("").style(Style::default().fg(Color::Yellow)),
+        "running" => {
+            Paragraph::new("Sharding... see [5] Jobs").style(Style::default().fg(Color::Yellow))
+        }
+        "cancelled" => Paragraph::new("Sharding cancelled").style(Style::default().fg(Color::Red)),
+        // Why the sharder refused to start
+        message => Paragraph::new(message.to_string()).style(Style::default().fg(Color::Red)),
     };
     let sharder_block = Block::default()
         .borders(Borders::ALL)
@@ -474,10 +741,19 @@ fn render_sharder_and_extender(frame: &mut Frame, app: &mut App, area: Rect) {
     frame.render_widget(sharder_status, sharder_chunks[1]);
 
     let extender_title = "Epoch Extender";
-    let extender_content = format!("BlobID to epoch extend: {}", app.extender_blob_id);
+    let extender_content = sharder_input(
+        app,
+        SharderField::BlobId,
+        "BlobID to epoch extend: ",
+        &app.extender_blob_id,
+    );
     let extender_status = match app.extender_status.as_str() {
         "success" => Paragraph::new("Extension succeeded").style(Style::default().fg(Color::Green)),
         "failure" => Paragraph::new("Extension failed").style(Style::default().fg(Color::Red)),
+        "running" => {
+            Paragraph::new("Extending... see [5] Jobs").style(Style::default().fg(Color::Yellow))
+        }
+        "cancelled" => Paragraph::new("Extension cancelled").style(Style::default().fg(Color::Red)),
         _ => Paragraph::new("").style(Style::default().fg(Color::Yellow)),
     };
     let extender_block = Block::default()
@@ -494,7 +770,602 @@ fn render_sharder_and_extender(frame: &mut Frame, app: &mut App, area: Rect) {
     render_footer(frame, app, chunks[1]);
 }
 
-fn render_exit_popup(frame: &mut Frame, area: Rect) {
+fn render_jobs(frame: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(90), Constraint::Percentage(10)])
+        .split(area);
+
+    if app.jobs.list.is_empty() {
+        let text = Text::from(
+            "\n\n\nNo jobs yet. Uploads, shards, extensions and migrations show up here.",
+        );
+        let paragraph = Paragraph::new(text).alignment(Alignment::Center);
+        frame.render_widget(paragraph, chunks[0]);
+        render_footer(frame, app, chunks[1]);
+        return;
+    }
+
+    let content_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .horizontal_margin(2)
+        .constraints([Constraint::Min(1), Constraint::Length(3)])
+        .split(chunks[0]);
+
+    let header_style = Style::default().fg(Color::LightCyan);
+    let selected_style = Style::default().fg(Color::Black).bg(Color::White).bold();
+
+    let header_cells = ["#", "Job", "Target", "Status", "Progress", "Elapsed"]
+        .iter()
+        .map(|&h| Cell::from(h).style(header_style))
+        .collect::<Vec<Cell>>();
+    let header = Row::new(header_cells).height(2);
+
+    let rows = app.jobs.list.iter().map(|job| {
+        let (status, color) = match &job.status {
+            JobStatus::Running if job.is_cancelling() => ("Cancelling...".to_string(), Color::Red),
+            JobStatus::Running => ("Running".to_string(), Color::Yellow),
+            JobStatus::Succeeded(_) => ("Done".to_string(), Color::Green),
+            JobStatus::Failed(e) => (format!("Failed: {}", e), Color::Red),
+            JobStatus::Cancelled => ("Cancelled".to_string(), Color::Red),
+        };
+        let progress = if job.total == 0 {
+            "-".to_string()
+        } else {
+            format!("{}/{} {}", job.done, job.total, job.kind.unit())
+        };
+
+        Row::new(vec![
+            Cell::from(job.id.to_string()),
+            Cell::from(job.kind.name()),
+            Cell::from(job.label.clone()),
+            Cell::from(status),
+            Cell::from(progress),
+            Cell::from(format_elapsed(job.elapsed())),
+        ])
+        .height(1)
+        .style(Style::default().fg(color))
+    });
+
+    let widths = &[
+        Constraint::Length(4),
+        Constraint::Percentage(10),
+        Constraint::Percentage(35),
+        Constraint::Percentage(25),
+        Constraint::Percentage(15),
+        Constraint::Percentage(10),
+    ];
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Jobs ({} running)", app.jobs.running()))
+                .title_alignment(Alignment::Center)
+                .border_style(Style::default().fg(Color::LightCyan)),
+        )
+        .row_highlight_style(selected_style)
+        .highlight_symbol(">> ");
+
+    frame.render_stateful_widget(table, content_chunks[0], &mut app.jobs.table_state);
+
+    if let Some(job) = app
+        .jobs
+        .table_state
+        .selected()
+        .and_then(|i| app.jobs.list.get(i))
+    {
+        let gauge = Gauge::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title(format!("{} {}", job.kind.name(), job.label)),
+            )
+            .gauge_style(Style::default().fg(Color::Cyan))
+            .ratio(job.ratio());
+        frame.render_widget(gauge, content_chunks[1]);
+    }
+
+    render_footer(frame, app, chunks[1]);
+}
+
+fn render_downloader(frame: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(90), Constraint::Percentage(10)])
+        .split(area);
+
+    let content_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .horizontal_margin(2)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(chunks[0]);
+
+    let tracked_files = app.tracked_files();
+    let files = app.walrus_client.config.get_files();
+    let items = std::iter::once(ListItem::new("[ Blob ID ]"))
+        .chain(tracked_files.iter().map(|name| {
+            let info = &files[name];
+            let lock = if info.is_encrypted { ", encrypted" } else { "" };
+            ListItem::new(format!("{} ({} shards{})", name, info.blobs.len(), lock))
+        }))
+        .collect::<Vec<ListItem>>();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title("Tracked files")
+                .title_alignment(Alignment::Center)
+                .border_style(Style::default().fg(Color::LightCyan)),
+        )
+        .style(Style::default().fg(Color::Yellow))
+        .highlight_style(Style::default().fg(Color::Black).bg(Color::White).bold())
+        .highlight_symbol(">> ");
+
+    frame.render_stateful_widget(list, content_chunks[0], &mut app.download_list_state);
+
+    let form = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(3),
+        ])
+        .split(content_chunks[1]);
+
+    let selected = app.selected_tracked_file();
+    let editing = |field| app.is_editing && app.download_field == field;
+    let (source_field, source) = match &selected {
+        Some(name) => (None, Line::from(format!("Tracked file: {}", name))),
+        None => (
+            Some(DownloadField::BlobId),
+            app.download_blob_id.line(editing(DownloadField::BlobId)),
+        ),
+    };
+    let default_destination = app.download_destination_for(match &selected {
+        Some(name) => name,
+        None if app.download_blob_id.is_empty() => "<blob ID>",
+        None => app.download_blob_id.value(),
+    });
+    let destination = if app.download_destination.is_empty() && !editing(DownloadField::Destination)
+    {
+        Line::from(format!("{} (default)", default_destination))
+    } else {
+        app.download_destination
+            .line(editing(DownloadField::Destination))
+    };
+    let password = match &selected {
+        Some(name) if app.walrus_client.config.get_files()[name].is_encrypted => {
+            app.download_password.line(editing(DownloadField::Password))
+        }
+        _ => Line::from("Not encrypted, no password needed"),
+    };
+
+    let inputs = [
+        (source_field, "Blob ID", source),
+        (Some(DownloadField::Destination), "Destination", destination),
+        (Some(DownloadField::Password), "Password", password),
+    ];
+    for (i, (field, title, value)) in inputs.into_iter().enumerate() {
+        let focused = field.is_some() && field == Some(app.download_field);
+        let color = match (focused, app.is_editing) {
+            (true, true) => Color::Green,
+            (true, false) => Color::Yellow,
+            _ => Color::Cyan,
+        };
+        let title = if focused && app.is_editing {
+            format!("{} (editing)", title)
+        } else {
+            title.to_string()
+        };
+        let input = Paragraph::new(value).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(title)
+                .border_style(Style::default().fg(color)),
+        );
+        frame.render_widget(input, form[i]);
+    }
+
+    if let Some(job) = app.download_job.and_then(|id| app.jobs.get(id)) {
+        let label = if job.total == 0 {
+            job.label.clone()
+        } else {
+            format!("{}/{} {}", job.done, job.total, job.kind.unit())
+        };
+        let gauge = Gauge::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title("Progress"),
+            )
+            .gauge_style(Style::default().fg(Color::Cyan))
+            .label(label)
+            .ratio(job.ratio());
+        frame.render_widget(gauge, form[3]);
+    }
+
+    let status_color = if app.download_status.starts_with("Download failed")
+        || app.download_status.starts_with("Download cancelled")
+    {
+        Color::Red
+    } else {
+        Color::Green
+    };
+    let status = Paragraph::new(app.download_status.clone())
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title("Result"),
+        )
+        .style(Style::default().fg(status_color))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(status, form[4]);
+
+    render_footer(frame, app, chunks[1]);
+}
+
+fn render_settings(frame: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(90), Constraint::Percentage(10)])
+        .split(area);
+
+    let content_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .horizontal_margin(2)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(chunks[0]);
+
+    let config = &app.walrus_client.config;
+    let rows = Setting::ALL.iter().map(|setting| {
+        let source = match config.overridden_by(*setting) {
+            Some(var) => {
+                Cell::from(format!("env {}", var)).style(Style::default().fg(Color::Magenta))
+            }
+            None => Cell::from("config file"),
+        };
+        Row::new(vec![
+            Cell::from(setting.name()),
+            Cell::from(setting_value(*setting, &setting.get(config))),
+            source,
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Percentage(35),
+            Constraint::Percentage(35),
+            Constraint::Percentage(30),
+        ],
+    )
+    .header(Row::new(vec!["Setting", "In effect", "Source"]).style(Style::default().bold()))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title("Settings")
+            .title_alignment(Alignment::Center)
+            .border_style(Style::default().fg(Color::LightCyan)),
+    )
+    .style(Style::default().fg(Color::Yellow))
+    .row_highlight_style(Style::default().fg(Color::Black).bg(Color::White).bold())
+    .highlight_symbol(">> ");
+    frame.render_stateful_widget(table, content_chunks[0], &mut app.settings_table_state);
+
+    let form = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(6),
+            Constraint::Length(3),
+        ])
+        .split(content_chunks[1]);
+
+    let setting = app.selected_setting();
+    let (title, color) = if app.is_editing {
+        (format!("{} (editing)", setting.name()), Color::Green)
+    } else {
+        (setting.name().to_string(), Color::Yellow)
+    };
+    let input = Paragraph::new(app.settings_input.line(app.is_editing)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title(title)
+            .border_style(Style::default().fg(color)),
+    );
+    frame.render_widget(input, form[0]);
+
+    let mut details = vec![
+        Line::from(setting_hint(app, setting)),
+        Line::from(""),
+        Line::from(format!(
+            "Saved in ~/.walter/config.json: {}",
+            setting_value(setting, &config.file_value(setting))
+        )),
+    ];
+    if let Some(var) = config.overridden_by(setting) {
+        details.push(Line::from(Span::styled(
+            format!(
+                "Overridden by {}={} for this session, edits are saved to the config file",
+                var,
+                setting.get(config)
+            ),
+            Style::default().fg(Color::Magenta),
+        )));
+    }
+    let details = Paragraph::new(details)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title("About"),
+        )
+        .wrap(Wrap { trim: false });
+    frame.render_widget(details, form[1]);
+
+    let status_color = if app.settings_status.starts_with("Not saved") {
+        Color::Red
+    } else {
+        Color::Green
+    };
+    let status = Paragraph::new(app.settings_status.clone())
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title("Result"),
+        )
+        .style(Style::default().fg(status_color))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(status, form[2]);
+
+    render_footer(frame, app, chunks[1]);
+}
+
+/// A setting's value for display, with shard sizes also in readable units.
+fn setting_value(setting: Setting, value: &str) -> String {
+    match (setting, value.parse()) {
+        (Setting::ShardSize, Ok(size)) => format!("{} ({})", value, format_size(size)),
+        _ => value.to_string(),
+    }
+}
+
+/// What a setting does and which values it accepts.
+fn setting_hint(app: &App, setting: Setting) -> String {
+    match setting {
+        Setting::DownloadDir => {
+            "Where downloads go when no destination is given, ~ is expanded".to_string()
+        }
+        Setting::DefaultEpochs => format!(
+            "Epochs files are stored for when uploaded, 1 to {}",
+            app.max_epochs()
+        ),
+        Setting::ShardSize => match app.system_info.as_ref().and_then(|info| info.max_blob_size) {
+            Some(max) => format!(
+                "Bytes per shard when sharding, above 0 and at most the {} maximum blob size",
+                format_size(max)
+            ),
+            None => "Bytes per shard when sharding, above 0. The maximum blob size is checked once walrus info loads".to_string(),
+        },
+        Setting::RenewEpochThreshold => format!(
+            "Blobs with this many epochs left are extended, 0 to {}",
+            MAX_EPOCHS
+        ),
+        Setting::RenewalValue => format!(
+            "Epochs a blob is extended by, 1 to {}",
+            app.max_epochs()
+        ),
+    }
+}
+
+fn render_blob_detail(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(blob) = app.selected_blob() else {
+        return;
+    };
+    let popup = centered_rect(80, 70, area);
+    frame.render_widget(Clear, popup);
+
+    let label = |name: &str| {
+        Span::styled(
+            format!("{:<18}", name),
+            Style::default().fg(Color::LightCyan),
+        )
+    };
+    let value = |text: String| Span::styled(text, Style::default().fg(Color::Yellow));
+
+    let (current_epoch, expiry) = match &app.system_info {
+        Some(info) => (
+            format!(" (current epoch {})", info.current_epoch),
+            estimate_expiry(blob.expiration_epoch, info)
+                .unwrap_or_else(|| "Unknown epoch duration".to_string()),
+        ),
+        None => (
+            String::new(),
+            "Unknown, walrus info is unavailable".to_string(),
+        ),
+    };
+
+    let mut lines = vec![
+        Line::from(vec![label("Blob ID"), value(blob.blob_id.clone())]),
+        Line::from(vec![label("Object ID"), value(blob.object_id.clone())]),
+        Line::from(vec![
+            label("File"),
+            value(
+                app.blob_files
+                    .get(&blob.blob_id)
+                    .cloned()
+                    .unwrap_or_else(|| "Not tracked".to_string()),
+            ),
+        ]),
+        Line::from(vec![label("Size"), value(blob.unencoded_size.clone())]),
+        Line::from(vec![
+            label("Certified"),
+            value(blob.is_certified.to_string()),
+        ]),
+        Line::from(vec![
+            label("Deletable"),
+            value(blob.is_deletable.to_string()),
+        ]),
+        Line::from(vec![
+            label("Expiry epoch"),
+            value(format!("{}{}", blob.expiration_epoch, current_epoch)),
+        ]),
+        Line::from(vec![label("Expires"), value(expiry)]),
+        Line::from(""),
+    ];
+
+    if let Some(job) = app.blob_action_job.and_then(|id| app.jobs.get(id)) {
+        let (text, color) = match &job.status {
+            JobStatus::Running => (format!("{}...", job.kind.name()), Color::Yellow),
+            JobStatus::Succeeded(message) => (message.clone(), Color::Green),
+            JobStatus::Failed(e) => (format!("{} failed: {}", job.kind.name(), e), Color::Red),
+            JobStatus::Cancelled => (format!("{} cancelled", job.kind.name()), Color::Red),
+        };
+        lines.push(Line::from(vec![
+            label(job.kind.name()),
+            Span::styled(text, Style::default().fg(color)),
+        ]));
+    }
+    if !app.blob_action_status.is_empty() {
+        lines.push(Line::from(Span::styled(
+            app.blob_action_status.clone(),
+            Style::default().fg(Color::White),
+        )));
+    }
+
+    lines.push(Line::from(""));
+    let mut actions = format!(
+        "[C]opy ID | [D]ownload | Ex[T]end by {} epochs (+/-) | [S]tatus",
+        app.epochs
+    );
+    if blob.is_deletable {
+        actions.push_str(" | [X] Delete");
+    }
+    actions.push_str(" | [Esc] Close");
+    lines.push(Line::from(Span::styled(
+        actions,
+        Style::default().fg(Color::Green),
+    )));
+
+    let detail = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title("Blob Details")
+                .title_alignment(Alignment::Center)
+                .border_style(Style::default().fg(Color::LightCyan))
+                .padding(Padding::new(2, 2, 1, 1)),
+        )
+        .wrap(Wrap { trim: false });
+
+    frame.render_widget(detail, popup);
+}
+
+fn render_file_picker(frame: &mut Frame, app: &mut App, area: Rect) {
+    let Some(picker) = app.file_picker.as_mut() else {
+        return;
+    };
+    let popup = centered_rect(70, 80, area);
+    frame.render_widget(Clear, popup);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .split(popup);
+
+    let filter = Paragraph::new(format!("{}_", picker.filter))
+        .style(Style::default().fg(Color::Yellow))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(format!("Filter ~ {}", picker.dir.display()))
+                .border_style(Style::default().fg(Color::LightCyan)),
+        );
+    frame.render_widget(filter, chunks[0]);
+
+    let hidden = if picker.show_hidden {
+        "shown"
+    } else {
+        "hidden"
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title("Select Files")
+        .title_alignment(Alignment::Center)
+        .title_bottom(
+            Line::from(format!(
+                " {} marked | hidden files {} ",
+                picker.marked.len(),
+                hidden
+            ))
+            .centered(),
+        )
+        .border_style(Style::default().fg(Color::LightCyan));
+
+    if let Some(error) = &picker.error {
+        let error = Paragraph::new(format!("Could not list the directory: {}", error))
+            .style(Style::default().fg(Color::Red))
+            .block(block);
+        frame.render_widget(error, chunks[1]);
+    } else {
+        let width = chunks[1].width.saturating_sub(20) as usize;
+        let items: Vec<ListItem> = picker
+            .visible()
+            .iter()
+            .map(|entry| {
+                let mark = if picker.marked.contains(&entry.path) {
+                    "[x]"
+                } else {
+                    "[ ]"
+                };
+                let (name, size, color) = if entry.is_dir {
+                    (
+                        format!("{}/", entry.name),
+                        "dir".to_string(),
+                        Color::LightCyan,
+                    )
+                } else {
+                    (entry.name.clone(), format_size(entry.size), Color::Yellow)
+                };
+                ListItem::new(format!("{} {:<width$} {:>10}", mark, name, size))
+                    .style(Style::default().fg(color))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::default().fg(Color::Black).bg(Color::White).bold())
+            .highlight_symbol(">> ");
+        frame.render_stateful_widget(list, chunks[1], &mut picker.list_state);
+    }
+
+    let help = Paragraph::new(
+        "[Enter] Open / Choose | [Space] Mark | [Left/Right] Up / Into | [Tab] Hidden | Type to Filter | [Esc] Cancel",
+    )
+    .style(Style::default().fg(Color::Green))
+    .alignment(Alignment::Center);
+    frame.render_widget(help, chunks[2]);
+}
+
+fn render_exit_popup(frame: &mut Frame, app: &App, area: Rect) {
     let popup_width = 40;
     let popup_height = 30;
     let outer_rect = centered_rect(popup_width + 2, popup_height + 2, area);
@@ -510,8 +1381,16 @@ fn render_exit_popup(frame: &mut Frame, area: Rect) {
         .border_type(BorderType::Rounded)
         .title("Confirm Exit");
 
+    let running = match app.jobs.running() {
+        0 => String::new(),
+        1 => "\n\n1 job is still running and will be stopped.".to_string(),
+        n => format!("\n\n{} jobs are still running and will be stopped.", n),
+    };
     let exit_text = Text::styled(
-        "\n\nDo you really want to exit? \n\n [Y]es / [N]o",
+        format!(
+            "\n\nDo you really want to exit? {}\n\n [Y]es / [N]o",
+            running
+        ),
         Style::default()
             .fg(Color::White)
             .add_modifier(Modifier::BOLD),
@@ -529,17 +1408,51 @@ fn render_footer(frame: &mut Frame, app: &mut App, area: Rect) {
     let instructions_block = Block::default().padding(Padding::vertical(1));
 
     let uploader_str = if app.is_editing {
-        format!("[1] Dashboard | [ESC] Exit Edit Mode | [Enter] Upload | [Up/Down] Epochs ({}) | [3] Migrator | [4] S&EE | [Q]uit", app.epochs)
+        format!(
+            "[ESC] Exit Edit Mode | [Enter] Upload | [Up/Down] Epochs ({})",
+            app.epochs
+        )
+    } else {
+        format!("[1] Dashboard | [E]dit Mode | [B]rowse | [Enter] Upload | [Up/Down] Epochs ({}) | [3] Migrator | [4] S&EE | [5] Jobs | [Q]uit", app.epochs)
+    };
+
+    let dashboard_str = if app.is_editing {
+        format!(
+            "[ESC] Clear Search | [Enter] Keep Search | [Up/Down] Select | Search: {}_",
+            app.blob_search
+        )
+    } else {
+        // The expiring filter needs the current epoch to compare against
+        let expiring = if app.system_info.is_some() {
+            format!("Expiring [W]ithin {} Epochs (+/-)", app.expiring_within)
+        } else {
+            "Expiring filter unavailable, current epoch unknown".to_string()
+        };
+        format!("[Enter] Details | [/] Search | S[o]rt | [R]everse | [D]eletable Only | {}\n[F5] Refresh | [A]uto-refresh ({}) | [2] Uploader | [3] Migrate | [4] Sharder & Epoch Extender | [5] Jobs | [6] Downloader | [7] Settings | [Q]uit", expiring, if app.auto_refresh { "on" } else { "off" })
+    };
+
+    let settings_str = if app.is_editing {
+        "[ESC] Cancel | [Enter] Save"
+    } else {
+        "[1] Dashboard | [Up/Down] Select | [Enter] or [E]dit | [5] Jobs | [6] Downloader | [Q]uit"
+    };
+
+    let downloader_str = if app.is_editing {
+        "[ESC] Exit Edit Mode | [Tab] Next Field | [Enter] Download"
     } else {
-        format!("[1] Dashboard | [E]dit Mode | [Enter] Upload | [Up/Down] Epochs ({}) | [3] Migrator | [4] S&EE | [Q]uit", app.epochs)
+        "[1] Dashboard | [Up/Down] Select | [Tab] Next Field | [E]dit | [Enter] Download | [5] Jobs | [Q]uit"
     };
 
     let content = match app.current_screen {
         CurrentScreen::Splash => "Press 'Enter' to continue",
-        CurrentScreen::Dashboard => "[2] Uploader | [3] Migrate | [4] Sharder & Epoch Extender | [Q]uit",
+        CurrentScreen::Dashboard if app.show_blob_detail => "[Up/Down] Select | [Esc] Close Details | [Q]uit",
+        CurrentScreen::Dashboard => &dashboard_str,
         CurrentScreen::Uploader => &uploader_str,
-        CurrentScreen::Migrator => "[1] Dashboard | [2] Uploader | [M]igrate | [4] Sharder & Epoch Extender | [Q]uit",
-        CurrentScreen::SharderAndEpochExtender => "[1] Dashboard | [2] Uploader | [3] Migrator | [K] Shard | Encr[Y]pt | Epoch Ex[T]end | [Q]uit",
+        CurrentScreen::Migrator => "[1] Dashboard | [2] Uploader | [E]dit API Key | [M]igrate | [X] Clear Key | [4] Sharder & Epoch Extender | [5] Jobs | [Q]uit",
+        CurrentScreen::SharderAndEpochExtender => "[1] Dashboard | [2] Uploader | [3] Migrator | [E]dit | [Tab] Next Field | [B]rowse | [K] Shard | Epoch Ex[T]end | [5] Jobs | [Q]uit",
+        CurrentScreen::Jobs => "[1] Dashboard | [2] Uploader | [3] Migrator | [4] S&EE | [6] Downloader | [7] Settings | [Up/Down] Select | [C]ancel | [Q]uit",
+        CurrentScreen::Downloader => downloader_str,
+        CurrentScreen::Settings => settings_str,
     };
 
     let instructions = Paragraph::new(Text::styled(content, Style::default().fg(Color::Green)))
@@ -1,38 +1,30 @@
 use std::{
     error::Error,
+    io::Write,
     process::{Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use walter_core::cancel::CancelToken;
+use walter_core::client::run_walrus_json_async;
+use walter_core::system_info::SystemInfo;
+use walter_core::types::{
+    BlobInfo, BlobObject, BlobStatus, InfoRequest, InfoResponse, ListBlobsRequest,
 };
-use walter_core::types::BlobInfo;
 
 pub async fn sui_active_address() -> Result<String, Box<dyn Error>> {
-    let child = Command::new("sui")
-        .arg("client")
-        .arg("active-address")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()
-        .map_err(|e| format!("Failed to start process: {}", e))?;
-
-    let output = child
-        .wait_with_output()
-        .map_err(|e| format!("Failed to wait on process: {}", e))?;
-
-    // Check for errors
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        return Err(format!("Process failed: {}", stderr).into());
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-
-    Ok(stdout)
+    Ok(tokio::task::spawn_blocking(|| sui_client("active-address")).await??)
 }
 
 pub async fn sui_active_env() -> Result<String, Box<dyn Error>> {
+    Ok(tokio::task::spawn_blocking(|| sui_client("active-env")).await??)
+}
+
+/// Runs `sui client <command>` and returns what it printed. It waits on the
+/// process, so async code runs it on the blocking pool.
+fn sui_client(command: &str) -> Result<String, String> {
     let child = Command::new("sui")
         .arg("client")
-        .arg("active-env")
+        .arg(command)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::null())
@@ -46,7 +38,7 @@ pub async fn sui_active_env() -> Result<String, Box<dyn Error>> {
     // Check for errors
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        return Err(format!("Process failed: {}", stderr).into());
+        return Err(format!("Process failed: {}", stderr));
     }
 
     let stdout = String::from_utf8_lossy(&output.stdout).to_string();
@@ -54,44 +46,54 @@ pub async fn sui_active_env() -> Result<String, Box<dyn Error>> {
     Ok(stdout)
 }
 
-pub fn parse_blob_list(stdout: &str) -> Result<Vec<BlobInfo>, Box<dyn Error>> {
-    let lines: Vec<&str> = stdout
-        .lines()
-        .map(str::trim)
-        .skip_while(|line| !line.contains("Blob ID")) // Skip until the "Blob ID" header
-        .skip(2)
-        .take_while(|line| !line.is_empty() && !line.contains("-----")) // Stop at the next empty or separator line
-        .collect();
+/// Everything the TUI shows about the session, fetched together so it can be
+/// refreshed in the background. Each part fails on its own, so a missing
+/// `sui` CLI still leaves the blobs to look at.
+pub struct Snapshot {
+    pub sui_active_address: Result<String, String>,
+    pub sui_active_env: Result<String, String>,
+    pub user_blobs: Result<Vec<BlobInfo>, String>,
+    pub system_info: Result<SystemInfo, String>,
+}
 
-    let mut blobs = Vec::new();
+pub async fn fetch_snapshot() -> Snapshot {
+    Snapshot {
+        sui_active_address: sui_active_address()
+            .await
+            .map(|address| address.trim().to_string())
+            .map_err(|e| e.to_string()),
+        sui_active_env: sui_active_env()
+            .await
+            .map(|env| env.trim().to_string())
+            .map_err(|e| e.to_string()),
+        user_blobs: walrus_list_blobs().await.map_err(|e| e.to_string()),
+        system_info: walrus_info_system().await.map_err(|e| e.to_string()),
+    }
+}
 
-    for line in lines {
-        let parts: Vec<&str> = line.trim().split_whitespace().collect();
+pub async fn walrus_list_blobs() -> Result<Vec<BlobInfo>, Box<dyn Error>> {
+    let blobs: Vec<BlobObject> =
+        run_walrus_json_async(&ListBlobsRequest::default(), &CancelToken::new()).await?;
+    Ok(blobs.iter().map(BlobInfo::from).collect())
+}
 
-        if parts.len() < 6 {
-            return Err(format!("Invalid line format: {}", line).into());
+/// What `walrus info --json` reports, with the fields it leaves out taken
+/// from the text `walrus info` prints when that has them.
+pub async fn walrus_info_system() -> Result<SystemInfo, Box<dyn Error>> {
+    let response: InfoResponse =
+        run_walrus_json_async(&InfoRequest {}, &CancelToken::new()).await?;
+    let mut info = SystemInfo::from_response(&response);
+    if info.is_partial() {
+        if let Ok(Ok(text)) = tokio::task::spawn_blocking(walrus_info_text).await {
+            info.fill_from(text);
         }
-
-        let blob_info = BlobInfo {
-            blob_id: parts[0].to_string(),
-            unencoded_size: parts[1..=2].join(" "),
-            is_certified: parts[3] == "true",
-            is_deletable: parts[4] == "true",
-            expiration_epoch: parts[5]
-                .parse()
-                .map_err(|_| format!("Failed to parse epoch: {}", parts[5]))?,
-            object_id: parts[6].to_string(),
-        };
-
-        blobs.push(blob_info);
     }
-
-    Ok(blobs)
+    Ok(info)
 }
 
-pub async fn walrus_list_blobs() -> Result<String, Box<dyn Error>> {
+fn walrus_info_text() -> Result<SystemInfo, String> {
     let output = Command::new("walrus")
-        .arg("list-blobs")
+        .arg("info")
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
         .stderr(Stdio::null())
@@ -102,36 +104,151 @@ pub async fn walrus_list_blobs() -> Result<String, Box<dyn Error>> {
         return Err(format!(
             "Process failed: {}",
             String::from_utf8_lossy(&output.stderr)
-        )
-        .into());
+        ));
     }
 
     let stdout = String::from_utf8_lossy(&output.stdout);
-    let blobs =
-        parse_blob_list(&stdout).map_err(|e| format!("Failed to parse blob list: {}", e))?;
 
-    serde_json::to_string_pretty(&blobs)
-        .map_err(|e| format!("Failed to serialize JSON: {}", e).into())
+    SystemInfo::parse(&stdout).map_err(|e| e.to_string())
 }
 
-pub async fn walrus_info_system() -> Result<String, Box<dyn Error>> {
-    let output = Command::new("walrus")
-        .arg("info")
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .output()
-        .map_err(|e| format!("Failed to start process: {}", e))?;
+/// Roughly when a blob stored until `expiry_epoch` goes away, if the epoch
+/// duration is known.
+pub fn estimate_expiry(expiry_epoch: u64, system_info: &SystemInfo) -> Option<String> {
+    let secs = system_info.time_until_epoch(expiry_epoch)?.as_secs();
+    if secs == 0 {
+        return Some("expired".to_string());
+    }
 
-    if !output.status.success() {
-        return Err(format!(
-            "Process failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        )
-        .into());
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let remaining = if secs >= 2 * 86400 {
+        format!("{} days", secs / 86400)
+    } else {
+        format!("{} hours", secs / 3600)
+    };
+    Some(format!(
+        "within {}, by {}",
+        remaining,
+        format_date(now + secs)
+    ))
+}
+
+/// `YYYY-MM-DD` of a unix timestamp, in UTC.
+pub fn format_date(unix_secs: u64) -> String {
+    // Days since 1970-01-01 to a civil date, after Howard Hinnant's algorithm
+    let z = (unix_secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+pub fn describe_blob_status(status: &BlobStatus) -> String {
+    match status {
+        BlobStatus::Nonexistent => "Nonexistent on Walrus".to_string(),
+        BlobStatus::Invalid { .. } => "Invalid, marked as inconsistent".to_string(),
+        BlobStatus::Permanent {
+            endEpoch,
+            isCertified,
+            ..
+        } => format!(
+            "Permanent, {} until epoch {}",
+            if *isCertified {
+                "certified"
+            } else {
+                "not certified"
+            },
+            endEpoch
+        ),
+        BlobStatus::Deletable {
+            deletableCounts, ..
+        } => format!(
+            "Deletable, {} of {} deletable objects certified",
+            deletableCounts.countDeletableCertified, deletableCounts.countDeletableTotal
+        ),
     }
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+/// Puts `text` on the clipboard with the first clipboard tool found, else
+/// asks the terminal to do it with an OSC 52 sequence. Returns what was used.
+pub fn copy_to_clipboard(text: &str) -> Result<&'static str, Box<dyn Error>> {
+    let tools: [(&str, &[&str]); 4] = [
+        ("pbcopy", &[]),
+        ("wl-copy", &[]),
+        ("xclip", &["-selection", "clipboard"]),
+        ("xsel", &["--clipboard", "--input"]),
+    ];
+    for (tool, args) in tools {
+        let Ok(mut child) = Command::new(tool)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            return Ok(tool);
+        }
+    }
+
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()?;
+    Ok("the terminal")
+}
 
-    Ok(stdout.to_string())
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(86399), "1970-01-01");
+        assert_eq!(format_date(951782400), "2000-02-29");
+        assert_eq!(format_date(951868800), "2000-03-01");
+        assert_eq!(format_date(1709164800), "2024-02-29");
+        assert_eq!(format_date(4107542400), "2100-03-01");
+    }
 }